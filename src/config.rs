@@ -5,6 +5,8 @@
 // you may not use this file except in compliance with the License.
 // A copy of the License has been included in the root of the repository.
 
+use std::net::IpAddr;
+
 use serde::{self, Deserialize};
 
 /// Application configuration.
@@ -18,6 +20,8 @@ pub struct Config {
     pub debug: DebugConfig,
     pub server: ServerConfig,
     pub database: DatabaseConfig,
+    /// Limits on how fast a user can submit forms. When absent, there is no limit.
+    pub rate_limit: Option<RateLimitConfig>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -31,8 +35,309 @@ pub struct AppConfig {
     /// The maximum number of teams that a user can create.
     pub max_teams_per_creator: u32,
 
+    /// The maximum number of members per team.
+    ///
+    /// Every member of a team is a voter who can't vote for it, so big teams
+    /// have an advantage. When absent, teams can be any size.
+    pub max_team_size: Option<u32>,
+
+    /// The minimum number of members a team needs before the presentations.
+    ///
+    /// The admin can't advance to the presentation while a team is smaller,
+    /// this prevents solo teams that exist only to collect votes. When
+    /// absent, a team can have any number of members.
+    pub min_team_size: Option<u32>,
+
     /// The number of coins that every user can spend on votes.
+    ///
+    /// This is stored in the database on the first run, after that the admin
+    /// can change it on the page while the event runs, and changing it here
+    /// has no effect.
     pub coins_to_spend: u32,
+
+    /// Award categories that people vote in separately, each with its own ranking.
+    ///
+    /// E.g. `categories = [{ name = "Best Design", coins_to_spend = 25 }]`.
+    /// Coins can't be moved between categories, and `coins_to_spend` is not
+    /// used. When empty (the default), there is a single vote and ranking.
+    /// The results exports and `/my-rank` show the first category.
+    #[serde(default)]
+    pub categories: Vec<Category>,
+
+    /// The email addresses of the judges, who vote separately from the crowd.
+    ///
+    /// When there are judges, the crowd vote is followed by a judging phase in
+    /// which only the judges vote, with their own coins. Their points are added
+    /// to the crowd points, see `judge_weight`. Without judges (the default),
+    /// the admin skips straight from the evaluation to the revelation.
+    #[serde(default)]
+    pub judge_emails: Vec<String>,
+
+    /// The number of coins that every judge can spend, `coins_to_spend` by default.
+    pub judge_coins_to_spend: Option<u32>,
+
+    /// How much a judge point counts compared to a crowd point, 1.0 by default.
+    ///
+    /// The weighted judge points of a team are rounded to the nearest integer.
+    /// With `categories`, the judge points count towards the first category.
+    pub judge_weight: Option<f64>,
+
+    /// The maximum number of supporters to list per team when showing results.
+    ///
+    /// The supporters who awarded the most points are listed first, the
+    /// remainder is summarized as a count. When absent, all are listed.
+    pub max_supporters_shown: Option<u32>,
+
+    /// Whether to list who voted for a team, and with how many points.
+    ///
+    /// Knowing that the vote will be public can put pressure on voters. When
+    /// disabled, teams still show their points and rank, but not who gave
+    /// them. Enabled by default.
+    #[serde(default = "default_true")]
+    pub show_supporters: bool,
+
+    /// Whether only teams that presented are eligible for a rank.
+    ///
+    /// The admin marks teams as presented during the presentation phase. When
+    /// this is enabled, teams that were not marked still get listed in the
+    /// outcome, but without a rank.
+    #[serde(default)]
+    pub require_presented_for_ranking: bool,
+
+    /// How to compute a team's total from the votes it received.
+    ///
+    /// E.g. `scoring = "sqrt-dampened"` or `scoring = { capped = 5 }`.
+    #[serde(default)]
+    pub scoring: Scoring,
+
+    /// What it costs a voter to give a team points.
+    ///
+    /// E.g. `vote_cost = "linear"`. Changing this while people are voting can
+    /// put earlier votes over the budget.
+    #[serde(default)]
+    pub vote_cost: VoteCost,
+
+    /// The most points that a voter can give a single team.
+    ///
+    /// Without it, a voter can spend the entire budget on one team. A lower
+    /// cap forces voters to spread their points. When absent, the budget is
+    /// the only limit. Zero is rejected, it would make voting impossible.
+    pub max_points_per_team: Option<u32>,
+
+    /// How to order teams that have the same number of points.
+    ///
+    /// This only affects the order in which tied teams are listed, teams with
+    /// the same number of points always share the same rank.
+    #[serde(default)]
+    pub tiebreak: Tiebreak,
+
+    /// The code points that team names, descriptions, and comments may contain.
+    ///
+    /// A list of inclusive ranges, e.g. add `[0x0900, 0x097f]` to allow
+    /// Devanagari. When absent, we allow Latin, Greek, Cyrillic, Hebrew,
+    /// Arabic, and General Punctuation. Control characters and invisible or
+    /// text direction characters are rejected regardless of this list.
+    pub allowed_unicode_blocks: Option<Vec<(u32, u32)>>,
+
+    /// Words that team names and descriptions may not contain.
+    ///
+    /// The match ignores case and accents, and also finds the word inside
+    /// longer words, so avoid words that occur in innocent names. Rejected
+    /// attempts are logged, with the email of the user.
+    #[serde(default)]
+    pub blocked_words: Vec<String>,
+
+    /// The number of teams to list per page, outside of the vote.
+    ///
+    /// With hundreds of teams, the page gets big and slow on phones. During
+    /// the vote all teams are on one page, because they are in one form. When
+    /// absent, all teams are always on one page.
+    pub teams_per_page: Option<u32>,
+
+    /// Whether to include the number of teams in the page title during registration.
+    ///
+    /// This is nice for a big screen that shows the page while people register.
+    #[serde(default)]
+    pub show_team_count_in_title: bool,
+
+    /// Whether to use a more compact layout on narrow screens such as phones.
+    ///
+    /// This tightens the spacing, shrinks the vote footer to just the coins
+    /// that are left, and enlarges the inputs so they are easier to tap.
+    #[serde(default)]
+    pub compact_layout: bool,
+
+    /// Whether team members can look up the rank of their own teams early.
+    ///
+    /// During the revelation, only the admin can see the outcome, so the ranks
+    /// can be revealed one by one. When this is enabled, members of a team can
+    /// see the rank of their own teams at `/my-rank` before it is revealed.
+    /// This spoils the suspense of the ceremony, so it is disabled by default.
+    #[serde(default)]
+    pub allow_self_rank_peek: bool,
+
+    /// Form fields to ignore when a form gets submitted.
+    ///
+    /// Unknown form fields are rejected, but some may be harmless, such as the
+    /// name of a submit button, e.g. `ignored_form_fields = ["submit"]`.
+    #[serde(default)]
+    pub ignored_form_fields: Vec<String>,
+
+    /// Whether to leave the admin out of the voting turnout.
+    ///
+    /// The admin runs the event and may or may not vote, so the turnout can
+    /// better reflect the participants when the admin is not counted.
+    #[serde(default)]
+    pub exclude_admin_from_turnout: bool,
+
+    /// Whether only the imported participants can vote.
+    ///
+    /// By default, everybody who gets through the proxy can vote, including
+    /// staff who are not participating. When enabled, only the participants
+    /// that the admin imported can vote during the evaluation. The admin is
+    /// exempt, so they can test, and judges vote as configured in `judge_emails`.
+    #[serde(default)]
+    pub restrict_vote_to_participants: bool,
+
+    /// Whether to render the cost of the recorded vote per team server-side.
+    ///
+    /// Normally the cost is only shown by the vote script. Enable this so that
+    /// people without Javascript can see how their coins are spent, at the
+    /// cost of a slightly busier page.
+    #[serde(default)]
+    pub render_vote_cost: bool,
+
+    /// The minimum number of teams needed before the admin can open the vote.
+    ///
+    /// You can't vote for your own team, so with a single team there is
+    /// nothing to vote on. When absent, the vote can open with any number.
+    pub min_teams_to_vote: Option<u32>,
+
+    /// The moment that voting closes, e.g. `2024-05-17T18:00:00+02:00`.
+    ///
+    /// This is an RFC 3339 date and time with offset, written as a TOML
+    /// datetime, without quotes. After the deadline votes are rejected, and the
+    /// evaluation advances to the next phase by itself within half a minute.
+    /// When absent, the vote stays open until the admin closes it.
+    pub evaluation_deadline: Option<toml::value::Datetime>,
+
+    /// Secret to sign links to the final standings that work without login.
+    ///
+    /// When set, the admin gets a link during the celebration that can be
+    /// shared with people who can't log in, e.g. external judges. Note that
+    /// the authenticating proxy must let `/results` through for this to work.
+    pub results_token_secret: Option<String>,
+
+    /// The number of days that a shared results link is valid, 7 by default.
+    pub results_token_valid_days: Option<u32>,
+
+    /// Whether to serve the standings at `/public` to anybody, without login.
+    ///
+    /// This is meant for a screen in the hallway that is not logged in. The
+    /// page lists only team names, ranks and points, and only from the
+    /// revelation onwards. The authenticating proxy must let `/public` through.
+    #[serde(default)]
+    pub public_results: bool,
+
+    /// Whether to show the teams in a different order to every voter.
+    ///
+    /// Voters tend to favor the first and last teams they see, shuffling
+    /// averages that out. But an order that differs from what everybody else
+    /// sees can be disorienting, e.g. with a screen reader. When disabled, the
+    /// teams are listed alphabetically during the vote, at the risk of bias
+    /// towards teams whose name starts with an A. Enabled by default.
+    #[serde(default = "default_true")]
+    pub shuffle_during_evaluation: bool,
+
+    /// Whether to list the people who voted for their own team after the vote.
+    ///
+    /// Votes for an own team are reset to zero either way. When disabled, the
+    /// "Hall of Shame" is only shown to the admin. Enabled by default.
+    #[serde(default = "default_true")]
+    pub public_cheaters: bool,
+
+    /// Whether to greet visitors with an overlay that explains the current phase.
+    ///
+    /// People can dismiss the overlay, it shows up again when the phase changes.
+    #[serde(default)]
+    pub show_onboarding: bool,
+
+    /// Whether to ask search engines not to index the pages. Enabled by default.
+    ///
+    /// This adds a `noindex` meta tag to every page, and serves a `robots.txt`
+    /// that disallows everything.
+    #[serde(default = "default_true")]
+    pub noindex: bool,
+
+    /// Secret to compute vote fingerprints with.
+    ///
+    /// When set, the page that shows a voter their vote includes a fingerprint
+    /// of it, and the admin can check fingerprints at `/verify-vote` in case
+    /// somebody disputes their vote. Use a different secret than for results.
+    pub vote_fingerprint_secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Category {
+    /// The name of the award, e.g. "Most Technical".
+    pub name: String,
+
+    /// The number of coins that every user can spend on votes in this category.
+    pub coins_to_spend: u32,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scoring {
+    /// The total is the sum of the points of all votes.
+    #[default]
+    Sum,
+
+    /// Like `Sum`, but a single vote counts for at most this many points.
+    Capped(i64),
+
+    /// Every vote counts for the square root of its points, and the total is
+    /// rounded to the nearest integer. This dampens the effect of voters who
+    /// put all of their coins on a single team.
+    SqrtDampened,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VoteCost {
+    /// Giving a team n points costs n² coins.
+    #[default]
+    Quadratic,
+
+    /// Giving a team n points costs n coins.
+    Linear,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Tiebreak {
+    /// List tied teams in the same order as they are listed in other phases.
+    None,
+
+    /// List tied teams by ascending team id, so older teams go first.
+    #[default]
+    TeamId,
+
+    /// List the team that reached its total first, first.
+    ///
+    /// Votes get replaced whenever a voter changes their vote, so this is the
+    /// team whose most recent supporting vote is the oldest.
+    Earliest,
+
+    /// List the team with the fewest members first, small teams did more with less.
+    FewestMembers,
+
+    /// List the team that the most voters gave points first.
+    MostVoters,
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -48,7 +353,14 @@ pub struct DebugConfig {
 #[derive(Debug, Deserialize)]
 pub struct ServerConfig {
     /// The interface address and port to listen on, e.g. `127.0.0.1:5591`.
-    pub listen: String,
+    ///
+    /// To listen on a Unix domain socket instead, e.g. for a proxy on the same
+    /// host, use `unix:` followed by the path, e.g. `unix:/run/hackomatic.sock`.
+    /// The socket is readable and writable by the owner and group.
+    ///
+    /// To listen on multiple addresses, e.g. on both IPv4 and IPv6, use a list,
+    /// e.g. `["127.0.0.1:5591", "[::1]:5591"]`.
+    pub listen: Listen,
 
     /// The url prefix, in case the app is not hosted at the root of a domain.
     ///
@@ -57,10 +369,118 @@ pub struct ServerConfig {
 
     /// The number of http handler threads to start.
     pub num_threads: u32,
+
+    /// The largest request body that we accept, in bytes, default 64 KiB.
+    ///
+    /// Larger requests get "413 Payload Too Large". Our forms are small, the
+    /// largest is the participant import, which fits about 2000 addresses.
+    pub max_body_bytes: Option<u64>,
+
+    /// The number of additional threads that serve only GET requests.
+    ///
+    /// Every reader thread has its own read-only database connection, so reads
+    /// do not have to wait for writes. When zero (the default), the handler
+    /// threads serve all requests.
+    #[serde(default)]
+    pub num_reader_threads: u32,
+
+    /// Log a warning for requests that take longer than this many milliseconds.
+    ///
+    /// This helps to find out whether voters are waiting on a locked database.
+    /// When absent, no warnings are logged.
+    pub slow_request_ms: Option<u64>,
+
+    /// How to format the access log, `text` (the default) or `json`.
+    #[serde(default)]
+    pub log_format: LogFormat,
+
+    /// Whether to serve metrics for Prometheus at `/metrics`, without login.
+    ///
+    /// The metrics are request counts and latencies, the phase, and the
+    /// number of teams and votes, they don't contain any email addresses.
+    #[serde(default)]
+    pub enable_metrics: bool,
+
+    /// The addresses that can read `/metrics`, e.g. `["127.0.0.1"]`.
+    ///
+    /// This is the address that connects to us, behind a proxy that is the
+    /// proxy, for every request. When absent, anybody can read the metrics.
+    pub metrics_allowed_ips: Option<Vec<IpAddr>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Listen {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Listen {
+    pub fn addresses(&self) -> &[String] {
+        match self {
+            Listen::One(address) => std::slice::from_ref(address),
+            Listen::Many(addresses) => addresses,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogFormat {
+    /// One human-readable line per request.
+    #[default]
+    Text,
+
+    /// One JSON object per line per request, for log aggregators.
+    ///
+    /// The object has the fields `request_id`, `method`, `url`, `email`,
+    /// `status`, and `duration_ms`, and `error` for internal errors.
+    Json,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RateLimitConfig {
+    /// The number of POST requests that a user can make in a quick burst.
+    pub burst: u32,
+
+    /// The number of POST requests per minute that a user can keep up.
+    ///
+    /// Once the burst is used up, this is the rate at which the user can make
+    /// requests. Requests that exceed it get a "429 Too Many Requests".
+    pub per_minute: u32,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct DatabaseConfig {
     /// Path to the database file.
     pub path: String,
+
+    /// Number of days after the celebration to keep email addresses.
+    ///
+    /// After that, on startup, every email address in the database is replaced
+    /// by a pseudonym. The outcome and the counts stay the same, but it is no
+    /// longer possible to tell who voted for what. When absent, email addresses
+    /// are kept indefinitely.
+    pub retention_days: Option<u32>,
+
+    /// How long a connection waits for a lock, in milliseconds.
+    ///
+    /// When the wait runs out, the transaction is retried, see `max_retries`.
+    /// When absent, the default is 5000.
+    pub busy_timeout_ms: Option<u32>,
+
+    /// How many times to retry a transaction when the database is locked.
+    ///
+    /// After the last retry, the request fails with "503 Service Unavailable".
+    /// When absent, the default is 5, so 6 attempts in total.
+    pub max_retries: Option<u32>,
+
+    /// Interval between WAL checkpoints, in seconds.
+    ///
+    /// SQLite checkpoints the write-ahead log by itself, but it can't reset
+    /// the log while readers are active, so over a multi-day event the `-wal`
+    /// file can grow large and slow down queries. When set, a background thread
+    /// checkpoints and truncates the log at this interval. When absent, we
+    /// leave it to SQLite. Zero is rejected, it would checkpoint in a loop.
+    pub checkpoint_interval_secs: Option<u32>,
 }
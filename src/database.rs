@@ -86,6 +86,9 @@ pub fn ensure_schema_exists(tx: &mut Transaction) -> Result<()> {
         , creator_email string  not null
         , description   string  not null
         , created_at    string  not null
+          -- Added later, see `add_teams_project_url`.
+        , project_url   string  null
+          -- Names are also unique regardless of case, see `add_teams_name_lower_index`.
         , unique (name)
         );
         "#;
@@ -104,6 +107,9 @@ pub fn ensure_schema_exists(tx: &mut Transaction) -> Result<()> {
         ( id           integer primary key
         , team_id      integer not null references teams (id)
         , member_email string  not null
+          -- Added later, see `add_team_memberships_is_captain`. One member per team is
+          -- the point of contact, 1 for the captain and 0 for everybody else.
+        , is_captain   integer not null default 0
           -- Every person can be in a given team at most once. They can be in multiple
           -- teams, and the team can have multiple members, this is only about
           -- cardinality.
@@ -126,9 +132,14 @@ pub fn ensure_schema_exists(tx: &mut Transaction) -> Result<()> {
         , voter_email string  not null
         , team_id     integer not null references teams (id)
         , points      integer not null
-          -- Every voter can vote at most once on a team. Without this, you could
-          -- sidestep the quadratic voting property.
-        , unique (voter_email, team_id)
+          -- Added later, see `migrate_votes_add_category_id`. This is the index into
+          -- the categories in the config, or 0 when there are no categories.
+        , category_id integer not null default 0
+          -- Added later, see `add_votes_created_at`. Votes cast before that have none.
+        , created_at  string  null
+          -- Every voter can vote at most once on a team per category. Without this,
+          -- you could sidestep the quadratic voting property.
+        , unique (voter_email, team_id, category_id)
         );
         "#;
     let statement = match tx.statements.entry(sql.as_ptr()) {
@@ -171,262 +182,311 @@ pub fn ensure_schema_exists(tx: &mut Transaction) -> Result<()> {
         Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
     statement.reset()?;
-    let result = match statement.next()? {
+    match statement.next()? {
         Row => panic!("Query 'ensure_schema_exists' unexpectedly returned a row."),
-        Done => (),
+        Done => {}
+    }
+
+    let sql = r#"
+        create table if not exists idempotency_keys
+        ( id              integer primary key
+        , creator_email   string  not null
+        , idempotency_key string  not null
+          -- This deliberately does not reference the teams table, a key outlives the
+          -- team it created, so a late retry after deletion does not recreate it.
+        , team_id         integer not null
+        , created_at      string  not null
+        , unique (creator_email, idempotency_key)
+        );
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
-    Ok(result)
-}
+    statement.reset()?;
+    match statement.next()? {
+        Row => panic!("Query 'ensure_schema_exists' unexpectedly returned a row."),
+        Done => {}
+    }
 
-pub fn get_current_phase(tx: &mut Transaction) -> Result<Option<String>> {
     let sql = r#"
-        select phase from progress order by id desc limit 1;
+        create table if not exists settings
+        ( id         integer primary key
+        , created_at string not null
+        , key        string not null
+        , value      string not null
+        );
         "#;
     let statement = match tx.statements.entry(sql.as_ptr()) {
         Occupied(entry) => entry.into_mut(),
         Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
     statement.reset()?;
-    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
-    let result = match statement.next()? {
-        Row => Some(decode_row(statement)?),
-        Done => None,
+    match statement.next()? {
+        Row => panic!("Query 'ensure_schema_exists' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        create table if not exists event_feedback
+        ( id           integer primary key
+        , author_email string  not null
+        , rating       integer not null
+          -- The comment is optional, but we store the empty string rather than null.
+        , comment      string  not null
+        , created_at   string  not null
+          -- Every person can give feedback once, giving it again replaces it.
+        , unique (author_email)
+        );
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
-    if result.is_some() {
-        if statement.next()? != Done {
-            panic!("Query 'get_current_phase' should return at most one row.");
-        }
+    statement.reset()?;
+    match statement.next()? {
+        Row => panic!("Query 'ensure_schema_exists' unexpectedly returned a row."),
+        Done => {}
     }
-    Ok(result)
-}
 
-pub fn set_current_phase(tx: &mut Transaction, phase: &str) -> Result<()> {
     let sql = r#"
-        insert into
-          progress (phase, created_at)
-        values
-          (:phase, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+        create table if not exists presentations
+        ( id         integer primary key
+        , team_id    integer not null references teams (id)
+        , created_at string  not null
+        , unique (team_id)
+        );
         "#;
     let statement = match tx.statements.entry(sql.as_ptr()) {
         Occupied(entry) => entry.into_mut(),
         Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
     statement.reset()?;
-    statement.bind(1, phase)?;
-    let result = match statement.next()? {
-        Row => panic!("Query 'set_current_phase' unexpectedly returned a row."),
-        Done => (),
+    match statement.next()? {
+        Row => panic!("Query 'ensure_schema_exists' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        create table if not exists judge_votes
+        ( id          integer primary key
+        , judge_email string  not null
+        , team_id     integer not null references teams (id)
+        , points      integer not null
+        , unique (judge_email, team_id)
+        );
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
-    Ok(result)
-}
+    statement.reset()?;
+    match statement.next()? {
+        Row => panic!("Query 'ensure_schema_exists' unexpectedly returned a row."),
+        Done => {}
+    }
 
-pub fn count_teams_by_creator(tx: &mut Transaction, creator_email: &str) -> Result<i64> {
     let sql = r#"
-        select count(1) from teams where creator_email = :creator_email;
+        create table if not exists participants
+        ( id         integer primary key
+        , email      string not null
+        , created_at string not null
+        , unique (email)
+        );
         "#;
     let statement = match tx.statements.entry(sql.as_ptr()) {
         Occupied(entry) => entry.into_mut(),
         Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
     statement.reset()?;
-    statement.bind(1, creator_email)?;
-    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
-    let result = match statement.next()? {
-        Row => decode_row(statement)?,
-        Done => panic!("Query 'count_teams_by_creator' should return exactly one row."),
+    match statement.next()? {
+        Row => panic!("Query 'ensure_schema_exists' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        create table if not exists favorites
+        ( id          integer primary key
+        , voter_email string  not null
+        , team_id     integer not null references teams (id)
+        , created_at  string  not null
+        , unique (voter_email, team_id)
+        );
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
-    if statement.next()? != Done {
-        panic!("Query 'count_teams_by_creator' should return exactly one row.");
+    statement.reset()?;
+    match statement.next()? {
+        Row => panic!("Query 'ensure_schema_exists' unexpectedly returned a row."),
+        Done => {}
     }
-    Ok(result)
-}
 
-pub fn add_team(
-    tx: &mut Transaction,
-    name: &str,
-    creator_email: &str,
-    description: &str,
-) -> Result<i64> {
     let sql = r#"
-        insert into
-          teams
-          ( name
-          , creator_email
-          , description
-          , created_at
-          )
-        values
-          ( :name
-          , :creator_email
-          , :description
-          , strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
-          )
-        returning
-          id;
+        create table if not exists team_feedback
+        ( id           integer primary key
+        , author_email string  not null
+        , team_id      integer not null references teams (id)
+        , body         string  not null
+        , created_at   string  not null
+          -- Every author gives a team feedback once, giving it again replaces it.
+        , unique (author_email, team_id)
+        );
         "#;
     let statement = match tx.statements.entry(sql.as_ptr()) {
         Occupied(entry) => entry.into_mut(),
         Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
     statement.reset()?;
-    statement.bind(1, name)?;
-    statement.bind(2, creator_email)?;
-    statement.bind(3, description)?;
-    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
-    let result = match statement.next()? {
-        Row => decode_row(statement)?,
-        Done => panic!("Query 'add_team' should return exactly one row."),
+    match statement.next()? {
+        Row => panic!("Query 'ensure_schema_exists' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        create table if not exists results_cache
+        ( id           integer primary key
+        , team_id      integer not null references teams (id)
+        , category_id  integer not null
+          -- Zero for teams that are not eligible for a rank.
+        , rank         integer not null
+        , total_points integer not null
+        , created_at   string  not null
+        , unique (team_id, category_id)
+        );
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
-    if statement.next()? != Done {
-        panic!("Query 'add_team' should return exactly one row.");
+    statement.reset()?;
+    match statement.next()? {
+        Row => panic!("Query 'ensure_schema_exists' unexpectedly returned a row."),
+        Done => {}
     }
-    Ok(result)
-}
 
-pub fn delete_team(tx: &mut Transaction, team_id: i64) -> Result<()> {
     let sql = r#"
-        delete from votes where team_id = :team_id;
+        create table if not exists audit_log
+        ( id          integer primary key
+        , created_at  string not null
+        , actor_email string not null
+          -- A short name for the kind of change, e.g. `phase-next`.
+        , action      string not null
+          -- What changed, for humans to read.
+        , detail      string not null
+        );
         "#;
     let statement = match tx.statements.entry(sql.as_ptr()) {
         Occupied(entry) => entry.into_mut(),
         Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
     statement.reset()?;
-    statement.bind(1, team_id)?;
     match statement.next()? {
-        Row => panic!("Query 'delete_team' unexpectedly returned a row."),
+        Row => panic!("Query 'ensure_schema_exists' unexpectedly returned a row."),
         Done => {}
     }
 
     let sql = r#"
-        delete from teams where id = :team_id;
+        create table if not exists cheat_attempts
+        ( id         integer primary key
+        , created_at string not null
+        );
         "#;
     let statement = match tx.statements.entry(sql.as_ptr()) {
         Occupied(entry) => entry.into_mut(),
         Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
     statement.reset()?;
-    statement.bind(1, team_id)?;
     let result = match statement.next()? {
-        Row => panic!("Query 'delete_team' unexpectedly returned a row."),
+        Row => panic!("Query 'ensure_schema_exists' unexpectedly returned a row."),
         Done => (),
     };
     Ok(result)
 }
 
-pub fn add_team_member(tx: &mut Transaction, team_id: i64, member_email: &str) -> Result<()> {
+/// Return whether the table has a column with the given name.
+/// The tables are created with all columns when they don't exist, but columns
+/// that were added later need to be added to databases created before that.
+pub fn has_column(tx: &mut Transaction, table_name: &str, column_name: &str) -> Result<i64> {
     let sql = r#"
-        insert into
-          team_memberships
-          ( team_id
-          , member_email
-          )
-        values
-          ( :team_id
-          , :member_email
-          )
-        on conflict
-          -- If the user is already a member, making them a member again does nothing.
-          do nothing
-        ;
+        select count(1) from pragma_table_info(:table_name) where name = :column_name;
         "#;
     let statement = match tx.statements.entry(sql.as_ptr()) {
         Occupied(entry) => entry.into_mut(),
         Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
     statement.reset()?;
-    statement.bind(1, team_id)?;
-    statement.bind(2, member_email)?;
+    statement.bind(1, table_name)?;
+    statement.bind(2, column_name)?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
     let result = match statement.next()? {
-        Row => panic!("Query 'add_team_member' unexpectedly returned a row."),
-        Done => (),
+        Row => decode_row(statement)?,
+        Done => panic!("Query 'has_column' should return exactly one row."),
     };
+    if statement.next()? != Done {
+        panic!("Query 'has_column' should return exactly one row.");
+    }
     Ok(result)
 }
 
-pub fn remove_team_member(tx: &mut Transaction, team_id: i64, member_email: &str) -> Result<()> {
+pub fn add_teams_project_url(tx: &mut Transaction) -> Result<()> {
     let sql = r#"
-        delete from
-          team_memberships
-        where
-          team_id = :team_id and member_email = :member_email;
+        alter table teams add column project_url string null;
         "#;
     let statement = match tx.statements.entry(sql.as_ptr()) {
         Occupied(entry) => entry.into_mut(),
         Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
     statement.reset()?;
-    statement.bind(1, team_id)?;
-    statement.bind(2, member_email)?;
     let result = match statement.next()? {
-        Row => panic!("Query 'remove_team_member' unexpectedly returned a row."),
+        Row => panic!("Query 'add_teams_project_url' unexpectedly returned a row."),
         Done => (),
     };
     Ok(result)
 }
 
-#[derive(Debug)]
-pub struct Team {
-    pub id: i64,
-    pub name: String,
-    pub creator_email: String,
-    pub description: String,
-}
-
-pub fn iter_teams<'i, 't, 'a>(tx: &'i mut Transaction<'t, 'a>) -> Result<Iter<'i, 'a, Team>> {
+pub fn has_index(tx: &mut Transaction, index_name: &str) -> Result<i64> {
     let sql = r#"
-        select
-            id
-          , name
-          , creator_email
-          , description
-          -- Previously we selected the members as well here with string_agg, but that
-          -- is not supported by the version of SQLite that Ubuntu ships :'(.
-        from
-          teams
-        order by
-          id desc;
+        select count(1) from sqlite_master where type = 'index' and name = :index_name;
         "#;
     let statement = match tx.statements.entry(sql.as_ptr()) {
         Occupied(entry) => entry.into_mut(),
         Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
     statement.reset()?;
-    let decode_row = |statement: &Statement| {
-        Ok(Team {
-            id: statement.read(0)?,
-            name: statement.read(1)?,
-            creator_email: statement.read(2)?,
-            description: statement.read(3)?,
-        })
-    };
-    let result = Iter {
-        statement,
-        decode_row,
+    statement.bind(1, index_name)?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => decode_row(statement)?,
+        Done => panic!("Query 'has_index' should return exactly one row."),
     };
+    if statement.next()? != Done {
+        panic!("Query 'has_index' should return exactly one row.");
+    }
     Ok(result)
 }
 
-pub fn iter_team_members<'i, 't, 'a>(
+/// Return the names of teams that differ only in case, one group per row.
+/// These prevent `add_teams_name_lower_index`.
+pub fn iter_case_colliding_team_names<'i, 't, 'a>(
     tx: &'i mut Transaction<'t, 'a>,
-    team_id: i64,
 ) -> Result<Iter<'i, 'a, String>> {
     let sql = r#"
         select
-          member_email
+          group_concat(name, ', ')
         from
-          team_memberships
-        where
-          team_id = :team_id
-        order by
-          id asc;
+          teams
+        group by
+          lower(name)
+        having
+          count(1) > 1;
         "#;
     let statement = match tx.statements.entry(sql.as_ptr()) {
         Occupied(entry) => entry.into_mut(),
         Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
     statement.reset()?;
-    statement.bind(1, team_id)?;
     let decode_row = |statement: &Statement| Ok(statement.read(0)?);
     let result = Iter {
         statement,
@@ -435,180 +495,199 @@ pub fn iter_team_members<'i, 't, 'a>(
     Ok(result)
 }
 
-pub fn iter_member_teams<'i, 't, 'a>(
-    tx: &'i mut Transaction<'t, 'a>,
-    member_email: &str,
-) -> Result<Iter<'i, 'a, i64>> {
+/// Make team names unique regardless of case, so we can't have both "DreamTeam"
+/// and "dreamteam". Note that SQLite's `lower` only folds ASCII.
+pub fn add_teams_name_lower_index(tx: &mut Transaction) -> Result<()> {
     let sql = r#"
-        select
-          team_id
-        from
-          team_memberships
-        where
-          member_email = :member_email;
+        create unique index teams_name_lower on teams (lower(name));
         "#;
     let statement = match tx.statements.entry(sql.as_ptr()) {
         Occupied(entry) => entry.into_mut(),
         Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
     statement.reset()?;
-    statement.bind(1, member_email)?;
-    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
-    let result = Iter {
-        statement,
-        decode_row,
+    let result = match statement.next()? {
+        Row => panic!("Query 'add_teams_name_lower_index' unexpectedly returned a row."),
+        Done => (),
     };
     Ok(result)
 }
 
-pub fn set_cheater(tx: &mut Transaction, email: &str) -> Result<()> {
+/// SQLite can't add a column with a non-constant default, so the existing votes
+/// get null, and `insert_vote` sets the time for new ones.
+pub fn add_votes_created_at(tx: &mut Transaction) -> Result<()> {
     let sql = r#"
-        insert into
-          cheaters (cheater_email, created_at)
-        values
-          (:email, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
-        on conflict
-          do nothing;
+        alter table votes add column created_at string null;
         "#;
     let statement = match tx.statements.entry(sql.as_ptr()) {
         Occupied(entry) => entry.into_mut(),
         Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
     statement.reset()?;
-    statement.bind(1, email)?;
     let result = match statement.next()? {
-        Row => panic!("Query 'set_cheater' unexpectedly returned a row."),
+        Row => panic!("Query 'add_votes_created_at' unexpectedly returned a row."),
         Done => (),
     };
     Ok(result)
 }
 
-pub fn iter_cheaters<'i, 't, 'a>(tx: &'i mut Transaction<'t, 'a>) -> Result<Iter<'i, 'a, String>> {
+/// Before there were captains, the first member to join is the best guess.
+pub fn add_team_memberships_is_captain(tx: &mut Transaction) -> Result<()> {
     let sql = r#"
-        select cheater_email from cheaters;
+        alter table team_memberships add column is_captain integer not null default 0;
         "#;
     let statement = match tx.statements.entry(sql.as_ptr()) {
         Occupied(entry) => entry.into_mut(),
         Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
     statement.reset()?;
-    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
-    let result = Iter {
-        statement,
-        decode_row,
-    };
-    Ok(result)
-}
+    match statement.next()? {
+        Row => panic!("Query 'add_team_memberships_is_captain' unexpectedly returned a row."),
+        Done => {}
+    }
 
-pub fn delete_votes_for_voter(tx: &mut Transaction, voter_email: &str) -> Result<()> {
     let sql = r#"
-        delete from
-          votes
+        update
+          team_memberships
+        set
+          is_captain = 1
         where
-          voter_email = :voter_email;
+          id in (select min(id) from team_memberships group by team_id);
         "#;
     let statement = match tx.statements.entry(sql.as_ptr()) {
         Occupied(entry) => entry.into_mut(),
         Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
     statement.reset()?;
-    statement.bind(1, voter_email)?;
     let result = match statement.next()? {
-        Row => panic!("Query 'delete_votes_for_voter' unexpectedly returned a row."),
+        Row => panic!("Query 'add_team_memberships_is_captain' unexpectedly returned a row."),
         Done => (),
     };
     Ok(result)
 }
 
-pub fn insert_vote(
-    tx: &mut Transaction,
-    voter_email: &str,
-    team_id: i64,
-    points: i64,
-) -> Result<()> {
+/// Add the category column to the votes table. This also changes the unique
+/// constraint, and SQLite can't alter constraints, so we rebuild the table.
+pub fn migrate_votes_add_category_id(tx: &mut Transaction) -> Result<()> {
+    let sql = r#"
+        create table votes_new
+        ( id          integer primary key
+        , voter_email string  not null
+        , team_id     integer not null references teams (id)
+        , points      integer not null
+        , category_id integer not null default 0
+        , unique (voter_email, team_id, category_id)
+        );
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    match statement.next()? {
+        Row => panic!("Query 'migrate_votes_add_category_id' unexpectedly returned a row."),
+        Done => {}
+    }
+
     let sql = r#"
         insert into
-          votes (voter_email, team_id, points)
-        values
-          (:voter_email, :team_id, :points);
+          votes_new (id, voter_email, team_id, points)
+        select
+          id, voter_email, team_id, points
+        from
+          votes;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    match statement.next()? {
+        Row => panic!("Query 'migrate_votes_add_category_id' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        drop table votes;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    match statement.next()? {
+        Row => panic!("Query 'migrate_votes_add_category_id' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        alter table votes_new rename to votes;
         "#;
     let statement = match tx.statements.entry(sql.as_ptr()) {
         Occupied(entry) => entry.into_mut(),
         Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
     statement.reset()?;
-    statement.bind(1, voter_email)?;
-    statement.bind(2, team_id)?;
-    statement.bind(3, points)?;
     let result = match statement.next()? {
-        Row => panic!("Query 'insert_vote' unexpectedly returned a row."),
+        Row => panic!("Query 'migrate_votes_add_category_id' unexpectedly returned a row."),
         Done => (),
     };
     Ok(result)
 }
 
-#[derive(Debug)]
-pub struct Vote {
-    pub points: i64,
-    pub voter_email: String,
+pub fn get_current_phase(tx: &mut Transaction) -> Result<Option<String>> {
+    let sql = r#"
+        select phase from progress order by id desc limit 1;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => Some(decode_row(statement)?),
+        Done => None,
+    };
+    if result.is_some() {
+        if statement.next()? != Done {
+            panic!("Query 'get_current_phase' should return at most one row.");
+        }
+    }
+    Ok(result)
 }
 
-pub fn iter_team_votes<'i, 't, 'a>(
-    tx: &'i mut Transaction<'t, 'a>,
-    team_id: i64,
-) -> Result<Iter<'i, 'a, Vote>> {
+pub fn set_current_phase(tx: &mut Transaction, phase: &str) -> Result<()> {
     let sql = r#"
-        select
-            points
-          , voter_email
-        from
-          votes
-        where
-          team_id = :team_id
-        order by
-          points desc,
-          voter_email asc;
+        insert into
+          progress (phase, created_at)
+        values
+          (:phase, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
         "#;
     let statement = match tx.statements.entry(sql.as_ptr()) {
         Occupied(entry) => entry.into_mut(),
         Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
     statement.reset()?;
-    statement.bind(1, team_id)?;
-    let decode_row = |statement: &Statement| {
-        Ok(Vote {
-            points: statement.read(0)?,
-            voter_email: statement.read(1)?,
-        })
-    };
-    let result = Iter {
-        statement,
-        decode_row,
+    statement.bind(1, phase)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'set_current_phase' unexpectedly returned a row."),
+        Done => (),
     };
     Ok(result)
 }
 
-/// Return how many points the voter gave to the given team.
-pub fn get_team_vote_for(
-    tx: &mut Transaction,
-    team_id: i64,
-    voter_email: &str,
-) -> Result<Option<i64>> {
+/// Return the most recent value of a setting that the admin can change at runtime.
+pub fn get_setting(tx: &mut Transaction, key: &str) -> Result<Option<String>> {
     let sql = r#"
-        select
-          points
-        from
-          votes
-        where
-          (team_id = :team_id) and (voter_email = :voter_email);
+        select value from settings where key = :key order by id desc limit 1;
         "#;
     let statement = match tx.statements.entry(sql.as_ptr()) {
         Occupied(entry) => entry.into_mut(),
         Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
     };
     statement.reset()?;
-    statement.bind(1, team_id)?;
-    statement.bind(2, voter_email)?;
+    statement.bind(1, key)?;
     let decode_row = |statement: &Statement| Ok(statement.read(0)?);
     let result = match statement.next()? {
         Row => Some(decode_row(statement)?),
@@ -616,19 +695,36 @@ pub fn get_team_vote_for(
     };
     if result.is_some() {
         if statement.next()? != Done {
-            panic!("Query 'get_team_vote_for' should return at most one row.");
+            panic!("Query 'get_setting' should return at most one row.");
         }
     }
     Ok(result)
 }
 
-/// Return the number of users who voted.
-pub fn count_voters(tx: &mut Transaction) -> Result<i64> {
+pub fn set_setting(tx: &mut Transaction, key: &str, value: &str) -> Result<()> {
     let sql = r#"
-        select
-          count(distinct voter_email)
-        from
-          votes;
+        insert into
+          settings (key, value, created_at)
+        values
+          (:key, :value, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, key)?;
+    statement.bind(2, value)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'set_setting' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+pub fn count_teams(tx: &mut Transaction) -> Result<i64> {
+    let sql = r#"
+        select count(1) from teams;
         "#;
     let statement = match tx.statements.entry(sql.as_ptr()) {
         Occupied(entry) => entry.into_mut(),
@@ -638,14 +734,2314 @@ pub fn count_voters(tx: &mut Transaction) -> Result<i64> {
     let decode_row = |statement: &Statement| Ok(statement.read(0)?);
     let result = match statement.next()? {
         Row => decode_row(statement)?,
-        Done => panic!("Query 'count_voters' should return exactly one row."),
+        Done => panic!("Query 'count_teams' should return exactly one row."),
     };
     if statement.next()? != Done {
-        panic!("Query 'count_voters' should return exactly one row.");
+        panic!("Query 'count_teams' should return exactly one row.");
+    }
+    Ok(result)
+}
+
+pub fn count_teams_by_name(tx: &mut Transaction, name: &str) -> Result<i64> {
+    let sql = r#"
+        select count(1) from teams where lower(name) = lower(:name);
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, name)?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => decode_row(statement)?,
+        Done => panic!("Query 'count_teams_by_name' should return exactly one row."),
+    };
+    if statement.next()? != Done {
+        panic!("Query 'count_teams_by_name' should return exactly one row.");
+    }
+    Ok(result)
+}
+
+pub fn count_teams_by_creator(tx: &mut Transaction, creator_email: &str) -> Result<i64> {
+    let sql = r#"
+        select count(1) from teams where creator_email = :creator_email;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, creator_email)?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => decode_row(statement)?,
+        Done => panic!("Query 'count_teams_by_creator' should return exactly one row."),
+    };
+    if statement.next()? != Done {
+        panic!("Query 'count_teams_by_creator' should return exactly one row.");
     }
     Ok(result)
 }
 
+pub fn add_team(
+    tx: &mut Transaction,
+    name: &str,
+    creator_email: &str,
+    description: &str,
+    project_url: Option<&str>,
+) -> Result<i64> {
+    let sql = r#"
+        insert into
+          teams
+          ( name
+          , creator_email
+          , description
+          , project_url
+          , created_at
+          )
+        values
+          ( :name
+          , :creator_email
+          , :description
+          , :project_url
+          , strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+          )
+        returning
+          id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, name)?;
+    statement.bind(2, creator_email)?;
+    statement.bind(3, description)?;
+    statement.bind(4, project_url)?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => decode_row(statement)?,
+        Done => panic!("Query 'add_team' should return exactly one row."),
+    };
+    if statement.next()? != Done {
+        panic!("Query 'add_team' should return exactly one row.");
+    }
+    Ok(result)
+}
+
+/// Return the team that was created by an earlier submission with the same key.
+pub fn get_idempotent_team(
+    tx: &mut Transaction,
+    creator_email: &str,
+    idempotency_key: &str,
+) -> Result<Option<i64>> {
+    let sql = r#"
+        select
+          team_id
+        from
+          idempotency_keys
+        where
+          (creator_email = :creator_email) and (idempotency_key = :idempotency_key);
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, creator_email)?;
+    statement.bind(2, idempotency_key)?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => Some(decode_row(statement)?),
+        Done => None,
+    };
+    if result.is_some() {
+        if statement.next()? != Done {
+            panic!("Query 'get_idempotent_team' should return at most one row.");
+        }
+    }
+    Ok(result)
+}
+
+pub fn add_idempotency_key(
+    tx: &mut Transaction,
+    creator_email: &str,
+    idempotency_key: &str,
+    team_id: i64,
+) -> Result<()> {
+    let sql = r#"
+        insert into
+          idempotency_keys
+          ( creator_email
+          , idempotency_key
+          , team_id
+          , created_at
+          )
+        values
+          ( :creator_email
+          , :idempotency_key
+          , :team_id
+          , strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+          );
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, creator_email)?;
+    statement.bind(2, idempotency_key)?;
+    statement.bind(3, team_id)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'add_idempotency_key' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+pub fn delete_team(tx: &mut Transaction, team_id: i64) -> Result<()> {
+    let sql = r#"
+        delete from votes where team_id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    match statement.next()? {
+        Row => panic!("Query 'delete_team' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        delete from judge_votes where team_id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    match statement.next()? {
+        Row => panic!("Query 'delete_team' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        delete from results_cache where team_id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    match statement.next()? {
+        Row => panic!("Query 'delete_team' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        delete from favorites where team_id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    match statement.next()? {
+        Row => panic!("Query 'delete_team' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        delete from team_feedback where team_id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    match statement.next()? {
+        Row => panic!("Query 'delete_team' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        delete from presentations where team_id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    match statement.next()? {
+        Row => panic!("Query 'delete_team' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        delete from teams where id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'delete_team' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+pub fn delete_team_cascade(tx: &mut Transaction, team_id: i64) -> Result<()> {
+    let sql = r#"
+        delete from votes where team_id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    match statement.next()? {
+        Row => panic!("Query 'delete_team_cascade' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        delete from judge_votes where team_id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    match statement.next()? {
+        Row => panic!("Query 'delete_team_cascade' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        delete from results_cache where team_id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    match statement.next()? {
+        Row => panic!("Query 'delete_team_cascade' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        delete from favorites where team_id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    match statement.next()? {
+        Row => panic!("Query 'delete_team_cascade' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        delete from team_feedback where team_id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    match statement.next()? {
+        Row => panic!("Query 'delete_team_cascade' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        delete from presentations where team_id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    match statement.next()? {
+        Row => panic!("Query 'delete_team_cascade' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        delete from team_memberships where team_id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    match statement.next()? {
+        Row => panic!("Query 'delete_team_cascade' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        delete from teams where id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'delete_team_cascade' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+pub fn add_team_member(tx: &mut Transaction, team_id: i64, member_email: &str) -> Result<()> {
+    let sql = r#"
+        insert into
+          team_memberships
+          ( team_id
+          , member_email
+          )
+        values
+          ( :team_id
+          , :member_email
+          )
+        on conflict
+          -- If the user is already a member, making them a member again does nothing.
+          do nothing
+        ;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    statement.bind(2, member_email)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'add_team_member' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+pub fn remove_team_member(tx: &mut Transaction, team_id: i64, member_email: &str) -> Result<()> {
+    let sql = r#"
+        delete from
+          team_memberships
+        where
+          team_id = :team_id and member_email = :member_email;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    statement.bind(2, member_email)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'remove_team_member' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+#[derive(Debug)]
+pub struct Team {
+    pub id: i64,
+    pub name: String,
+    pub creator_email: String,
+    pub description: String,
+    pub project_url: Option<String>,
+    pub created_at: String,
+}
+
+pub fn iter_teams<'i, 't, 'a>(tx: &'i mut Transaction<'t, 'a>) -> Result<Iter<'i, 'a, Team>> {
+    let sql = r#"
+        select
+            id
+          , name
+          , creator_email
+          , description
+          , project_url
+          , created_at
+          -- Previously we selected the members as well here with string_agg, but that
+          -- is not supported by the version of SQLite that Ubuntu ships :'(.
+        from
+          teams
+        order by
+          id desc;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let decode_row = |statement: &Statement| {
+        Ok(Team {
+            id: statement.read(0)?,
+            name: statement.read(1)?,
+            creator_email: statement.read(2)?,
+            description: statement.read(3)?,
+            project_url: statement.read(4)?,
+            created_at: statement.read(5)?,
+        })
+    };
+    let result = Iter {
+        statement,
+        decode_row,
+    };
+    Ok(result)
+}
+
+pub fn get_team(tx: &mut Transaction, team_id: i64) -> Result<Option<Team>> {
+    let sql = r#"
+        select
+            id
+          , name
+          , creator_email
+          , description
+          , project_url
+          , created_at
+        from
+          teams
+        where
+          id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    let decode_row = |statement: &Statement| {
+        Ok(Team {
+            id: statement.read(0)?,
+            name: statement.read(1)?,
+            creator_email: statement.read(2)?,
+            description: statement.read(3)?,
+            project_url: statement.read(4)?,
+            created_at: statement.read(5)?,
+        })
+    };
+    let result = match statement.next()? {
+        Row => Some(decode_row(statement)?),
+        Done => None,
+    };
+    if result.is_some() {
+        if statement.next()? != Done {
+            panic!("Query 'get_team' should return at most one row.");
+        }
+    }
+    Ok(result)
+}
+
+pub fn set_team_creator(tx: &mut Transaction, team_id: i64, creator_email: &str) -> Result<()> {
+    let sql = r#"
+        update teams set creator_email = :creator_email where id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, creator_email)?;
+    statement.bind(2, team_id)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'set_team_creator' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+pub fn update_team(
+    tx: &mut Transaction,
+    team_id: i64,
+    name: &str,
+    description: &str,
+) -> Result<()> {
+    let sql = r#"
+        update teams set name = :name, description = :description where id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, name)?;
+    statement.bind(2, description)?;
+    statement.bind(3, team_id)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'update_team' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+#[derive(Debug)]
+pub struct TeamMember {
+    pub member_email: String,
+    pub is_captain: i64,
+}
+
+pub fn iter_team_members<'i, 't, 'a>(
+    tx: &'i mut Transaction<'t, 'a>,
+    team_id: i64,
+) -> Result<Iter<'i, 'a, TeamMember>> {
+    let sql = r#"
+        select
+            member_email
+          , is_captain
+        from
+          team_memberships
+        where
+          team_id = :team_id
+        order by
+          id asc;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    let decode_row = |statement: &Statement| {
+        Ok(TeamMember {
+            member_email: statement.read(0)?,
+            is_captain: statement.read(1)?,
+        })
+    };
+    let result = Iter {
+        statement,
+        decode_row,
+    };
+    Ok(result)
+}
+
+/// Make the member the captain, and the other members not.
+pub fn set_team_captain(tx: &mut Transaction, team_id: i64, member_email: &str) -> Result<()> {
+    let sql = r#"
+        update
+          team_memberships
+        set
+          is_captain = (member_email = :member_email)
+        where
+          team_id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, member_email)?;
+    statement.bind(2, team_id)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'set_team_captain' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+#[derive(Debug)]
+pub struct TeamSize {
+    pub team_name: String,
+    pub n_members: i64,
+}
+
+/// Return the teams with fewer than `min_size` members, smallest first.
+pub fn iter_teams_below_size<'i, 't, 'a>(
+    tx: &'i mut Transaction<'t, 'a>,
+    min_size: i64,
+) -> Result<Iter<'i, 'a, TeamSize>> {
+    let sql = r#"
+        select
+            teams.name as team_name
+          , count(team_memberships.id) as n_members
+        from
+          teams left join team_memberships on teams.id = team_memberships.team_id
+        group by
+          teams.id
+        having
+          count(team_memberships.id) < :min_size
+        order by
+          n_members asc,
+          teams.name asc;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, min_size)?;
+    let decode_row = |statement: &Statement| {
+        Ok(TeamSize {
+            team_name: statement.read(0)?,
+            n_members: statement.read(1)?,
+        })
+    };
+    let result = Iter {
+        statement,
+        decode_row,
+    };
+    Ok(result)
+}
+
+pub fn iter_member_teams<'i, 't, 'a>(
+    tx: &'i mut Transaction<'t, 'a>,
+    member_email: &str,
+) -> Result<Iter<'i, 'a, i64>> {
+    let sql = r#"
+        select
+          team_id
+        from
+          team_memberships
+        where
+          member_email = :member_email;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, member_email)?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = Iter {
+        statement,
+        decode_row,
+    };
+    Ok(result)
+}
+
+pub fn set_team_presented(tx: &mut Transaction, team_id: i64) -> Result<()> {
+    let sql = r#"
+        insert into
+          presentations (team_id, created_at)
+        values
+          (:team_id, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+        on conflict
+          do nothing;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'set_team_presented' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+pub fn unset_team_presented(tx: &mut Transaction, team_id: i64) -> Result<()> {
+    let sql = r#"
+        delete from presentations where team_id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'unset_team_presented' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+pub fn iter_presented_teams<'i, 't, 'a>(
+    tx: &'i mut Transaction<'t, 'a>,
+) -> Result<Iter<'i, 'a, i64>> {
+    let sql = r#"
+        select team_id from presentations;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = Iter {
+        statement,
+        decode_row,
+    };
+    Ok(result)
+}
+
+pub fn add_favorite(tx: &mut Transaction, voter_email: &str, team_id: i64) -> Result<()> {
+    let sql = r#"
+        insert into
+          favorites (voter_email, team_id, created_at)
+        values
+          (:voter_email, :team_id, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+        on conflict
+          do nothing;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, voter_email)?;
+    statement.bind(2, team_id)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'add_favorite' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+pub fn remove_favorite(tx: &mut Transaction, voter_email: &str, team_id: i64) -> Result<()> {
+    let sql = r#"
+        delete from favorites where voter_email = :voter_email and team_id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, voter_email)?;
+    statement.bind(2, team_id)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'remove_favorite' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+pub fn iter_favorites<'i, 't, 'a>(
+    tx: &'i mut Transaction<'t, 'a>,
+    voter_email: &str,
+) -> Result<Iter<'i, 'a, i64>> {
+    let sql = r#"
+        select team_id from favorites where voter_email = :voter_email;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, voter_email)?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = Iter {
+        statement,
+        decode_row,
+    };
+    Ok(result)
+}
+
+pub fn add_participant(tx: &mut Transaction, email: &str) -> Result<()> {
+    let sql = r#"
+        insert into
+          participants (email, created_at)
+        values
+          (:email, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+        on conflict
+          do nothing;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, email)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'add_participant' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+pub fn count_participants(tx: &mut Transaction) -> Result<i64> {
+    let sql = r#"
+        select count(1) from participants;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => decode_row(statement)?,
+        Done => panic!("Query 'count_participants' should return exactly one row."),
+    };
+    if statement.next()? != Done {
+        panic!("Query 'count_participants' should return exactly one row.");
+    }
+    Ok(result)
+}
+
+pub fn is_participant(tx: &mut Transaction, email: &str) -> Result<i64> {
+    let sql = r#"
+        select count(1) from participants where lower(email) = lower(:email);
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, email)?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => decode_row(statement)?,
+        Done => panic!("Query 'is_participant' should return exactly one row."),
+    };
+    if statement.next()? != Done {
+        panic!("Query 'is_participant' should return exactly one row.");
+    }
+    Ok(result)
+}
+
+/// Return the imported participants who have not voted, see `add_participant`.
+/// Emails are compared case-insensitively, like in `is_participant`.
+pub fn iter_non_voters<'i, 't, 'a>(
+    tx: &'i mut Transaction<'t, 'a>,
+) -> Result<Iter<'i, 'a, String>> {
+    let sql = r#"
+        select
+          email
+        from
+          participants
+        where
+          lower(email) not in (select lower(voter_email) from votes)
+        order by
+          email asc;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = Iter {
+        statement,
+        decode_row,
+    };
+    Ok(result)
+}
+
+pub fn append_audit(
+    tx: &mut Transaction,
+    actor_email: &str,
+    action: &str,
+    detail: &str,
+) -> Result<()> {
+    let sql = r#"
+        insert into
+          audit_log (actor_email, action, detail, created_at)
+        values
+          (:actor_email, :action, :detail, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, actor_email)?;
+    statement.bind(2, action)?;
+    statement.bind(3, detail)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'append_audit' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+#[derive(Debug)]
+pub struct AuditEntry {
+    pub created_at: String,
+    pub actor_email: String,
+    pub action: String,
+    pub detail: String,
+}
+
+pub fn iter_audit_log<'i, 't, 'a>(
+    tx: &'i mut Transaction<'t, 'a>,
+) -> Result<Iter<'i, 'a, AuditEntry>> {
+    let sql = r#"
+        select
+            created_at
+          , actor_email
+          , action
+          , detail
+        from
+          audit_log
+        order by
+          id desc;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let decode_row = |statement: &Statement| {
+        Ok(AuditEntry {
+            created_at: statement.read(0)?,
+            actor_email: statement.read(1)?,
+            action: statement.read(2)?,
+            detail: statement.read(3)?,
+        })
+    };
+    let result = Iter {
+        statement,
+        decode_row,
+    };
+    Ok(result)
+}
+
+pub fn set_cheater(tx: &mut Transaction, email: &str) -> Result<()> {
+    let sql = r#"
+        insert into
+          cheaters (cheater_email, created_at)
+        values
+          (:email, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+        on conflict
+          do nothing;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, email)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'set_cheater' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+pub fn iter_cheaters<'i, 't, 'a>(tx: &'i mut Transaction<'t, 'a>) -> Result<Iter<'i, 'a, String>> {
+    let sql = r#"
+        select cheater_email from cheaters;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = Iter {
+        statement,
+        decode_row,
+    };
+    Ok(result)
+}
+
+pub fn add_cheat_attempt(tx: &mut Transaction) -> Result<()> {
+    let sql = r#"
+        insert into
+          cheat_attempts (created_at)
+        values
+          (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'add_cheat_attempt' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+pub fn count_cheat_attempts(tx: &mut Transaction) -> Result<i64> {
+    let sql = r#"
+        select count(1) from cheat_attempts;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => decode_row(statement)?,
+        Done => panic!("Query 'count_cheat_attempts' should return exactly one row."),
+    };
+    if statement.next()? != Done {
+        panic!("Query 'count_cheat_attempts' should return exactly one row.");
+    }
+    Ok(result)
+}
+
+/// Return the most coins that any voter spent, for the vote without categories.
+pub fn get_max_coins_spent(tx: &mut Transaction) -> Result<i64> {
+    let sql = r#"
+        select
+          coalesce(max(coins_spent), 0)
+        from
+          ( select sum(points * points) as coins_spent
+            from votes
+            where category_id = 0
+            group by voter_email
+          );
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => decode_row(statement)?,
+        Done => panic!("Query 'get_max_coins_spent' should return exactly one row."),
+    };
+    if statement.next()? != Done {
+        panic!("Query 'get_max_coins_spent' should return exactly one row.");
+    }
+    Ok(result)
+}
+
+/// Like `get_max_coins_spent`, for when a point costs one coin.
+pub fn get_max_points_spent(tx: &mut Transaction) -> Result<i64> {
+    let sql = r#"
+        select
+          coalesce(max(points_spent), 0)
+        from
+          ( select sum(points) as points_spent
+            from votes
+            where category_id = 0
+            group by voter_email
+          );
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => decode_row(statement)?,
+        Done => panic!("Query 'get_max_points_spent' should return exactly one row."),
+    };
+    if statement.next()? != Done {
+        panic!("Query 'get_max_points_spent' should return exactly one row.");
+    }
+    Ok(result)
+}
+
+#[derive(Debug)]
+pub struct VoteTimelineBucket {
+    pub minute: String,
+    pub n_votes: i64,
+    pub n_voters: i64,
+}
+
+/// Count the votes per minute in which they were cast, oldest first.
+///
+/// Changing a vote replaces it, so a vote counts in the minute of the last change.
+pub fn iter_vote_timeline<'i, 't, 'a>(
+    tx: &'i mut Transaction<'t, 'a>,
+) -> Result<Iter<'i, 'a, VoteTimelineBucket>> {
+    let sql = r#"
+        select
+            substr(created_at, 1, 16) || ':00Z' as minute
+          , count(1) as n_votes
+          , count(distinct voter_email) as n_voters
+        from
+          votes
+        where
+          created_at is not null
+        group by
+          minute
+        order by
+          minute asc;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let decode_row = |statement: &Statement| {
+        Ok(VoteTimelineBucket {
+            minute: statement.read(0)?,
+            n_votes: statement.read(1)?,
+            n_voters: statement.read(2)?,
+        })
+    };
+    let result = Iter {
+        statement,
+        decode_row,
+    };
+    Ok(result)
+}
+
+pub fn delete_votes_for_voter(tx: &mut Transaction, voter_email: &str) -> Result<()> {
+    let sql = r#"
+        delete from
+          votes
+        where
+          voter_email = :voter_email;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, voter_email)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'delete_votes_for_voter' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+/// Record the points, or replace the points that the voter gave the team before.
+pub fn insert_vote(
+    tx: &mut Transaction,
+    voter_email: &str,
+    team_id: i64,
+    category_id: i64,
+    points: i64,
+) -> Result<()> {
+    let sql = r#"
+        insert into
+          votes (voter_email, team_id, category_id, points, created_at)
+        values
+          (:voter_email, :team_id, :category_id, :points, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+        on conflict (voter_email, team_id, category_id)
+          do update set points = excluded.points, created_at = excluded.created_at;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, voter_email)?;
+    statement.bind(2, team_id)?;
+    statement.bind(3, category_id)?;
+    statement.bind(4, points)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'insert_vote' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+#[derive(Debug)]
+pub struct Vote {
+    pub id: i64,
+    pub points: i64,
+    pub voter_email: String,
+    pub created_at: Option<String>,
+}
+
+pub fn iter_team_votes<'i, 't, 'a>(
+    tx: &'i mut Transaction<'t, 'a>,
+    team_id: i64,
+    category_id: i64,
+) -> Result<Iter<'i, 'a, Vote>> {
+    let sql = r#"
+        select
+            id
+          , points
+          , voter_email
+          , created_at
+        from
+          votes
+        where
+          team_id = :team_id and category_id = :category_id
+        order by
+          points desc,
+          voter_email asc;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    statement.bind(2, category_id)?;
+    let decode_row = |statement: &Statement| {
+        Ok(Vote {
+            id: statement.read(0)?,
+            points: statement.read(1)?,
+            voter_email: statement.read(2)?,
+            created_at: statement.read(3)?,
+        })
+    };
+    let result = Iter {
+        statement,
+        decode_row,
+    };
+    Ok(result)
+}
+
+#[derive(Debug)]
+pub struct VoterVote {
+    pub team_id: i64,
+    pub team_name: String,
+    pub points: i64,
+    pub category_id: i64,
+}
+
+pub fn iter_voter_votes<'i, 't, 'a>(
+    tx: &'i mut Transaction<'t, 'a>,
+    voter_email: &str,
+) -> Result<Iter<'i, 'a, VoterVote>> {
+    let sql = r#"
+        select
+            teams.id as team_id
+          , teams.name as team_name
+          , votes.points
+          , votes.category_id
+        from
+          votes, teams
+        where
+          votes.team_id = teams.id and votes.voter_email = :voter_email
+        order by
+          votes.category_id asc,
+          votes.points desc,
+          teams.name asc;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, voter_email)?;
+    let decode_row = |statement: &Statement| {
+        Ok(VoterVote {
+            team_id: statement.read(0)?,
+            team_name: statement.read(1)?,
+            points: statement.read(2)?,
+            category_id: statement.read(3)?,
+        })
+    };
+    let result = Iter {
+        statement,
+        decode_row,
+    };
+    Ok(result)
+}
+
+#[derive(Debug)]
+pub struct DatasetVote {
+    pub voter_email: String,
+    pub team_id: i64,
+    pub category_id: i64,
+    pub points: i64,
+}
+
+/// All crowd votes, for the anonymized dataset, see `handle_dataset_json`.
+pub fn iter_all_votes<'i, 't, 'a>(
+    tx: &'i mut Transaction<'t, 'a>,
+) -> Result<Iter<'i, 'a, DatasetVote>> {
+    let sql = r#"
+        select
+            voter_email
+          , team_id
+          , category_id
+          , points
+        from
+          votes
+        order by
+          voter_email asc,
+          category_id asc,
+          team_id asc;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let decode_row = |statement: &Statement| {
+        Ok(DatasetVote {
+            voter_email: statement.read(0)?,
+            team_id: statement.read(1)?,
+            category_id: statement.read(2)?,
+            points: statement.read(3)?,
+        })
+    };
+    let result = Iter {
+        statement,
+        decode_row,
+    };
+    Ok(result)
+}
+
+/// Return how many points the voter gave to the given team in the given category.
+pub fn get_team_vote_for(
+    tx: &mut Transaction,
+    team_id: i64,
+    voter_email: &str,
+    category_id: i64,
+) -> Result<Option<i64>> {
+    let sql = r#"
+        select
+          points
+        from
+          votes
+        where
+          (team_id = :team_id) and (voter_email = :voter_email) and (category_id = :category_id);
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    statement.bind(2, voter_email)?;
+    statement.bind(3, category_id)?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => Some(decode_row(statement)?),
+        Done => None,
+    };
+    if result.is_some() {
+        if statement.next()? != Done {
+            panic!("Query 'get_team_vote_for' should return at most one row.");
+        }
+    }
+    Ok(result)
+}
+
+pub fn insert_cached_result(
+    tx: &mut Transaction,
+    team_id: i64,
+    category_id: i64,
+    rank: i64,
+    total_points: i64,
+) -> Result<()> {
+    let sql = r#"
+        insert into
+          results_cache (team_id, category_id, rank, total_points, created_at)
+        values
+          (:team_id, :category_id, :rank, :total_points, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    statement.bind(2, category_id)?;
+    statement.bind(3, rank)?;
+    statement.bind(4, total_points)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'insert_cached_result' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+#[derive(Debug)]
+pub struct CachedResult {
+    pub id: i64,
+    pub team_id: i64,
+    pub rank: i64,
+    pub total_points: i64,
+}
+
+/// Return the finalized standings of the category, in ranked order.
+pub fn iter_cached_results<'i, 't, 'a>(
+    tx: &'i mut Transaction<'t, 'a>,
+    category_id: i64,
+) -> Result<Iter<'i, 'a, CachedResult>> {
+    let sql = r#"
+        select
+            id
+          , team_id
+          , rank
+          , total_points
+        from
+          results_cache
+        where
+          category_id = :category_id
+        order by
+          id asc;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, category_id)?;
+    let decode_row = |statement: &Statement| {
+        Ok(CachedResult {
+            id: statement.read(0)?,
+            team_id: statement.read(1)?,
+            rank: statement.read(2)?,
+            total_points: statement.read(3)?,
+        })
+    };
+    let result = Iter {
+        statement,
+        decode_row,
+    };
+    Ok(result)
+}
+
+/// Return when the admin finalized the results, if they did.
+pub fn get_results_finalized_at(tx: &mut Transaction) -> Result<Option<String>> {
+    let sql = r#"
+        select created_at from results_cache order by id asc limit 1;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => Some(decode_row(statement)?),
+        Done => None,
+    };
+    if result.is_some() {
+        if statement.next()? != Done {
+            panic!("Query 'get_results_finalized_at' should return at most one row.");
+        }
+    }
+    Ok(result)
+}
+
+pub fn delete_results_cache(tx: &mut Transaction) -> Result<()> {
+    let sql = r#"
+        delete from results_cache;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'delete_results_cache' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+pub fn delete_judge_votes_for_judge(tx: &mut Transaction, judge_email: &str) -> Result<()> {
+    let sql = r#"
+        delete from
+          judge_votes
+        where
+          judge_email = :judge_email;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, judge_email)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'delete_judge_votes_for_judge' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+/// Like `insert_vote`, this replaces the points that the judge gave before.
+pub fn insert_judge_vote(
+    tx: &mut Transaction,
+    judge_email: &str,
+    team_id: i64,
+    points: i64,
+) -> Result<()> {
+    let sql = r#"
+        insert into
+          judge_votes (judge_email, team_id, points)
+        values
+          (:judge_email, :team_id, :points)
+        on conflict (judge_email, team_id)
+          do update set points = excluded.points;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, judge_email)?;
+    statement.bind(2, team_id)?;
+    statement.bind(3, points)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'insert_judge_vote' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+/// Return how many points the judge gave to the given team.
+pub fn get_team_judge_vote_for(
+    tx: &mut Transaction,
+    team_id: i64,
+    judge_email: &str,
+) -> Result<Option<i64>> {
+    let sql = r#"
+        select
+          points
+        from
+          judge_votes
+        where
+          (team_id = :team_id) and (judge_email = :judge_email);
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    statement.bind(2, judge_email)?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => Some(decode_row(statement)?),
+        Done => None,
+    };
+    if result.is_some() {
+        if statement.next()? != Done {
+            panic!("Query 'get_team_judge_vote_for' should return at most one row.");
+        }
+    }
+    Ok(result)
+}
+
+/// Return the sum of the points that the judges gave to the given team.
+pub fn get_team_judge_points(tx: &mut Transaction, team_id: i64) -> Result<i64> {
+    let sql = r#"
+        select
+          coalesce(sum(points), 0)
+        from
+          judge_votes
+        where
+          team_id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => decode_row(statement)?,
+        Done => panic!("Query 'get_team_judge_points' should return exactly one row."),
+    };
+    if statement.next()? != Done {
+        panic!("Query 'get_team_judge_points' should return exactly one row.");
+    }
+    Ok(result)
+}
+
+/// Return the number of users who voted.
+pub fn count_voters(tx: &mut Transaction) -> Result<i64> {
+    let sql = r#"
+        select
+          count(distinct voter_email)
+        from
+          votes;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => decode_row(statement)?,
+        Done => panic!("Query 'count_voters' should return exactly one row."),
+    };
+    if statement.next()? != Done {
+        panic!("Query 'count_voters' should return exactly one row.");
+    }
+    Ok(result)
+}
+
+/// Return the number of votes, a voter has one per team that they gave points.
+pub fn count_votes(tx: &mut Transaction) -> Result<i64> {
+    let sql = r#"
+        select count(1) from votes;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => decode_row(statement)?,
+        Done => panic!("Query 'count_votes' should return exactly one row."),
+    };
+    if statement.next()? != Done {
+        panic!("Query 'count_votes' should return exactly one row.");
+    }
+    Ok(result)
+}
+
+/// Return the number of users who voted, not counting the given user.
+pub fn count_voters_except(tx: &mut Transaction, excluded_email: &str) -> Result<i64> {
+    let sql = r#"
+        select
+          count(distinct voter_email)
+        from
+          votes
+        where
+          voter_email != :excluded_email;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, excluded_email)?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => decode_row(statement)?,
+        Done => panic!("Query 'count_voters_except' should return exactly one row."),
+    };
+    if statement.next()? != Done {
+        panic!("Query 'count_voters_except' should return exactly one row.");
+    }
+    Ok(result)
+}
+
+pub fn set_event_feedback(
+    tx: &mut Transaction,
+    author_email: &str,
+    rating: i64,
+    comment: &str,
+) -> Result<()> {
+    let sql = r#"
+        insert into
+          event_feedback (author_email, rating, comment, created_at)
+        values
+          (:author_email, :rating, :comment, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+        on conflict (author_email) do update set
+          rating = excluded.rating,
+          comment = excluded.comment,
+          created_at = excluded.created_at;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, author_email)?;
+    statement.bind(2, rating)?;
+    statement.bind(3, comment)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'set_event_feedback' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+#[derive(Debug)]
+pub struct EventFeedback {
+    pub rating: i64,
+    pub comment: String,
+}
+
+pub fn get_event_feedback(
+    tx: &mut Transaction,
+    author_email: &str,
+) -> Result<Option<EventFeedback>> {
+    let sql = r#"
+        select
+            rating
+          , comment
+        from
+          event_feedback
+        where
+          author_email = :author_email;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, author_email)?;
+    let decode_row = |statement: &Statement| {
+        Ok(EventFeedback {
+            rating: statement.read(0)?,
+            comment: statement.read(1)?,
+        })
+    };
+    let result = match statement.next()? {
+        Row => Some(decode_row(statement)?),
+        Done => None,
+    };
+    if result.is_some() {
+        if statement.next()? != Done {
+            panic!("Query 'get_event_feedback' should return at most one row.");
+        }
+    }
+    Ok(result)
+}
+
+pub fn iter_event_feedback<'i, 't, 'a>(
+    tx: &'i mut Transaction<'t, 'a>,
+) -> Result<Iter<'i, 'a, EventFeedback>> {
+    let sql = r#"
+        select
+            rating
+          , comment
+        from
+          event_feedback
+        order by
+          id asc;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let decode_row = |statement: &Statement| {
+        Ok(EventFeedback {
+            rating: statement.read(0)?,
+            comment: statement.read(1)?,
+        })
+    };
+    let result = Iter {
+        statement,
+        decode_row,
+    };
+    Ok(result)
+}
+
+pub fn set_team_feedback(
+    tx: &mut Transaction,
+    author_email: &str,
+    team_id: i64,
+    body: &str,
+) -> Result<()> {
+    let sql = r#"
+        insert into
+          team_feedback (author_email, team_id, body, created_at)
+        values
+          (:author_email, :team_id, :body, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+        on conflict (author_email, team_id) do update set
+          body = excluded.body,
+          created_at = excluded.created_at;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, author_email)?;
+    statement.bind(2, team_id)?;
+    statement.bind(3, body)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'set_team_feedback' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+pub fn delete_team_feedback(tx: &mut Transaction, author_email: &str, team_id: i64) -> Result<()> {
+    let sql = r#"
+        delete from
+          team_feedback
+        where
+          author_email = :author_email and team_id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, author_email)?;
+    statement.bind(2, team_id)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'delete_team_feedback' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+pub fn get_team_feedback_by(
+    tx: &mut Transaction,
+    author_email: &str,
+    team_id: i64,
+) -> Result<Option<String>> {
+    let sql = r#"
+        select
+          body
+        from
+          team_feedback
+        where
+          author_email = :author_email and team_id = :team_id;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, author_email)?;
+    statement.bind(2, team_id)?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => Some(decode_row(statement)?),
+        Done => None,
+    };
+    if result.is_some() {
+        if statement.next()? != Done {
+            panic!("Query 'get_team_feedback_by' should return at most one row.");
+        }
+    }
+    Ok(result)
+}
+
+#[derive(Debug)]
+pub struct TeamFeedback {
+    pub author_email: String,
+    pub body: String,
+}
+
+pub fn iter_team_feedback<'i, 't, 'a>(
+    tx: &'i mut Transaction<'t, 'a>,
+    team_id: i64,
+) -> Result<Iter<'i, 'a, TeamFeedback>> {
+    let sql = r#"
+        select
+            author_email
+          , body
+        from
+          team_feedback
+        where
+          team_id = :team_id
+        order by
+          id asc;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, team_id)?;
+    let decode_row = |statement: &Statement| {
+        Ok(TeamFeedback {
+            author_email: statement.read(0)?,
+            body: statement.read(1)?,
+        })
+    };
+    let result = Iter {
+        statement,
+        decode_row,
+    };
+    Ok(result)
+}
+
+/// Return whether the hackathon entered the celebration phase (and is still in
+/// it) more than the given number of days ago.
+pub fn is_celebrating_for_days(tx: &mut Transaction, days: i64) -> Result<i64> {
+    let sql = r#"
+        select
+          count(1)
+        from
+          progress
+        where
+          id = (select max(id) from progress)
+          and phase = 'celebration'
+          and created_at < strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-' || :days || ' days');
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, days)?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = match statement.next()? {
+        Row => decode_row(statement)?,
+        Done => panic!("Query 'is_celebrating_for_days' should return exactly one row."),
+    };
+    if statement.next()? != Done {
+        panic!("Query 'is_celebrating_for_days' should return exactly one row.");
+    }
+    Ok(result)
+}
+
+#[derive(Debug)]
+pub struct OrphanedRow {
+    pub table_name: String,
+    pub team_id: i64,
+    pub email: String,
+}
+
+/// Return the rows that reference a team that does not exist. Foreign keys
+/// prevent these, but only when they are enabled on the connection that wrote.
+pub fn iter_orphaned_rows<'i, 't, 'a>(
+    tx: &'i mut Transaction<'t, 'a>,
+) -> Result<Iter<'i, 'a, OrphanedRow>> {
+    let sql = r#"
+        select
+            'team_memberships' as table_name
+          , team_id
+          , member_email as email
+        from
+          team_memberships
+        where
+          team_id not in (select id from teams)
+        union all
+        select 'votes', team_id, voter_email from votes
+        where team_id not in (select id from teams)
+        union all
+        select 'judge_votes', team_id, judge_email from judge_votes
+        where team_id not in (select id from teams)
+        union all
+        select 'presentations', team_id, '' from presentations
+        where team_id not in (select id from teams)
+        union all
+        select 'results_cache', team_id, '' from results_cache
+        where team_id not in (select id from teams)
+        union all
+        select 'favorites', team_id, voter_email from favorites
+        where team_id not in (select id from teams)
+        union all
+        select 'team_feedback', team_id, author_email from team_feedback
+        where team_id not in (select id from teams)
+        order by
+          1, 2;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let decode_row = |statement: &Statement| {
+        Ok(OrphanedRow {
+            table_name: statement.read(0)?,
+            team_id: statement.read(1)?,
+            email: statement.read(2)?,
+        })
+    };
+    let result = Iter {
+        statement,
+        decode_row,
+    };
+    Ok(result)
+}
+
+pub fn delete_orphaned_rows(tx: &mut Transaction) -> Result<()> {
+    let sql = r#"
+        delete from team_memberships where team_id not in (select id from teams);
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    match statement.next()? {
+        Row => panic!("Query 'delete_orphaned_rows' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        delete from votes where team_id not in (select id from teams);
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    match statement.next()? {
+        Row => panic!("Query 'delete_orphaned_rows' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        delete from judge_votes where team_id not in (select id from teams);
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    match statement.next()? {
+        Row => panic!("Query 'delete_orphaned_rows' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        delete from presentations where team_id not in (select id from teams);
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    match statement.next()? {
+        Row => panic!("Query 'delete_orphaned_rows' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        delete from results_cache where team_id not in (select id from teams);
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    match statement.next()? {
+        Row => panic!("Query 'delete_orphaned_rows' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        delete from favorites where team_id not in (select id from teams);
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    match statement.next()? {
+        Row => panic!("Query 'delete_orphaned_rows' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        delete from team_feedback where team_id not in (select id from teams);
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'delete_orphaned_rows' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
+/// Return all email addresses that have not yet been replaced by a pseudonym.
+pub fn iter_unredacted_emails<'i, 't, 'a>(
+    tx: &'i mut Transaction<'t, 'a>,
+) -> Result<Iter<'i, 'a, String>> {
+    let sql = r#"
+        select voter_email from votes where voter_email not like 'redacted-%'
+        union
+        select judge_email from judge_votes where judge_email not like 'redacted-%'
+        union
+        select voter_email from favorites where voter_email not like 'redacted-%'
+        union
+        select email from participants where email not like 'redacted-%'
+        union
+        select member_email from team_memberships where member_email not like 'redacted-%'
+        union
+        select creator_email from teams where creator_email not like 'redacted-%'
+        union
+        select cheater_email from cheaters where cheater_email not like 'redacted-%'
+        union
+        select creator_email from idempotency_keys where creator_email not like 'redacted-%'
+        union
+        select author_email from event_feedback where author_email not like 'redacted-%'
+        union
+        select author_email from team_feedback where author_email not like 'redacted-%'
+        union
+        select actor_email from audit_log where actor_email not like 'redacted-%';
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    let decode_row = |statement: &Statement| Ok(statement.read(0)?);
+    let result = Iter {
+        statement,
+        decode_row,
+    };
+    Ok(result)
+}
+
+pub fn redact_email(tx: &mut Transaction, email: &str, pseudonym: &str) -> Result<()> {
+    let sql = r#"
+        update votes set voter_email = :pseudonym where voter_email = :email;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, pseudonym)?;
+    statement.bind(2, email)?;
+    match statement.next()? {
+        Row => panic!("Query 'redact_email' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        update judge_votes set judge_email = :pseudonym where judge_email = :email;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, pseudonym)?;
+    statement.bind(2, email)?;
+    match statement.next()? {
+        Row => panic!("Query 'redact_email' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        update favorites set voter_email = :pseudonym where voter_email = :email;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, pseudonym)?;
+    statement.bind(2, email)?;
+    match statement.next()? {
+        Row => panic!("Query 'redact_email' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        update participants set email = :pseudonym where email = :email;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, pseudonym)?;
+    statement.bind(2, email)?;
+    match statement.next()? {
+        Row => panic!("Query 'redact_email' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        update team_memberships set member_email = :pseudonym where member_email = :email;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, pseudonym)?;
+    statement.bind(2, email)?;
+    match statement.next()? {
+        Row => panic!("Query 'redact_email' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        update teams set creator_email = :pseudonym where creator_email = :email;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, pseudonym)?;
+    statement.bind(2, email)?;
+    match statement.next()? {
+        Row => panic!("Query 'redact_email' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        update cheaters set cheater_email = :pseudonym where cheater_email = :email;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, pseudonym)?;
+    statement.bind(2, email)?;
+    match statement.next()? {
+        Row => panic!("Query 'redact_email' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        update idempotency_keys set creator_email = :pseudonym where creator_email = :email;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, pseudonym)?;
+    statement.bind(2, email)?;
+    match statement.next()? {
+        Row => panic!("Query 'redact_email' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        update event_feedback set author_email = :pseudonym where author_email = :email;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, pseudonym)?;
+    statement.bind(2, email)?;
+    match statement.next()? {
+        Row => panic!("Query 'redact_email' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        update team_feedback set author_email = :pseudonym where author_email = :email;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, pseudonym)?;
+    statement.bind(2, email)?;
+    match statement.next()? {
+        Row => panic!("Query 'redact_email' unexpectedly returned a row."),
+        Done => {}
+    }
+
+    let sql = r#"
+        update audit_log set actor_email = :pseudonym where actor_email = :email;
+        "#;
+    let statement = match tx.statements.entry(sql.as_ptr()) {
+        Occupied(entry) => entry.into_mut(),
+        Vacant(vacancy) => vacancy.insert(tx.connection.prepare(sql)?),
+    };
+    statement.reset()?;
+    statement.bind(1, pseudonym)?;
+    statement.bind(2, email)?;
+    let result = match statement.next()? {
+        Row => panic!("Query 'redact_email' unexpectedly returned a row."),
+        Done => (),
+    };
+    Ok(result)
+}
+
 // A useless main function, included only to make the example compile with
 // Cargo’s default settings for examples.
 #[allow(dead_code)]
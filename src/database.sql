@@ -14,6 +14,9 @@ create table if not exists teams
 , creator_email string  not null
 , description   string  not null
 , created_at    string  not null
+  -- Added later, see `add_teams_project_url`.
+, project_url   string  null
+  -- Names are also unique regardless of case, see `add_teams_name_lower_index`.
 , unique (name)
 );
 
@@ -21,6 +24,9 @@ create table if not exists team_memberships
 ( id           integer primary key
 , team_id      integer not null references teams (id)
 , member_email string  not null
+  -- Added later, see `add_team_memberships_is_captain`. One member per team is
+  -- the point of contact, 1 for the captain and 0 for everybody else.
+, is_captain   integer not null default 0
   -- Every person can be in a given team at most once. They can be in multiple
   -- teams, and the team can have multiple members, this is only about
   -- cardinality.
@@ -32,9 +38,14 @@ create table if not exists votes
 , voter_email string  not null
 , team_id     integer not null references teams (id)
 , points      integer not null
-  -- Every voter can vote at most once on a team. Without this, you could
-  -- sidestep the quadratic voting property.
-, unique (voter_email, team_id)
+  -- Added later, see `migrate_votes_add_category_id`. This is the index into
+  -- the categories in the config, or 0 when there are no categories.
+, category_id integer not null default 0
+  -- Added later, see `add_votes_created_at`. Votes cast before that have none.
+, created_at  string  null
+  -- Every voter can vote at most once on a team per category. Without this,
+  -- you could sidestep the quadratic voting property.
+, unique (voter_email, team_id, category_id)
 );
 
 create table if not exists progress
@@ -49,8 +60,186 @@ create table if not exists cheaters
 , created_at    string not null
 , unique (cheater_email)
 );
+
+create table if not exists idempotency_keys
+( id              integer primary key
+, creator_email   string  not null
+, idempotency_key string  not null
+  -- This deliberately does not reference the teams table, a key outlives the
+  -- team it created, so a late retry after deletion does not recreate it.
+, team_id         integer not null
+, created_at      string  not null
+, unique (creator_email, idempotency_key)
+);
+
+create table if not exists settings
+( id         integer primary key
+, created_at string not null
+, key        string not null
+, value      string not null
+);
+
+create table if not exists event_feedback
+( id           integer primary key
+, author_email string  not null
+, rating       integer not null
+  -- The comment is optional, but we store the empty string rather than null.
+, comment      string  not null
+, created_at   string  not null
+  -- Every person can give feedback once, giving it again replaces it.
+, unique (author_email)
+);
+
+create table if not exists presentations
+( id         integer primary key
+, team_id    integer not null references teams (id)
+, created_at string  not null
+, unique (team_id)
+);
+
+-- The judges vote separately from the crowd, with their own budget. Their
+-- points get added to the crowd points with the weight from the config.
+create table if not exists judge_votes
+( id          integer primary key
+, judge_email string  not null
+, team_id     integer not null references teams (id)
+, points      integer not null
+, unique (judge_email, team_id)
+);
+
+-- The people who are expected to vote, as imported by the admin. This tells who
+-- did not vote yet. Unless `restrict_vote_to_participants` is set, anybody who
+-- can log in can still vote.
+create table if not exists participants
+( id         integer primary key
+, email      string not null
+, created_at string not null
+, unique (email)
+);
+
+-- Teams that a user wants to remember for the vote. Favorites are private, only
+-- the user who picked them sees them, and they don't affect the outcome.
+create table if not exists favorites
+( id          integer primary key
+, voter_email string  not null
+, team_id     integer not null references teams (id)
+, created_at  string  not null
+, unique (voter_email, team_id)
+);
+
+-- Written feedback on a team, from the judges, or from anybody when there are no
+-- judges. The members of the team can read it during the celebration.
+create table if not exists team_feedback
+( id           integer primary key
+, author_email string  not null
+, team_id      integer not null references teams (id)
+, body         string  not null
+, created_at   string  not null
+  -- Every author gives a team feedback once, giving it again replaces it.
+, unique (author_email, team_id)
+);
+
+-- The standings as of when the admin finalized the results, in ranked order.
+-- During the celebration we show these rather than recounting the votes, see
+-- `handle_finalize`. The table is empty when the results are not final.
+create table if not exists results_cache
+( id           integer primary key
+, team_id      integer not null references teams (id)
+, category_id  integer not null
+  -- Zero for teams that are not eligible for a rank.
+, rank         integer not null
+, total_points integer not null
+, created_at   string  not null
+, unique (team_id, category_id)
+);
+
+-- What the admin changed and when, for accountability after the event. Entries
+-- are written in the same transaction as the change they describe.
+create table if not exists audit_log
+( id          integer primary key
+, created_at  string not null
+, actor_email string not null
+  -- A short name for the kind of change, e.g. `phase-next`.
+, action      string not null
+  -- What changed, for humans to read.
+, detail      string not null
+);
+
+-- Every time somebody tries to vote for their own team, we record an attempt.
+-- Unlike the cheaters table, this counts repeated attempts. It deliberately
+-- does not store who made the attempt, the cheaters table already has that.
+create table if not exists cheat_attempts
+( id         integer primary key
+, created_at string not null
+);
 -- @end ensure_schema_exists()
 
+-- Return whether the table has a column with the given name.
+-- The tables are created with all columns when they don't exist, but columns
+-- that were added later need to be added to databases created before that.
+-- @query has_column(table_name: str, column_name: str) ->1 i64
+select count(1) from pragma_table_info(:table_name) where name = :column_name;
+
+-- @query add_teams_project_url()
+alter table teams add column project_url string null;
+
+-- @query has_index(index_name: str) ->1 i64
+select count(1) from sqlite_master where type = 'index' and name = :index_name;
+
+-- Return the names of teams that differ only in case, one group per row.
+-- These prevent `add_teams_name_lower_index`.
+-- @query iter_case_colliding_team_names() ->* str
+select
+  group_concat(name, ', ')
+from
+  teams
+group by
+  lower(name)
+having
+  count(1) > 1;
+
+-- Make team names unique regardless of case, so we can't have both "DreamTeam"
+-- and "dreamteam". Note that SQLite's `lower` only folds ASCII.
+-- @query add_teams_name_lower_index()
+create unique index teams_name_lower on teams (lower(name));
+
+-- SQLite can't add a column with a non-constant default, so the existing votes
+-- get null, and `insert_vote` sets the time for new ones.
+-- @query add_votes_created_at()
+alter table votes add column created_at string null;
+
+-- Before there were captains, the first member to join is the best guess.
+-- @begin add_team_memberships_is_captain()
+alter table team_memberships add column is_captain integer not null default 0;
+update
+  team_memberships
+set
+  is_captain = 1
+where
+  id in (select min(id) from team_memberships group by team_id);
+-- @end add_team_memberships_is_captain()
+
+-- Add the category column to the votes table. This also changes the unique
+-- constraint, and SQLite can't alter constraints, so we rebuild the table.
+-- @begin migrate_votes_add_category_id()
+create table votes_new
+( id          integer primary key
+, voter_email string  not null
+, team_id     integer not null references teams (id)
+, points      integer not null
+, category_id integer not null default 0
+, unique (voter_email, team_id, category_id)
+);
+insert into
+  votes_new (id, voter_email, team_id, points)
+select
+  id, voter_email, team_id, points
+from
+  votes;
+drop table votes;
+alter table votes_new rename to votes;
+-- @end migrate_votes_add_category_id()
+
 -- @query get_current_phase() ->? str
 select phase from progress order by id desc limit 1;
 
@@ -60,6 +249,22 @@ insert into
 values
   (:phase, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
 
+-- Return the most recent value of a setting that the admin can change at runtime.
+-- @query get_setting(key: str) ->? str
+select value from settings where key = :key order by id desc limit 1;
+
+-- @query set_setting(key: str, value: str)
+insert into
+  settings (key, value, created_at)
+values
+  (:key, :value, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+
+-- @query count_teams() ->1 i64
+select count(1) from teams;
+
+-- @query count_teams_by_name(name: str) ->1 i64
+select count(1) from teams where lower(name) = lower(:name);
+
 -- @query count_teams_by_creator(creator_email: str) ->1 i64
 select count(1) from teams where creator_email = :creator_email;
 
@@ -67,28 +272,73 @@ select count(1) from teams where creator_email = :creator_email;
 --    name: str,
 --    creator_email: str,
 --    description: str,
+--    project_url: str?,
 -- ) ->1 i64
 insert into
   teams
   ( name
   , creator_email
   , description
+  , project_url
   , created_at
   )
 values
   ( :name
   , :creator_email
   , :description
+  , :project_url
   , strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
   )
 returning
   id;
 
+-- Return the team that was created by an earlier submission with the same key.
+-- @query get_idempotent_team(creator_email: str, idempotency_key: str) ->? i64
+select
+  team_id
+from
+  idempotency_keys
+where
+  (creator_email = :creator_email) and (idempotency_key = :idempotency_key);
+
+-- @query add_idempotency_key(creator_email: str, idempotency_key: str, team_id: i64)
+insert into
+  idempotency_keys
+  ( creator_email
+  , idempotency_key
+  , team_id
+  , created_at
+  )
+values
+  ( :creator_email
+  , :idempotency_key
+  , :team_id
+  , strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+  );
+
 -- @begin delete_team(team_id: i64)
 -- Normally during team manipulation there are no votes, but if the admin goes
 -- back and forth between phases and there were already votes on this team, then
 -- drop those votes.
 delete from votes where team_id = :team_id;
+delete from judge_votes where team_id = :team_id;
+delete from results_cache where team_id = :team_id;
+delete from favorites where team_id = :team_id;
+delete from team_feedback where team_id = :team_id;
+delete from presentations where team_id = :team_id;
+delete from teams where id = :team_id;
+-- @end
+
+-- @begin delete_team_cascade(team_id: i64)
+-- Like `delete_team`, but also for a team that still has members. This is for
+-- the admin to remove spam teams.
+delete from votes where team_id = :team_id;
+delete from judge_votes where team_id = :team_id;
+delete from results_cache where team_id = :team_id;
+delete from favorites where team_id = :team_id;
+delete from team_feedback where team_id = :team_id;
+delete from presentations where team_id = :team_id;
+delete from team_memberships where team_id = :team_id;
 delete from teams where id = :team_id;
 -- @end
 
@@ -119,6 +369,8 @@ select
   , name          -- :str
   , creator_email -- :str
   , description   -- :str
+  , project_url   -- :str?
+  , created_at    -- :str
   -- Previously we selected the members as well here with string_agg, but that
   -- is not supported by the version of SQLite that Ubuntu ships :'(.
 from
@@ -126,9 +378,29 @@ from
 order by
   id desc;
 
--- @query iter_team_members(team_id: i64) ->* str
+-- @query get_team(team_id: i64) ->? Team
+select
+    id            -- :i64
+  , name          -- :str
+  , creator_email -- :str
+  , description   -- :str
+  , project_url   -- :str?
+  , created_at    -- :str
+from
+  teams
+where
+  id = :team_id;
+
+-- @query set_team_creator(team_id: i64, creator_email: str)
+update teams set creator_email = :creator_email where id = :team_id;
+
+-- @query update_team(team_id: i64, name: str, description: str)
+update teams set name = :name, description = :description where id = :team_id;
+
+-- @query iter_team_members(team_id: i64) ->* TeamMember
 select
-  member_email
+    member_email -- :str
+  , is_captain   -- :i64
 from
   team_memberships
 where
@@ -136,6 +408,30 @@ where
 order by
   id asc;
 
+-- Make the member the captain, and the other members not.
+-- @query set_team_captain(team_id: i64, member_email: str)
+update
+  team_memberships
+set
+  is_captain = (member_email = :member_email)
+where
+  team_id = :team_id;
+
+-- Return the teams with fewer than `min_size` members, smallest first.
+-- @query iter_teams_below_size(min_size: i64) ->* TeamSize
+select
+    teams.name as team_name                 -- :str
+  , count(team_memberships.id) as n_members -- :i64
+from
+  teams left join team_memberships on teams.id = team_memberships.team_id
+group by
+  teams.id
+having
+  count(team_memberships.id) < :min_size
+order by
+  n_members asc,
+  teams.name asc;
+
 -- @query iter_member_teams(member_email: str) ->* i64
 select
   team_id
@@ -144,6 +440,77 @@ from
 where
   member_email = :member_email;
 
+-- @query set_team_presented(team_id: i64)
+insert into
+  presentations (team_id, created_at)
+values
+  (:team_id, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+on conflict
+  do nothing;
+
+-- @query unset_team_presented(team_id: i64)
+delete from presentations where team_id = :team_id;
+
+-- @query iter_presented_teams() ->* i64
+select team_id from presentations;
+
+-- @query add_favorite(voter_email: str, team_id: i64)
+insert into
+  favorites (voter_email, team_id, created_at)
+values
+  (:voter_email, :team_id, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+on conflict
+  do nothing;
+
+-- @query remove_favorite(voter_email: str, team_id: i64)
+delete from favorites where voter_email = :voter_email and team_id = :team_id;
+
+-- @query iter_favorites(voter_email: str) ->* i64
+select team_id from favorites where voter_email = :voter_email;
+
+-- @query add_participant(email: str)
+insert into
+  participants (email, created_at)
+values
+  (:email, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+on conflict
+  do nothing;
+
+-- @query count_participants() ->1 i64
+select count(1) from participants;
+
+-- @query is_participant(email: str) ->1 i64
+select count(1) from participants where lower(email) = lower(:email);
+
+-- Return the imported participants who have not voted, see `add_participant`.
+-- Emails are compared case-insensitively, like in `is_participant`.
+-- @query iter_non_voters() ->* str
+select
+  email
+from
+  participants
+where
+  lower(email) not in (select lower(voter_email) from votes)
+order by
+  email asc;
+
+-- @query append_audit(actor_email: str, action: str, detail: str)
+insert into
+  audit_log (actor_email, action, detail, created_at)
+values
+  (:actor_email, :action, :detail, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+
+-- @query iter_audit_log() ->* AuditEntry
+select
+    created_at  -- :str
+  , actor_email -- :str
+  , action      -- :str
+  , detail      -- :str
+from
+  audit_log
+order by
+  id desc;
+
 -- @query set_cheater(email: str)
 insert into
   cheaters (cheater_email, created_at)
@@ -155,38 +522,180 @@ on conflict
 -- @query iter_cheaters() ->* str
 select cheater_email from cheaters;
 
+-- @query add_cheat_attempt()
+insert into
+  cheat_attempts (created_at)
+values
+  (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+
+-- @query count_cheat_attempts() ->1 i64
+select count(1) from cheat_attempts;
+
+-- Return the most coins that any voter spent, for the vote without categories.
+-- @query get_max_coins_spent() ->1 i64
+select
+  coalesce(max(coins_spent), 0)
+from
+  ( select sum(points * points) as coins_spent
+    from votes
+    where category_id = 0
+    group by voter_email
+  );
+
+-- Like `get_max_coins_spent`, for when a point costs one coin.
+-- @query get_max_points_spent() ->1 i64
+select
+  coalesce(max(points_spent), 0)
+from
+  ( select sum(points) as points_spent
+    from votes
+    where category_id = 0
+    group by voter_email
+  );
+
+-- Count the votes per minute in which they were cast, oldest first.
+--
+-- Changing a vote replaces it, so a vote counts in the minute of the last change.
+-- @query iter_vote_timeline() ->* VoteTimelineBucket
+select
+    substr(created_at, 1, 16) || ':00Z' as minute -- :str
+  , count(1) as n_votes                           -- :i64
+  , count(distinct voter_email) as n_voters       -- :i64
+from
+  votes
+where
+  created_at is not null
+group by
+  minute
+order by
+  minute asc;
+
 -- @query delete_votes_for_voter(voter_email: str)
 delete from
   votes
 where
   voter_email = :voter_email;
 
--- @query insert_vote(voter_email: str, team_id: i64, points: i64)
+-- Record the points, or replace the points that the voter gave the team before.
+-- @query insert_vote(voter_email: str, team_id: i64, category_id: i64, points: i64)
 insert into
-  votes (voter_email, team_id, points)
+  votes (voter_email, team_id, category_id, points, created_at)
 values
-  (:voter_email, :team_id, :points);
+  (:voter_email, :team_id, :category_id, :points, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+on conflict (voter_email, team_id, category_id)
+  do update set points = excluded.points, created_at = excluded.created_at;
 
--- @query iter_team_votes(team_id: i64) ->* Vote
+-- @query iter_team_votes(team_id: i64, category_id: i64) ->* Vote
 select
-    points      -- :i64
+    id          -- :i64
+  , points      -- :i64
   , voter_email -- :str
+  , created_at  -- :str?
 from
   votes
 where
-  team_id = :team_id
+  team_id = :team_id and category_id = :category_id
 order by
   points desc,
   voter_email asc;
 
--- Return how many points the voter gave to the given team.
--- @query get_team_vote_for(team_id: i64, voter_email: str) ->? i64
+-- @query iter_voter_votes(voter_email: str) ->* VoterVote
+select
+    teams.id as team_id     -- :i64
+  , teams.name as team_name -- :str
+  , votes.points            -- :i64
+  , votes.category_id       -- :i64
+from
+  votes, teams
+where
+  votes.team_id = teams.id and votes.voter_email = :voter_email
+order by
+  votes.category_id asc,
+  votes.points desc,
+  teams.name asc;
+
+-- All crowd votes, for the anonymized dataset, see `handle_dataset_json`.
+-- @query iter_all_votes() ->* DatasetVote
+select
+    voter_email -- :str
+  , team_id     -- :i64
+  , category_id -- :i64
+  , points      -- :i64
+from
+  votes
+order by
+  voter_email asc,
+  category_id asc,
+  team_id asc;
+
+-- Return how many points the voter gave to the given team in the given category.
+-- @query get_team_vote_for(team_id: i64, voter_email: str, category_id: i64) ->? i64
 select
   points
 from
   votes
 where
-  (team_id = :team_id) and (voter_email = :voter_email);
+  (team_id = :team_id) and (voter_email = :voter_email) and (category_id = :category_id);
+
+-- @query insert_cached_result(team_id: i64, category_id: i64, rank: i64, total_points: i64)
+insert into
+  results_cache (team_id, category_id, rank, total_points, created_at)
+values
+  (:team_id, :category_id, :rank, :total_points, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+
+-- Return the finalized standings of the category, in ranked order.
+-- @query iter_cached_results(category_id: i64) ->* CachedResult
+select
+    id           -- :i64
+  , team_id      -- :i64
+  , rank         -- :i64
+  , total_points -- :i64
+from
+  results_cache
+where
+  category_id = :category_id
+order by
+  id asc;
+
+-- Return when the admin finalized the results, if they did.
+-- @query get_results_finalized_at() ->? str
+select created_at from results_cache order by id asc limit 1;
+
+-- @query delete_results_cache()
+delete from results_cache;
+
+-- @query delete_judge_votes_for_judge(judge_email: str)
+delete from
+  judge_votes
+where
+  judge_email = :judge_email;
+
+-- Like `insert_vote`, this replaces the points that the judge gave before.
+-- @query insert_judge_vote(judge_email: str, team_id: i64, points: i64)
+insert into
+  judge_votes (judge_email, team_id, points)
+values
+  (:judge_email, :team_id, :points)
+on conflict (judge_email, team_id)
+  do update set points = excluded.points;
+
+-- Return how many points the judge gave to the given team.
+-- @query get_team_judge_vote_for(team_id: i64, judge_email: str) ->? i64
+select
+  points
+from
+  judge_votes
+where
+  (team_id = :team_id) and (judge_email = :judge_email);
+
+-- Return the sum of the points that the judges gave to the given team.
+-- @query get_team_judge_points(team_id: i64) ->1 i64
+select
+  coalesce(sum(points), 0)
+from
+  judge_votes
+where
+  team_id = :team_id;
 
 -- Return the number of users who voted.
 -- @query count_voters() ->1 i64
@@ -194,3 +703,170 @@ select
   count(distinct voter_email)
 from
   votes;
+
+-- Return the number of votes, a voter has one per team that they gave points.
+-- @query count_votes() ->1 i64
+select count(1) from votes;
+
+-- Return the number of users who voted, not counting the given user.
+-- @query count_voters_except(excluded_email: str) ->1 i64
+select
+  count(distinct voter_email)
+from
+  votes
+where
+  voter_email != :excluded_email;
+
+-- @query set_event_feedback(author_email: str, rating: i64, comment: str)
+insert into
+  event_feedback (author_email, rating, comment, created_at)
+values
+  (:author_email, :rating, :comment, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+on conflict (author_email) do update set
+  rating = excluded.rating,
+  comment = excluded.comment,
+  created_at = excluded.created_at;
+
+-- @query get_event_feedback(author_email: str) ->? EventFeedback
+select
+    rating  -- :i64
+  , comment -- :str
+from
+  event_feedback
+where
+  author_email = :author_email;
+
+-- @query iter_event_feedback() ->* EventFeedback
+select
+    rating  -- :i64
+  , comment -- :str
+from
+  event_feedback
+order by
+  id asc;
+
+-- @query set_team_feedback(author_email: str, team_id: i64, body: str)
+insert into
+  team_feedback (author_email, team_id, body, created_at)
+values
+  (:author_email, :team_id, :body, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+on conflict (author_email, team_id) do update set
+  body = excluded.body,
+  created_at = excluded.created_at;
+
+-- @query delete_team_feedback(author_email: str, team_id: i64)
+delete from
+  team_feedback
+where
+  author_email = :author_email and team_id = :team_id;
+
+-- @query get_team_feedback_by(author_email: str, team_id: i64) ->? str
+select
+  body
+from
+  team_feedback
+where
+  author_email = :author_email and team_id = :team_id;
+
+-- @query iter_team_feedback(team_id: i64) ->* TeamFeedback
+select
+    author_email -- :str
+  , body         -- :str
+from
+  team_feedback
+where
+  team_id = :team_id
+order by
+  id asc;
+
+-- Return whether the hackathon entered the celebration phase (and is still in
+-- it) more than the given number of days ago.
+-- @query is_celebrating_for_days(days: i64) ->1 i64
+select
+  count(1)
+from
+  progress
+where
+  id = (select max(id) from progress)
+  and phase = 'celebration'
+  and created_at < strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-' || :days || ' days');
+
+-- Return the rows that reference a team that does not exist. Foreign keys
+-- prevent these, but only when they are enabled on the connection that wrote.
+-- @query iter_orphaned_rows() ->* OrphanedRow
+select
+    'team_memberships' as table_name -- :str
+  , team_id                          -- :i64
+  , member_email as email            -- :str
+from
+  team_memberships
+where
+  team_id not in (select id from teams)
+union all
+select 'votes', team_id, voter_email from votes
+where team_id not in (select id from teams)
+union all
+select 'judge_votes', team_id, judge_email from judge_votes
+where team_id not in (select id from teams)
+union all
+select 'presentations', team_id, '' from presentations
+where team_id not in (select id from teams)
+union all
+select 'results_cache', team_id, '' from results_cache
+where team_id not in (select id from teams)
+union all
+select 'favorites', team_id, voter_email from favorites
+where team_id not in (select id from teams)
+union all
+select 'team_feedback', team_id, author_email from team_feedback
+where team_id not in (select id from teams)
+order by
+  1, 2;
+
+-- @begin delete_orphaned_rows()
+delete from team_memberships where team_id not in (select id from teams);
+delete from votes where team_id not in (select id from teams);
+delete from judge_votes where team_id not in (select id from teams);
+delete from presentations where team_id not in (select id from teams);
+delete from results_cache where team_id not in (select id from teams);
+delete from favorites where team_id not in (select id from teams);
+delete from team_feedback where team_id not in (select id from teams);
+-- @end delete_orphaned_rows()
+
+-- Return all email addresses that have not yet been replaced by a pseudonym.
+-- @query iter_unredacted_emails() ->* str
+select voter_email from votes where voter_email not like 'redacted-%'
+union
+select judge_email from judge_votes where judge_email not like 'redacted-%'
+union
+select voter_email from favorites where voter_email not like 'redacted-%'
+union
+select email from participants where email not like 'redacted-%'
+union
+select member_email from team_memberships where member_email not like 'redacted-%'
+union
+select creator_email from teams where creator_email not like 'redacted-%'
+union
+select cheater_email from cheaters where cheater_email not like 'redacted-%'
+union
+select creator_email from idempotency_keys where creator_email not like 'redacted-%'
+union
+select author_email from event_feedback where author_email not like 'redacted-%'
+union
+select author_email from team_feedback where author_email not like 'redacted-%'
+union
+select actor_email from audit_log where actor_email not like 'redacted-%';
+
+-- @begin redact_email(email: str, pseudonym: str)
+update votes set voter_email = :pseudonym where voter_email = :email;
+update judge_votes set judge_email = :pseudonym where judge_email = :email;
+update favorites set voter_email = :pseudonym where voter_email = :email;
+update participants set email = :pseudonym where email = :email;
+update team_memberships set member_email = :pseudonym where member_email = :email;
+update teams set creator_email = :pseudonym where creator_email = :email;
+update cheaters set cheater_email = :pseudonym where cheater_email = :email;
+update idempotency_keys set creator_email = :pseudonym where creator_email = :email;
+update event_feedback set author_email = :pseudonym where author_email = :email;
+update team_feedback set author_email = :pseudonym where author_email = :email;
+update audit_log set actor_email = :pseudonym where actor_email = :email;
+-- @end redact_email()
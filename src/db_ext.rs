@@ -0,0 +1,178 @@
+// Hack-o-matic -- A webapp for facilitating remote and on-site hackathons
+// Copyright 2024 Chorus One
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Database helpers that the query generator does not provide.
+//!
+//! `database.rs` is generated from `database.sql` by Squiller, and regenerating
+//! it overwrites any edits, so everything hand-written goes here instead.
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use std::path::Path;
+use std::time::Duration;
+
+use sqlite3_sys as ffi;
+
+use crate::database as db;
+
+/// An error from the database, classified so callers need not inspect codes.
+#[derive(Debug)]
+pub enum Error {
+    /// The database is locked by another connection, retrying may succeed.
+    Busy(sqlite::Error),
+
+    /// A `UNIQUE` constraint failed, e.g. `teams.name`.
+    ///
+    /// The constraint is as SQLite names it, for an index on an expression
+    /// that is the index, e.g. `index 'teams_name_lower'`.
+    UniqueViolation {
+        constraint: String,
+        source: sqlite::Error,
+    },
+
+    /// Any other error.
+    Other(sqlite::Error),
+}
+
+impl From<sqlite::Error> for Error {
+    fn from(err: sqlite::Error) -> Error {
+        // The primary result codes, extended codes have these in the low byte.
+        const SQLITE_BUSY: isize = 5;
+        const SQLITE_CONSTRAINT: isize = 19;
+        match err.code.map(|code| code & 0xff) {
+            Some(SQLITE_BUSY) => Error::Busy(err),
+            Some(SQLITE_CONSTRAINT) => {
+                let constraint = err
+                    .message
+                    .as_deref()
+                    .and_then(|msg| msg.strip_prefix("UNIQUE constraint failed: "))
+                    .map(|constraint| constraint.to_string());
+                match constraint {
+                    Some(constraint) => Error::UniqueViolation {
+                        constraint,
+                        source: err,
+                    },
+                    None => Error::Other(err),
+                }
+            }
+            _ => Error::Other(err),
+        }
+    }
+}
+
+impl Error {
+    /// Return the underlying SQLite error.
+    ///
+    /// This is not a `From` impl, because with one, the error type of the `?`
+    /// operator in the generated queries can no longer be inferred.
+    pub fn into_inner(self) -> sqlite::Error {
+        match self {
+            Error::Busy(err) => err,
+            Error::UniqueViolation { source, .. } => source,
+            Error::Other(err) => err,
+        }
+    }
+}
+
+/// Begin a new transaction that holds the write lock from the start.
+///
+/// The generated `Connection` only has `begin`, which executes a deferred
+/// `BEGIN`. That takes no locks until the first statement, so we can replace it
+/// with `BEGIN IMMEDIATE` right away, and the returned transaction commits or
+/// rolls back the immediate one.
+pub fn begin_immediate<'tx, 'a>(
+    raw_connection: &sqlite::Connection,
+    connection: &'tx mut db::Connection<'a>,
+) -> db::Result<db::Transaction<'tx, 'a>> {
+    let tx = connection.begin()?;
+    raw_connection.execute("ROLLBACK; BEGIN IMMEDIATE;")?;
+    Ok(tx)
+}
+
+/// Reset all prepared statements of the connection.
+///
+/// When executing a statement fails, resetting it reports that same error
+/// again, and the generated queries reset their cached statement before every
+/// use. When a handler recovers from an error, e.g. a unique violation, the
+/// next use of that query would fail. Resetting all statements after every
+/// transaction clears the error.
+pub fn reset_statements(raw_connection: &sqlite::Connection) {
+    // SAFETY: The statements are owned by the statement cache of the generated
+    // `Connection`, we only reset them, we don't finalize them. None of them is
+    // in use while no transaction is running.
+    unsafe {
+        let db = raw_connection.as_raw();
+        let mut statement = ffi::sqlite3_next_stmt(db, std::ptr::null_mut());
+        while !statement.is_null() {
+            ffi::sqlite3_reset(statement);
+            statement = ffi::sqlite3_next_stmt(db, statement);
+        }
+    }
+}
+
+/// Copy the entire database into memory, with SQLite's online backup API.
+///
+/// The copy is consistent, it is the database as of the current transaction
+/// of `raw_connection`. It is made a few pages at a time into a scratch file at
+/// `scratch_path`, which is removed afterwards. In WAL mode, writers can
+/// proceed in the meantime.
+pub fn backup_to_vec(
+    raw_connection: &sqlite::Connection,
+    scratch_path: &Path,
+) -> db::Result<Vec<u8>> {
+    const PAGES_PER_STEP: c_int = 64;
+    const MAX_BUSY_STEPS: u32 = 100;
+    let main = b"main\0".as_ptr() as *const c_char;
+
+    let destination = sqlite::open(scratch_path)?;
+
+    // SAFETY: Both connections outlive the backup handle, and we finish the
+    // backup before we return, also on failure.
+    let codes = unsafe {
+        let backup =
+            ffi::sqlite3_backup_init(destination.as_raw(), main, raw_connection.as_raw(), main);
+        if backup.is_null() {
+            (ffi::sqlite3_errcode(destination.as_raw()), ffi::SQLITE_OK)
+        } else {
+            let mut n_busy = 0;
+            let step_code = loop {
+                match ffi::sqlite3_backup_step(backup, PAGES_PER_STEP) {
+                    ffi::SQLITE_OK => continue,
+                    ffi::SQLITE_BUSY | ffi::SQLITE_LOCKED if n_busy < MAX_BUSY_STEPS => {
+                        n_busy += 1;
+                        std::thread::sleep(Duration::from_millis(10));
+                    }
+                    code => break code,
+                }
+            };
+            (step_code, ffi::sqlite3_backup_finish(backup))
+        }
+    };
+    let result = match codes {
+        (ffi::SQLITE_DONE, ffi::SQLITE_OK) => Ok(()),
+        (ffi::SQLITE_DONE, code) | (code, _) => Err(sqlite::Error {
+            code: Some(code as isize),
+            // SAFETY: SQLite returns a static nul-terminated string.
+            message: Some(unsafe {
+                CStr::from_ptr(ffi::sqlite3_errstr(code))
+                    .to_string_lossy()
+                    .into_owned()
+            }),
+        }),
+    };
+
+    // Close the scratch database before we read it, so it is complete.
+    std::mem::drop(destination);
+    let result = result.and_then(|()| {
+        std::fs::read(scratch_path).map_err(|err| sqlite::Error {
+            code: None,
+            message: Some(format!("Failed to read backup: {err}")),
+        })
+    });
+    let _ = std::fs::remove_file(scratch_path);
+    result
+}
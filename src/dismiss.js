@@ -0,0 +1,24 @@
+// Elements with a data-dismiss-key attribute can be hidden by the user with a
+// button inside them. We remember that in local storage, so they stay hidden
+// on the next page load. The key should change when the content does.
+
+function hideDismissed() {
+    for (const element of document.querySelectorAll("[data-dismiss-key]")) {
+        const key = "dismissed-" + element.dataset.dismissKey;
+        if (window.localStorage.getItem(key) !== null) {
+            element.hidden = true;
+            continue;
+        }
+        // Elements that would be in the way if they could not be dismissed
+        // start out hidden, now that we can dismiss them, show them.
+        element.hidden = false;
+        for (const button of element.querySelectorAll("button.dismiss")) {
+            button.addEventListener("click", (event) => {
+                window.localStorage.setItem(key, "1");
+                element.hidden = true;
+            });
+        }
+    }
+}
+
+document.addEventListener("DOMContentLoaded", hideDismissed);
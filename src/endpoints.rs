@@ -5,16 +5,22 @@
 // you may not use this file except in compliance with the License.
 // A copy of the License has been included in the root of the repository.
 
-use std::collections::hash_map::DefaultHasher;
+use std::borrow::Cow;
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
-use std::hash::Hasher;
+use std::hash::{BuildHasher, Hasher};
 use std::str::FromStr;
+use std::time::SystemTime;
 
 use maud::{html, Markup, DOCTYPE};
 use tiny_http::Header;
 
-use crate::config::Config;
+use crate::config::{Category, Config, Scoring, Tiebreak, VoteCost};
 use crate::database as db;
+use crate::db_ext;
+use crate::hash;
+use crate::json;
+use crate::metrics::Metrics;
 use crate::{Phase, Response, User};
 
 /// Additional data to display with the team, depending on the phase.
@@ -24,6 +30,10 @@ enum TeamData {
     UserVote {
         points: i64,
     },
+    /// The points that the current user awarded to this team, per category.
+    CategoryVotes {
+        points: Vec<i64>,
+    },
     /// All the votes for this team.
     AllVotes {
         votes: Vec<db::Vote>,
@@ -33,8 +43,25 @@ enum TeamData {
 struct TeamEntry {
     team: db::Team,
     member_emails: Vec<String>,
+    /// The member who is the point of contact, see `handle_set_captain`.
+    captain_email: Option<String>,
     data: TeamData,
     total_points: i64,
+    /// The id of the most recent vote for this team, or 0 if there are none.
+    ///
+    /// Vote ids increase over time, so we can use it to tell which team reached
+    /// its total earlier.
+    last_vote_id: i64,
+    /// The number of voters who gave this team points, or 0 if we didn't load votes.
+    n_voters: usize,
+    /// Whether the admin marked the team as having presented.
+    presented: bool,
+    /// Whether the user marked the team as a favorite, see `handle_favorite`.
+    favorite: bool,
+    /// The written feedback on the team, only for its members during the
+    /// celebration, see `handle_team_feedback`.
+    feedback: Vec<db::TeamFeedback>,
+    /// The rank in the outcome, or 0 if the team is not eligible for a rank.
     rank: u32,
 }
 
@@ -44,9 +71,21 @@ fn respond_html(markup: Markup) -> Response {
     )
 }
 
+fn respond_json(body: String) -> Response {
+    Response::from_string(body)
+        .with_header(Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap())
+}
+
 fn respond_error<R: Into<String>>(reason: R) -> Response {
     let page = html! {
-        (view_html_head("Hack-o-matic Error"))
+        // Error pages have nothing worth indexing, regardless of the config.
+        // We don't have the config here, so we don't know the url of the
+        // stylesheet, but error pages are rare, so we inline it instead.
+        (view_html_head_with_style(
+            "Hack-o-matic Error",
+            true,
+            html! { style { (get_stylesheet()) } },
+        ))
         body {
             h1 { "D’oh!" }
             p { (reason.into()) }
@@ -55,10 +94,19 @@ fn respond_error<R: Into<String>>(reason: R) -> Response {
     respond_html(page)
 }
 
-fn bad_request<R: Into<String>>(reason: R) -> Response {
+pub fn bad_request<R: Into<String>>(reason: R) -> Response {
     respond_error(reason).with_status_code(400)
 }
 
+/// Like `bad_request`, but respond with JSON if that is what the client wants.
+fn bad_request_for<R: Into<String>>(user: &User, reason: R) -> Response {
+    if !user.wants_json {
+        return bad_request(reason);
+    }
+    let body = format!("{{\"error\":{}}}\n", json::string(&reason.into()));
+    respond_json(body).with_status_code(400)
+}
+
 pub fn not_found<R: Into<String>>(reason: R) -> Response {
     respond_error(reason).with_status_code(404)
 }
@@ -67,7 +115,7 @@ fn conflict<R: Into<String>>(reason: R) -> Response {
     respond_error(reason).with_status_code(409)
 }
 
-fn forbidden<R: Into<String>>(reason: R) -> Response {
+pub fn forbidden<R: Into<String>>(reason: R) -> Response {
     respond_error(reason).with_status_code(403)
 }
 
@@ -79,6 +127,68 @@ pub fn service_unavailable<R: Into<String>>(reason: R) -> Response {
     respond_error(reason).with_status_code(503)
 }
 
+pub fn too_many_requests<R: Into<String>>(reason: R) -> Response {
+    respond_error(reason).with_status_code(429)
+}
+
+pub fn payload_too_large<R: Into<String>>(reason: R) -> Response {
+    respond_error(reason).with_status_code(413)
+}
+
+/// Serve the metrics in the Prometheus text format, see `enable_metrics`.
+pub fn handle_metrics(tx: &mut db::Transaction, metrics: &Metrics) -> db::Result<Response> {
+    let mut out = String::new();
+    metrics.format(&mut out);
+
+    let current = crate::load_phase(tx)?;
+    out.push_str("# HELP hackomatic_phase Whether the hackathon is in this phase.\n");
+    out.push_str("# TYPE hackomatic_phase gauge\n");
+    let phases = [
+        Phase::Registration,
+        Phase::Presentation,
+        Phase::Evaluation,
+        Phase::Judging,
+        Phase::Revelation,
+        Phase::Celebration,
+    ];
+    for phase in phases {
+        out.push_str(&format!(
+            "hackomatic_phase{{phase=\"{}\"}} {}\n",
+            phase.to_str(),
+            (phase == current) as u8,
+        ));
+    }
+
+    let gauges = [
+        ("teams", "Teams registered.", db::count_teams(tx)?),
+        (
+            "votes",
+            "Votes cast, one per team per voter.",
+            db::count_votes(tx)?,
+        ),
+        ("voters", "People who cast a vote.", db::count_voters(tx)?),
+    ];
+    for (name, help, value) in gauges {
+        out.push_str(&format!("# HELP hackomatic_{name} {help}\n"));
+        out.push_str(&format!("# TYPE hackomatic_{name} gauge\n"));
+        out.push_str(&format!("hackomatic_{name} {value}\n"));
+    }
+
+    let content_type = "text/plain; version=0.0.4; charset=utf-8";
+    Ok(Response::from_string(out)
+        .with_header(Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes()).unwrap()))
+}
+
+/// Serve a robots.txt that asks crawlers to stay away, if so configured.
+pub fn handle_robots_txt(config: &Config) -> Response {
+    if !config.app.noindex {
+        return not_found("Not found.");
+    }
+    Response::from_string("User-agent: *\nDisallow: /\n").with_header(
+        Header::from_bytes(&b"Content-Type"[..], &b"text/plain; charset=utf-8"[..]).unwrap(),
+    )
+}
+
 fn redirect_see_other<R: AsRef<[u8]>>(location: R) -> Response {
     Response::from_string("")
         .with_status_code(303)
@@ -86,17 +196,32 @@ fn redirect_see_other<R: AsRef<[u8]>>(location: R) -> Response {
 }
 
 /// Render the standard header that is the same across all pages.
-fn view_html_head(page_title: &str) -> Markup {
+fn view_html_head(config: &Config, page_title: &str) -> Markup {
+    // The stylesheet is the same for every page, so browsers can cache it,
+    // see `handle_stylesheet`. The version changes when the stylesheet does.
+    let href = format!(
+        "{}/style.css?v={}",
+        config.server.prefix,
+        get_stylesheet_etag(&get_stylesheet())
+    );
+    let style = html! { link rel="stylesheet" href=(href); };
+    view_html_head_with_style(page_title, config.app.noindex, style)
+}
+
+fn view_html_head_with_style(page_title: &str, noindex: bool, style: Markup) -> Markup {
     html! {
         (DOCTYPE)
         head {
             meta charset="utf-8";
+            @if noindex {
+                meta name="robots" content="noindex";
+            }
             link rel="preconnect" href="https://fonts.googleapis.com";
             link rel="preconnect" href="https://fonts.gstatic.com" crossorigin;
             link href="https://fonts.googleapis.com/css2?family=Work+Sans:ital,wght@0,700..800;1,900&family=Atkinson+Hyperlegible:ital,wght@0,400;0,700;1,400&display=swap" rel="stylesheet";
             meta name="viewport" content="width=device-width, initial-scale=1";
             title { (page_title) }
-            style { (get_stylesheet()) }
+            (style)
         }
     }
 }
@@ -104,20 +229,56 @@ fn view_html_head(page_title: &str) -> Markup {
 // In debug mode, we load the stylesheet from disk on the fly, so you can edit
 // without having to rebuild the server.
 #[cfg(debug_assertions)]
-fn get_stylesheet() -> Markup {
+fn get_stylesheet() -> Cow<'static, str> {
     let data = std::fs::read_to_string("src/style.css")
         .expect("Need to run from repo root in debug mode.");
-    html! { (data) }
+    Cow::Owned(data)
 }
 
 // For a release build, we embed the stylesheet into the binary.
 #[cfg(not(debug_assertions))]
-fn get_stylesheet() -> Markup {
-    let data = include_str!("style.css");
-    html! { (data) }
+fn get_stylesheet() -> Cow<'static, str> {
+    Cow::Borrowed(include_str!("style.css"))
+}
+
+/// Return a hash of the stylesheet, to tell whether a cached copy is current.
+fn get_stylesheet_etag(stylesheet: &str) -> String {
+    format!("{:016x}", hash::siphash13(0, 0, stylesheet.as_bytes()))
+}
+
+/// Serve the stylesheet, so browsers can cache it rather than get it inline.
+///
+/// The pages link to it with the hash in the url, so a cached copy is never
+/// stale, and the browser can keep it for a long time.
+pub fn handle_stylesheet(if_none_match: Option<&str>) -> Response {
+    let stylesheet = get_stylesheet();
+    let etag = format!("\"{}\"", get_stylesheet_etag(&stylesheet));
+    let etag_header = Header::from_bytes(&b"ETag"[..], etag.as_bytes()).unwrap();
+    let cache_header =
+        Header::from_bytes(&b"Cache-Control"[..], &b"public, max-age=31536000"[..]).unwrap();
+
+    let is_cached = if_none_match.map_or(false, |tags| {
+        tags.split(',')
+            .map(|tag| tag.trim())
+            .any(|tag| tag == etag || tag == "*")
+    });
+    if is_cached {
+        return Response::from_data(Vec::new())
+            .with_status_code(304)
+            .with_header(etag_header)
+            .with_header(cache_header);
+    }
+
+    Response::from_string(stylesheet.into_owned())
+        .with_header(
+            Header::from_bytes(&b"Content-Type"[..], &b"text/css; charset=utf-8"[..]).unwrap(),
+        )
+        .with_header(etag_header)
+        .with_header(cache_header)
 }
 
-// Same for the script.
+// Same for the script. This is the only place where we bypass escaping, so it
+// must only ever be used for the script that we ship, never for user input.
 #[cfg(debug_assertions)]
 fn get_vote_script() -> Markup {
     let data =
@@ -130,6 +291,18 @@ fn get_vote_script() -> Markup {
     maud::PreEscaped(include_str!("vote.js").to_string())
 }
 
+#[cfg(debug_assertions)]
+fn get_dismiss_script() -> Markup {
+    let data = std::fs::read_to_string("src/dismiss.js")
+        .expect("Need to run from repo root in debug mode.");
+    maud::PreEscaped(data)
+}
+
+#[cfg(not(debug_assertions))]
+fn get_dismiss_script() -> Markup {
+    maud::PreEscaped(include_str!("dismiss.js").to_string())
+}
+
 fn view_email<'a>(config: &Config, email: &'a str) -> &'a str {
     match email.strip_suffix(&config.app.email_suffix) {
         Some(stripped) => stripped,
@@ -139,9 +312,135 @@ fn view_email<'a>(config: &Config, email: &'a str) -> &'a str {
 
 struct IndexData<'a> {
     phase: Phase,
+    banner: Option<&'a str>,
     teams: &'a [TeamEntry],
+    /// The number of teams on all pages, `teams` is only the current page.
+    n_teams: usize,
     cheaters: &'a [String],
+    /// The number of times that somebody tried to vote for their own team.
+    cheat_attempts: i64,
     voter_count: u32,
+    /// The feedback that the user gave about the event, if any.
+    feedback: Option<db::EventFeedback>,
+    /// Feedback from everybody, only loaded for the admin.
+    all_feedback: &'a [db::EventFeedback],
+    /// Whether the user followed a link to a team that does not exist (any more).
+    missing_team: bool,
+    /// The outcome per category, if there are categories and the user can see it.
+    category_rankings: &'a [CategoryRanking<'a>],
+    /// The number of coins that the user can spend in this phase.
+    coins_to_spend: u32,
+    /// Which page of teams this is, if there are multiple, see `teams_per_page`.
+    pagination: Option<Pagination>,
+    /// How the teams are ordered during registration.
+    team_sort: TeamSort,
+    /// When the admin finalized the results, if they did, see `handle_finalize`.
+    results_finalized_at: Option<String>,
+    /// Whether the results were shown before, see `are_results_revealed`.
+    results_revealed: bool,
+    /// Whether the admin froze the teams, see `handle_set_registration_lock`.
+    registration_locked: bool,
+    /// Whether the user can vote now, unlike `User::can_vote` this includes
+    /// the participant check, see `is_excluded_from_vote`.
+    can_vote: bool,
+    /// How many teams the user can vote for, only while they can vote.
+    vote_eligibility: Option<VoteEligibility>,
+    /// The number of participants that the admin imported, only for the admin.
+    n_participants: i64,
+    /// The imported participants who did not vote, only for the admin during the vote.
+    non_voters: &'a [String],
+    /// The create team form that failed to submit, to show it again with the error.
+    create_team: Option<&'a CreateTeamForm>,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum CreateTeamField {
+    TeamName,
+    Description,
+    ProjectUrl,
+}
+
+/// What the user submitted to create a team, see `reject_create_team`.
+struct CreateTeamForm {
+    team_name: String,
+    description: String,
+    project_url: String,
+    idempotency_key: String,
+    /// The error, with the field it is about, or `None` when it is about the whole form.
+    error: Option<(Option<CreateTeamField>, String)>,
+}
+
+struct VoteEligibility {
+    n_teams: usize,
+    /// The number of teams that the user is a member of, and can't vote for.
+    n_own_teams: usize,
+}
+
+/// How to order the teams during registration, from `?sort=` on the index.
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum TeamSort {
+    /// The most recently created team first, the default.
+    Newest,
+    Oldest,
+    Name,
+}
+
+impl TeamSort {
+    const ALL: [TeamSort; 3] = [TeamSort::Newest, TeamSort::Oldest, TeamSort::Name];
+
+    /// Parse the `sort` query parameter, anything unknown is the default.
+    fn from_query(query: &str) -> TeamSort {
+        let value = form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _value)| key == "sort")
+            .map(|(_key, value)| value);
+        TeamSort::ALL
+            .into_iter()
+            .find(|sort| Some(sort.as_str()) == value.as_deref())
+            .unwrap_or(TeamSort::Newest)
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            TeamSort::Newest => "newest",
+            TeamSort::Oldest => "oldest",
+            TeamSort::Name => "name",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            TeamSort::Newest => "newest first",
+            TeamSort::Oldest => "oldest first",
+            TeamSort::Name => "name",
+        }
+    }
+
+    /// Return the index url with this order, at the given page.
+    fn get_url(self, config: &Config, page: usize) -> String {
+        let mut params = form_urlencoded::Serializer::new(String::new());
+        if self != TeamSort::Newest {
+            params.append_pair("sort", self.as_str());
+        }
+        if page > 1 {
+            params.append_pair("page", &page.to_string());
+        }
+        match params.finish() {
+            query if query.is_empty() => config.server.prefix.clone(),
+            query => format!("{}?{}", config.server.prefix, query),
+        }
+    }
+}
+
+struct Pagination {
+    /// The current page, starting at 1.
+    current: usize,
+    n_pages: usize,
+}
+
+/// The ranked teams in one award category, see `categories` in the config.
+struct CategoryRanking<'a> {
+    name: &'a str,
+    teams: Vec<TeamEntry>,
 }
 
 fn view_index(config: &Config, user: &User, data: IndexData) -> Markup {
@@ -149,8 +448,12 @@ fn view_index(config: &Config, user: &User, data: IndexData) -> Markup {
     // has voted or not, so we need to know if they have any.
     let mut did_vote = false;
     for team in data.teams {
-        match team.data {
-            TeamData::UserVote { points } if points != 0 => {
+        match &team.data {
+            TeamData::UserVote { points } if *points != 0 => {
+                did_vote = true;
+                break;
+            }
+            TeamData::CategoryVotes { points } if points.iter().any(|p| *p != 0) => {
                 did_vote = true;
                 break;
             }
@@ -158,20 +461,130 @@ fn view_index(config: &Config, user: &User, data: IndexData) -> Markup {
         }
     }
 
+    // With categories, the inputs are grouped per category rather than per team.
+    let has_categories =
+        matches!(data.phase, Phase::Evaluation) && !config.app.categories.is_empty();
+
+    // The coins that the user spent in their submitted vote, per category, so
+    // the coins left are right on first load, also without Javascript.
+    let n_categories = if has_categories {
+        config.app.categories.len()
+    } else {
+        1
+    };
+    let mut points_per_category: Vec<HashMap<i64, i64>> = vec![HashMap::new(); n_categories];
+    for entry in data.teams {
+        match &entry.data {
+            TeamData::UserVote { points } => {
+                points_per_category[0].insert(entry.team.id, *points);
+            }
+            TeamData::CategoryVotes { points } => {
+                for (category_points, p) in points_per_category.iter_mut().zip(points) {
+                    category_points.insert(entry.team.id, *p);
+                }
+            }
+            _ => continue,
+        }
+    }
+    // The stored votes are within budget, so the sum does not overflow.
+    let coins_spent: Vec<i64> = points_per_category
+        .iter()
+        .map(|points| get_coins_spent(config, points).unwrap_or(0))
+        .collect();
+
+    let title = match data.n_teams {
+        n if n > 0
+            && config.app.show_team_count_in_title
+            && matches!(data.phase, Phase::Registration) =>
+        {
+            let noun = if n == 1 { "team" } else { "teams" };
+            format!("Hack-o-matic — {n} {noun}")
+        }
+        _ => "Hack-o-matic".to_string(),
+    };
+
     html! {
-        (view_html_head("Hack-o-matic"))
-        body {
+        (view_html_head(config, &title))
+        body .compact[config.app.compact_layout] {
+            @if let Some(banner) = data.banner {
+                (view_banner(banner))
+            }
+            @if config.app.show_onboarding {
+                (view_onboarding(config, data.phase, data.coins_to_spend))
+            }
+            @if data.banner.is_some() || config.app.show_onboarding {
+                script { (get_dismiss_script()) }
+            }
             h1 {
                 "Hack-o-matic"
             }
+            @if data.missing_team {
+                p .notice {
+                    "The team that you followed a link to no longer exists. "
+                    "It may have been deleted by its members."
+                }
+            }
             p {
                 "Welcome to the hackaton support system, " (user.email) "."
             }
-            (view_phases(data.phase))
+            @if data.results_revealed && matches!(data.phase, Phase::Evaluation | Phase::Judging) {
+                p .notice {
+                    "The vote was reopened after the results were revealed, "
+                    "so the results you saw are provisional."
+                }
+            }
+            @if let Some(eligibility) = data.vote_eligibility {
+                (view_vote_eligibility(eligibility))
+            }
+            @if user.can_vote(data.phase) && !data.can_vote {
+                p .notice {
+                    "Only the registered participants can vote, and "
+                    (user.email) " is not on the list. "
+                    "If you are participating, ask the organizers to add you."
+                }
+            }
+            (view_phases(config, data.phase))
             @if user.is_admin {
-                (view_phase_admin_form(config, data.phase))
+                (view_phase_admin_form(
+                    config,
+                    data.phase,
+                    data.coins_to_spend,
+                    data.registration_locked,
+                ))
+                (form_banner(config, data.banner))
+                p {
+                    a href=(format!("{}/integrity", config.server.prefix)) {
+                        "Check database integrity »"
+                    }
+                }
+                p {
+                    a href=(format!("{}/audit", config.server.prefix)) {
+                        "View the audit log »"
+                    }
+                }
+                p {
+                    a href=(format!("{}/backup.sqlite", config.server.prefix)) {
+                        "Download a database backup »"
+                    }
+                }
+                @if config.app.vote_fingerprint_secret.is_some() {
+                    p {
+                        a href=(format!("{}/verify-vote", config.server.prefix)) {
+                            "Verify a vote fingerprint »"
+                        }
+                    }
+                }
+                @if matches!(
+                    data.phase,
+                    Phase::Registration | Phase::Presentation | Phase::Evaluation
+                ) {
+                    (form_import_participants(config, data.n_participants))
+                }
             }
-            @if matches!(data.phase, Phase::Evaluation | Phase::Revelation | Phase::Celebration) {
+            @if matches!(
+                data.phase,
+                Phase::Evaluation | Phase::Judging | Phase::Revelation | Phase::Celebration
+            ) {
                 h2 { "Voting Turnout" }
                 p {
                     @match data.voter_count {
@@ -180,59 +593,140 @@ fn view_index(config: &Config, user: &User, data: IndexData) -> Markup {
                         n => { (n) " people have cast their vote." },
                     }
                 }
+                // The admin can see how common cheating is already during the
+                // vote, to decide whether to call it out.
+                @if user.is_admin {
+                    p {
+                        @match data.cheat_attempts {
+                            0 => "Nobody has tried to vote for their own team yet.",
+                            1 => "There has been one attempt to vote for an own team.",
+                            n => {
+                                "There have been " (n) " attempts to vote for an own team, "
+                                "by " (data.cheaters.len()) " people."
+                            },
+                        }
+                    }
+                    @if matches!(data.phase, Phase::Evaluation) && data.n_participants > 0 {
+                        (view_non_voters(config, data.n_participants, data.non_voters))
+                    }
+                }
             }
             @if matches!(data.phase, Phase::Revelation | Phase::Celebration) {
                 // Display the cheaters only after the vote ends. That way
                 // people are not immediately discouraged from cheating during
                 // the vote when the first person tries to do it. I'm curious to
                 // catch all the people who would try.
-                @if !data.cheaters.is_empty() {
+                @if !data.cheaters.is_empty() && config.app.public_cheaters {
                     h2 { "Hall of Shame" }
-                    p { "The following people tried to cheat and vote for themselves:" }
-                    ul {
-                        @for cheater_email in data.cheaters {
-                            li { (view_email(config, &cheater_email)) }
-                        }
+                    (view_cheaters(config, data.cheaters))
+                } @else if !data.cheaters.is_empty() && user.is_admin {
+                    div .admin-only {
+                        h2 { "Hall of Shame" }
+                        p { "Only you can see this, see " code { "public_cheaters" } "." }
+                        (view_cheaters(config, data.cheaters))
                     }
                 }
             }
             @if matches!(data.phase, Phase::Evaluation) {
-                (view_voting_help(config))
+                (view_voting_help(config, data.coins_to_spend))
+            }
+            @if matches!(data.phase, Phase::Celebration) {
+                (form_event_feedback(config, data.feedback.as_ref()))
+                @if user.is_admin {
+                    (view_event_feedback_summary(data.all_feedback))
+                    @if let Some(secret) = &config.app.results_token_secret {
+                        (view_share_results(config, secret))
+                    }
+                    (form_finalize(config, data.results_finalized_at.as_deref()))
+                }
+            }
+            @for ranking in data.category_rankings {
+                (view_category_outcome(ranking))
             }
             h2 { "Teams" }
-            @if matches!(data.phase, Phase::Registration) {
+            @if matches!(data.phase, Phase::Revelation)
+                && config.app.allow_self_rank_peek
+                && !user.is_admin
+            {
                 p {
-                    details {
+                    a href=(format!("{}/my-rank", config.server.prefix)) {
+                        "Peek at the rank of your team »"
+                    }
+                }
+            }
+            @if matches!(data.phase, Phase::Registration) && data.registration_locked {
+                p .notice { "Registration is locked, teams can’t be changed right now." }
+            } @else if matches!(data.phase, Phase::Registration) {
+                p {
+                    details open[data.create_team.is_some()] {
                         summary { "Add a new team" }
-                        (form_create_team(config))
+                        (form_create_team(config, data.create_team))
                     }
                 }
             }
-            @if matches!(data.phase, Phase::Evaluation) {
+            @if data.can_vote {
                 form
                     action=(format!("{}/vote", config.server.prefix))
                     method="post"
                 {
                     @for entry in data.teams {
-                        (view_team(config, user, data.phase, entry))
+                        (view_team(config, user, data.phase, data.coins_to_spend, entry))
+                    }
+                    @if has_categories {
+                        @for (category_id, category) in config.app.categories.iter().enumerate() {
+                            (view_category_vote(config, user, category_id, category, data.teams))
+                        }
                     }
                     div .score-float {
-                        h2 { "Your Vote" }
+                        h2 .score-title { "Your Vote" }
                         p {
                             "You have "
-                            span #coins-left {
-                                (config.app.coins_to_spend) " coins"
+                            @if has_categories {
+                                @for (category_id, category) in config.app.categories.iter().enumerate() {
+                                    @if category_id > 0 { ", " }
+                                    span id=(format!("coins-left-{category_id}")) {
+                                        (view_coins(category.coins_to_spend as i64 - coins_spent[category_id]))
+                                    }
+                                    " for " (category.name)
+                                }
+                            } @else {
+                                span #coins-left {
+                                    (view_coins(data.coins_to_spend as i64 - coins_spent[0]))
+                                }
                             }
                             " left to spend. "
                             noscript {
                                 "If you enable Javascript, "
                                 "this number updates as you edit your vote, "
-                                "but now it only shows the total you can spend."
+                                "but now it only shows what is left after the vote you submitted."
                             }
                         }
                     }
                     footer {
                         button type="submit" #submit-vote { "Submit Vote" }
+                        " "
+                        // The preview gets the same form, see `handle_vote_preview`.
+                        button
+                            type="submit"
+                            formaction=(format!("{}/vote-preview", config.server.prefix))
+                        { "Preview" }
+                        // Forms can't nest, so this submits the vote form to a
+                        // different endpoint, which ignores the points.
+                        @if did_vote && matches!(data.phase, Phase::Evaluation) {
+                            " "
+                            button
+                                type="submit"
+                                formaction=(format!("{}/clear-vote", config.server.prefix))
+                            { "Clear my vote" }
+                        }
+                        // The review page shows the crowd vote, not the judge vote.
+                        @if matches!(data.phase, Phase::Evaluation) {
+                            p {
+                                a href=(format!("{}/my-vote", config.server.prefix)) {
+                                    "Review your submitted vote »"
+                                }
+                            }
+                        }
                         p #voteMessage {
                             @if did_vote {
                                 "Your vote has been recorded. "
@@ -248,43 +742,203 @@ fn view_index(config: &Config, user: &User, data: IndexData) -> Markup {
                     // end of the page.
                     div #your-vote {}
                 }
+                // Maud escapes for html, not for Javascript, so only ever
+                // interpolate numbers here, never strings.
                 script {
-                    "const coinsToSpend = " (config.app.coins_to_spend) ";\n"
-                    "const inputBoxes = [";
-                    @for entry in data.teams { "input" (entry.team.id) ", " }
+                    "const voteCategories = [\n"
+                    @if has_categories {
+                        @for (category_id, category) in config.app.categories.iter().enumerate() {
+                            "{ id: " (category_id) ", coinsToSpend: " (category.coins_to_spend) ", "
+                            "maxPoints: " (get_max_points(config, category.coins_to_spend)) ", "
+                            "costExponent: " (get_vote_cost_exponent(config)) ", "
+                            "teamIds: [";
+                            @for entry in data.teams { (entry.team.id) ", " }
+                            "] },\n"
+                        }
+                    } @else {
+                        "{ id: null, coinsToSpend: " (data.coins_to_spend) ", "
+                        "maxPoints: " (get_max_points(config, data.coins_to_spend)) ", "
+                        "costExponent: " (get_vote_cost_exponent(config)) ", "
+                        "teamIds: [";
+                        @for entry in data.teams { (entry.team.id) ", " }
+                        "] },\n"
+                    }
                     "];\n"
                     (get_vote_script())
                 }
             } @else {
+                @if matches!(data.phase, Phase::Registration) && data.n_teams > 1 {
+                    (view_team_sort(config, data.team_sort))
+                }
                 @for entry in data.teams {
-                    (view_team(config, user, data.phase, entry))
+                    (view_team(config, user, data.phase, data.coins_to_spend, entry))
+                }
+                @if let Some(pagination) = &data.pagination {
+                    (view_pagination(config, pagination, data.team_sort))
+                }
+            }
+            @if matches!(data.phase, Phase::Presentation | Phase::Evaluation) {
+                (form_favorite(config))
+            }
+        }
+    }
+}
+
+/// Return the url of the team on the index.
+///
+/// With pages, the team may be on a different page, so we link to `?team=ID`,
+/// which redirects to the right page.
+fn get_team_url(config: &Config, team_id: i64) -> String {
+    match config.app.teams_per_page {
+        Some(n) if n > 0 => format!("{}?team={}", config.server.prefix, team_id),
+        _ => format!("{}#team-{}", config.server.prefix, team_id),
+    }
+}
+
+fn view_team_sort(config: &Config, current: TeamSort) -> Markup {
+    html! {
+        nav .team-sort {
+            "Sort teams by: "
+            @for (i, sort) in TeamSort::ALL.into_iter().enumerate() {
+                @if i > 0 { " · " }
+                @if sort == current {
+                    strong { (sort.label()) }
+                } @else {
+                    a href=(sort.get_url(config, 1)) { (sort.label()) }
+                }
+            }
+        }
+    }
+}
+
+fn view_pagination(config: &Config, pagination: &Pagination, sort: TeamSort) -> Markup {
+    let page_url = |page: usize| sort.get_url(config, page);
+    html! {
+        nav .pagination {
+            @if pagination.current > 1 {
+                a href=(page_url(pagination.current - 1)) { "« Previous page" }
+                " "
+            }
+            span { "Page " (pagination.current) " of " (pagination.n_pages) }
+            @if pagination.current < pagination.n_pages {
+                " "
+                a href=(page_url(pagination.current + 1)) { "Next page »" }
+            }
+        }
+    }
+}
+
+/// Render the inputs to vote with in one category, one for every team.
+fn view_category_vote(
+    config: &Config,
+    user: &User,
+    category_id: usize,
+    category: &Category,
+    teams: &[TeamEntry],
+) -> Markup {
+    let max_points = get_max_points(config, category.coins_to_spend);
+    html! {
+        div .category {
+            h2 { (category.name) }
+            p {
+                "Spend up to " (category.coins_to_spend) " coins "
+                "on the teams that you think deserve this award."
+            }
+            @for entry in teams {
+                @let user_points = match &entry.data {
+                    TeamData::CategoryVotes { points } => points[category_id],
+                    _ => 0,
+                };
+                @let input_id = format!("input{}-{}", category_id, entry.team.id);
+                @let input_name = format!("category-{}-team-{}", category_id, entry.team.id);
+                label {
+                    (entry.team.name) ": ";
+                    @if entry.member_emails.contains(&user.email) {
+                        input
+                            id=(input_id)
+                            name=(input_name)
+                            disabled
+                            value=(user_points)
+                            title="You can’t vote for this team because you are a member.";
+                    } @else {
+                        input
+                            id=(input_id)
+                            name=(input_name)
+                            type="number"
+                            min="0"
+                            max=(max_points)
+                            value=(user_points);
+                    }
+                    span .cost id=(format!("cost{}-{}", category_id, entry.team.id)) {
+                        @if config.app.render_vote_cost && user_points != 0 {
+                            "(" (view_coins(get_vote_cost(config, user_points).unwrap_or(0))) ")"
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Render the ranking of one category, in the order of the entries.
+fn view_category_outcome(ranking: &CategoryRanking) -> Markup {
+    html! {
+        h2 { (ranking.name) }
+        ol .category-outcome {
+            @for entry in &ranking.teams {
+                li {
+                    @if entry.rank > 0 {
+                        strong { "№ " (entry.rank) }
+                    } @else {
+                        strong { "Unranked" }
+                    }
+                    " " (entry.team.name) " — " (entry.total_points)
+                    @if entry.total_points == 1 { " point" } @else { " points" }
                 }
             }
         }
     }
 }
 
-fn view_team(config: &Config, user: &User, phase: Phase, entry: &TeamEntry) -> Markup {
-    // Due to quadratic cost, the max points you can spend is the square root
-    // of the coins you have.
-    let max_points = (config.app.coins_to_spend as f32).sqrt().floor() as i32;
+fn view_team(
+    config: &Config,
+    user: &User,
+    phase: Phase,
+    coins_to_spend: u32,
+    entry: &TeamEntry,
+) -> Markup {
+    let max_points = get_max_points(config, coins_to_spend);
     let user_points = match entry.data {
         TeamData::UserVote { points } => points,
         _ => 0,
     };
+    let has_user_vote = user_points != 0;
     let supporters = match &entry.data {
-        TeamData::AllVotes { votes } => Some(&votes[..]),
+        TeamData::AllVotes { votes } if config.app.show_supporters => Some(&votes[..]),
         _ => None,
     };
+    // The votes are ordered by points descending, so if we cut off the list,
+    // the top supporters remain.
+    let n_supporters_shown = match (supporters, config.app.max_supporters_shown) {
+        (Some(votes), Some(max)) => votes.len().min(max as usize),
+        (Some(votes), None) => votes.len(),
+        (None, _) => 0,
+    };
 
     html! {
         // We give teams an anchor so we can refer to it from a
         // redirect and even highlight after creation using CSS.
-        div .team id=(format!("team-{}", entry.team.id)) {
+        // During the vote, mark the teams that the user awarded points to, so
+        // they can see their allocation at a glance in the shuffled list.
+        div .team .voted[has_user_vote] .favorite[entry.favorite] id=(format!("team-{}", entry.team.id)) {
             @if matches!(&entry.data, TeamData::AllVotes { .. }) {
                 div .outcome-outer {
                     div .outcome {
-                        div .rank { (entry.rank) }
+                        @if entry.rank > 0 {
+                            div .rank { (entry.rank) }
+                        } @else {
+                            div .rank .unranked { "Did not present" }
+                        }
                         div .points {
                             @match entry.total_points {
                                 0 => "0 points",
@@ -296,33 +950,89 @@ fn view_team(config: &Config, user: &User, phase: Phase, entry: &TeamEntry) -> M
                 }
             }
             h3 {
-                a href=(format!("{}#team-{}", config.server.prefix, entry.team.id)) {
+                a href=(get_team_url(config, entry.team.id)) {
                     (entry.team.name)
                 }
+                @if has_user_vote {
+                    " " span .voted-badge { "Voted" }
+                }
             }
             p .description { (entry.team.description) }
+            @if let Some(url) = &entry.team.project_url {
+                p .project-url {
+                    a href=(url) rel="nofollow noopener" { (url) }
+                }
+            }
+            // Helps the admin spot the teams that registered at the last minute.
+            @if matches!(phase, Phase::Registration) {
+                p .created-at { "Created " (entry.team.created_at) }
+            }
+            @if entry.presented && matches!(phase, Phase::Presentation) {
+                p .presented { "Presented" }
+            }
+            @if matches!(phase, Phase::Presentation | Phase::Evaluation)
+                && !entry.member_emails.contains(&user.email)
+            {
+                (view_favorite_toggle(entry.team.id, entry.favorite))
+            }
+            // This is a link rather than a form, because during the vote the
+            // team is inside the vote form, and forms can't nest.
+            @if can_give_team_feedback(config, user, phase)
+                && !entry.member_emails.contains(&user.email)
+            {
+                p {
+                    a href=(format!("{}/team-feedback?team-id={}", config.server.prefix, entry.team.id)) {
+                        "Write feedback for this team »"
+                    }
+                }
+            }
             p {
                 strong { "Members: " }
                 @for (i, member) in entry.member_emails.iter().enumerate() {
                     @if i > 0 { ", " }
-                    (view_email(config, member))
+                    @if entry.captain_email.as_ref() == Some(member) {
+                        span .captain { (view_email(config, member)) " (captain)" }
+                    } @else {
+                        (view_email(config, member))
+                    }
                 }
                 @if let Some(supporters) = supporters {
                     @if !supporters.is_empty() {
                         br;
                         strong { "Supporters: " }
-                        @for (i, vote) in supporters.iter().enumerate() {
+                        @for (i, vote) in supporters[..n_supporters_shown].iter().enumerate() {
                             @if i > 0 { ",\u{2002}" }
                             (view_email(config, &vote.voter_email))
                             " (" (vote.points) ")"
                         }
+                        @match supporters.len() - n_supporters_shown {
+                            0 => {},
+                            1 => ",\u{2002}+1 other",
+                            n => { ",\u{2002}+" (n) " others" },
+                        }
+                    }
+                }
+            }
+            @if !entry.feedback.is_empty() {
+                div .team-feedback {
+                    strong { "Feedback for your team:" }
+                    ul {
+                        @for feedback in &entry.feedback {
+                            li { (feedback.body) " — " (view_email(config, &feedback.author_email)) }
+                        }
                     }
                 }
             }
             @if matches!(phase, Phase::Registration) {
                 (form_team_actions(config, user, entry.team.id, &entry.member_emails))
+                @if entry.member_emails.contains(&user.email) {
+                    (form_edit_team(config, entry))
+                }
+            }
+            @if matches!(phase, Phase::Presentation) && user.is_admin {
+                (form_team_presented(config, entry.team.id, entry.presented))
             }
-            @if matches!(phase, Phase::Evaluation) {
+            @if matches!(entry.data, TeamData::UserVote { .. }) {
                 label {
                     "Your points: ";
                     @if entry.member_emails.contains(&user.email) {
@@ -342,24 +1052,37 @@ fn view_team(config: &Config, user: &User, phase: Phase, entry: &TeamEntry) -> M
                             value=(user_points);
                     }
                     // Add a span where js will put the computed cost of this
-                    // vote. Don't bother rendering it server side too, we'll
-                    // just run the js after page load, and if you view the page
-                    // with js disabled, it's better to not show anything than
-                    // to show the wrong number.
-                    span .cost id=(format!("cost{}", entry.team.id));
+                    // vote. By default we don't render it server side too,
+                    // we'll just run the js after page load, and if you view
+                    // the page with js disabled, it's better to not show
+                    // anything than to show the wrong number. When enabled, we
+                    // do show the cost of the vote on record, in the same
+                    // format as the js, which then takes over after page load.
+                    span .cost id=(format!("cost{}", entry.team.id)) {
+                        @if config.app.render_vote_cost && has_user_vote {
+                            "(" (view_coins(get_vote_cost(config, user_points).unwrap_or(0))) ")"
+                        }
+                    }
                 }
             }
         }
     }
 }
 
-fn view_phase_admin_form(config: &Config, phase: Phase) -> Markup {
+fn view_phase_admin_form(
+    config: &Config,
+    phase: Phase,
+    coins_to_spend: u32,
+    registration_locked: bool,
+) -> Markup {
     let submit_next = format!("{}/next", config.server.prefix);
     let submit_prev = format!("{}/prev", config.server.prefix);
     let can_next = phase.next() != phase;
     let can_prev = phase.prev() != phase;
     html! {
         form method="post" {
+            // See `PhaseForm::is_stale`.
+            input type="hidden" name="phase" value=(phase.to_str());
             button
                 type="submit"
                 formaction=(submit_prev)
@@ -372,60 +1095,305 @@ fn view_phase_admin_form(config: &Config, phase: Phase) -> Markup {
                 disabled[!can_next]
             { "Next Phase →" }
         }
+        @if matches!(phase, Phase::Registration) {
+            (form_registration_lock(config, registration_locked))
+        }
+        // With categories, every category has its own fixed budget. After the
+        // evaluation, changing the budget would not change anything.
+        @if config.app.categories.is_empty()
+            && matches!(phase, Phase::Registration | Phase::Presentation | Phase::Evaluation)
+        {
+            (form_coins_to_spend(config, coins_to_spend))
+        }
     }
 }
 
-fn view_phases(current: Phase) -> Markup {
-    let here = html! {
-        " " div .here { "We are here" }
-    };
-
+fn form_registration_lock(config: &Config, locked: bool) -> Markup {
     html! {
-        h2 { "Progress" }
-        p { "The hackathon proceeds in five steps:" }
-        ol {
-            li {
-                strong { "Registration" }
-                " — Participants form teams."
-                @if matches!(current, Phase::Registration) { (here) }
-            }
-            li {
-                strong { "Presentation" }
-                " — Teams present what they built."
-                @if matches!(current, Phase::Presentation) { (here) }
-            }
-            li {
-                strong { "Evaluation" }
-                " — Everybody votes for their favorite teams."
-                @if matches!(current, Phase::Evaluation) { (here) }
-            }
-            li {
-                strong { "Revelation" }
-                " — We announce the winners."
-                @if matches!(current, Phase::Revelation) { (here) }
-            }
-            li {
-                strong { "Celebration" }
-                " — The end of the hackathon."
-                @if matches!(current, Phase::Celebration) { (here) }
+        form action=(format!("{}/registration-lock", config.server.prefix)) method="post" {
+            input type="hidden" name="locked" value=(!locked);
+            @if locked {
+                "Registration is locked. "
+                button type="submit" { "Unlock Registration" }
+            } @else {
+                button type="submit" { "Lock Registration" }
             }
         }
     }
 }
 
-fn view_voting_help(config: &Config) -> Markup {
+fn form_coins_to_spend(config: &Config, current: u32) -> Markup {
+    let submit_url = format!("{}/coins", config.server.prefix);
     html! {
-        h2 { "Voting System" }
-        p {
-            "Voting is now open. We are using "
-            em { "quadratic voting" } ". "
-            "It works as follows:"
+        form action=(submit_url) method="post" {
+            label {
+                "Coins to spend: ";
+                input name="coins-to-spend" type="number" min="1" value=(current);
+            }
+            button type="submit" { "Set Budget" }
+        }
+    }
+}
+
+fn view_banner(message: &str) -> Markup {
+    // Key the dismissal on the message, so that when the admin changes the
+    // banner, it shows up again for people who dismissed the previous one.
+    let dismiss_key = format!("banner-{:016x}", hash::siphash13(0, 0, message.as_bytes()));
+    html! {
+        div .banner data-dismiss-key=(dismiss_key) {
+            p { (message) }
+            button .dismiss type="button" { "Dismiss" }
+        }
+    }
+}
+
+/// Render an overlay that explains the current phase to first-time visitors.
+///
+/// The overlay is keyed on the phase, so once dismissed it stays away until the
+/// next phase starts. It starts out hidden and the dismiss script shows it, so
+/// without Javascript it does not block the page with no way to close it.
+fn view_onboarding(config: &Config, phase: Phase, coins_to_spend: u32) -> Markup {
+    let vote_cost = match config.app.vote_cost {
+        VoteCost::Quadratic => "the cost of a vote is the square of the points you give a team",
+        VoteCost::Linear => "every point costs one coin",
+    };
+    html! {
+        div .onboarding data-dismiss-key=(format!("onboarding-{}", phase.to_str())) hidden {
+            div .onboarding-content {
+                h2 { "Welcome!" }
+                p {
+                    "This is where we run the hackathon. "
+                    @if config.app.judge_emails.is_empty() {
+                        "It proceeds in five steps: registration, presentation, "
+                        "evaluation, revelation, and celebration. "
+                    } @else {
+                        "It proceeds in six steps: registration, presentation, "
+                        "evaluation, judging, revelation, and celebration. "
+                    }
+                    "We are now in the " strong { (phase.to_str()) } " phase."
+                }
+                p {
+                    @match phase {
+                        Phase::Registration => {
+                            "Teams are forming. Add your team on this page, "
+                            "or join a team that somebody already added."
+                        }
+                        Phase::Presentation => {
+                            "Teams are presenting what they built. "
+                            "Keep an eye out for the ones you like, you can vote for them next."
+                        }
+                        Phase::Evaluation if !config.app.categories.is_empty() => {
+                            "Voting is open, for every award separately. You get coins "
+                            "to award points to teams, and " (vote_cost) ". "
+                            "You can't vote for your own team."
+                        }
+                        Phase::Evaluation => {
+                            "Voting is open. You get " (coins_to_spend) " coins "
+                            "to award points to teams, and " (vote_cost) ". "
+                            "You can't vote for your own team."
+                        }
+                        Phase::Judging => {
+                            "Voting is closed, and now the judges cast their votes. "
+                            "We announce the winners next."
+                        }
+                        Phase::Revelation => {
+                            "Voting is closed, and we are announcing the winners. "
+                            "Follow along with the ceremony."
+                        }
+                        Phase::Celebration => {
+                            "The hackathon is over. Scroll down to see the outcome, "
+                            "and let us know what you thought of it."
+                        }
+                    }
+                }
+                button .dismiss type="button" { "Got it" }
+            }
+        }
+    }
+}
+
+fn form_banner(config: &Config, current: Option<&str>) -> Markup {
+    let submit_url = format!("{}/banner", config.server.prefix);
+    html! {
+        form action=(submit_url) method="post" {
+            label {
+                "Banner: ";
+                input name="message" value=(current.unwrap_or(""));
+            }
+            button type="submit" { "Set Banner" }
+            " "
+            button type="submit" name="message" value="" { "Clear Banner" }
+        }
+    }
+}
+
+fn form_import_participants(config: &Config, n_participants: i64) -> Markup {
+    let submit_url = format!("{}/import-participants", config.server.prefix);
+    html! {
+        details {
+            summary {
+                "Import participants ("
+                @match n_participants {
+                    0 => "none yet",
+                    1 => "1 so far",
+                    n => { (n) " so far" },
+                }
+                ")"
+            }
+            p {
+                "One email address per line. Importing adds to the list, "
+                "it does not remove anybody. "
+                @if config.app.restrict_vote_to_participants {
+                    "Only people on the list can vote, and the list tells who did not vote yet."
+                } @else {
+                    "The list is only used to tell who did not vote yet, "
+                    "also people who are not on it can vote."
+                }
+            }
+            form action=(submit_url) method="post" {
+                textarea name="emails" rows="6" cols="40" {}
+                br;
+                button type="submit" { "Import" }
+            }
+        }
+    }
+}
+
+fn view_non_voters(config: &Config, n_participants: i64, non_voters: &[String]) -> Markup {
+    html! {
+        p {
+            @match non_voters.len() {
+                0 => { "All " (n_participants) " participants have voted." },
+                1 => { "1 of " (n_participants) " participants still needs to vote:" },
+                n => { (n) " of " (n_participants) " participants still need to vote:" },
+            }
+        }
+        @if !non_voters.is_empty() {
+            ul {
+                @for email in non_voters {
+                    li { (view_email(config, email)) }
+                }
+            }
+        }
+    }
+}
+
+fn view_phases(config: &Config, current: Phase) -> Markup {
+    let here = html! {
+        " " div .here { "We are here" }
+    };
+    let has_judging = !config.app.judge_emails.is_empty();
+
+    html! {
+        h2 { "Progress" }
+        p {
+            "The hackathon proceeds in "
+            @if has_judging { "six" } @else { "five" }
+            " steps:"
+        }
+        ol {
+            li {
+                strong { "Registration" }
+                " — Participants form teams."
+                @if matches!(current, Phase::Registration) { (here) }
+            }
+            li {
+                strong { "Presentation" }
+                " — Teams present what they built."
+                @if matches!(current, Phase::Presentation) { (here) }
+            }
+            li {
+                strong { "Evaluation" }
+                " — Everybody votes for their favorite teams."
+                @if matches!(current, Phase::Evaluation) { (here) }
+            }
+            @if has_judging {
+                li {
+                    strong { "Judging" }
+                    " — The judges cast their votes."
+                    @if matches!(current, Phase::Judging) { (here) }
+                }
+            }
+            li {
+                strong { "Revelation" }
+                " — We announce the winners."
+                @if matches!(current, Phase::Revelation) { (here) }
+            }
+            li {
+                strong { "Celebration" }
+                " — The end of the hackathon."
+                @if matches!(current, Phase::Celebration) { (here) }
+            }
+        }
+    }
+}
+
+fn view_vote_eligibility(eligibility: VoteEligibility) -> Markup {
+    let n_eligible = eligibility.n_teams - eligibility.n_own_teams;
+    let noun = if n_eligible == 1 { "team" } else { "teams" };
+    html! {
+        p .notice {
+            @match eligibility.n_own_teams {
+                0 => "You can vote for every team.",
+                1 => {
+                    "You can vote for " (n_eligible) " " (noun) ". "
+                    "You can't vote for your own team."
+                },
+                n => {
+                    "You can vote for " (n_eligible) " " (noun) ". "
+                    "You can't vote for the " (n) " teams that you are a member of."
+                },
+            }
+        }
+    }
+}
+
+fn view_cheaters(config: &Config, cheaters: &[String]) -> Markup {
+    html! {
+        p { "The following people tried to cheat and vote for themselves:" }
+        ul {
+            @for cheater_email in cheaters {
+                li { (view_email(config, cheater_email)) }
+            }
+        }
+    }
+}
+
+fn view_voting_help(config: &Config, coins_to_spend: u32) -> Markup {
+    html! {
+        h2 { "Voting System" }
+        @match config.app.vote_cost {
+            VoteCost::Quadratic => p {
+                "Voting is now open. We are using "
+                em { "quadratic voting" } ". "
+                "It works as follows:"
+            },
+            VoteCost::Linear => p { "Voting is now open. It works as follows:" },
         }
         ol {
-            li { "You get " (config.app.coins_to_spend) " " em { "coins" } "." }
+            @if config.app.categories.is_empty() {
+                li { "You get " (coins_to_spend) " " em { "coins" } "." }
+            } @else {
+                li { "You get " em { "coins" } " for every award, to spend on that award only." }
+            }
             li { "You can spend coins to give teams " em { "points" } "." }
-            li { "The cost in coins is the square of the points you award per team." }
+            @match config.app.vote_cost {
+                VoteCost::Quadratic => li { "The cost in coins is the square of the points you award per team." },
+                VoteCost::Linear => li { "Every point costs one coin." },
+            }
+            @if let Some(cap) = config.app.max_points_per_team {
+                li { "You can give at most " (cap) " points to a single team." }
+            }
         }
+        @match config.app.vote_cost {
+            VoteCost::Quadratic => (view_quadratic_voting_examples(config, coins_to_spend)),
+            VoteCost::Linear => p { "You can spread your coins over the teams however you like." },
+        }
+    }
+}
+
+fn view_quadratic_voting_examples(config: &Config, coins_to_spend: u32) -> Markup {
+    html! {
         p {
             "This means that if you " em { "really" } " like one team, "
             "you can spend all your coins on them, "
@@ -457,33 +1425,214 @@ fn view_voting_help(config: &Config) -> Markup {
         }
         p {
             "Depending on how you vote, "
-            "it may not be possible spend exactly " (config.app.coins_to_spend) " coins. "
+            @if config.app.categories.is_empty() {
+                "it may not be possible spend exactly " (coins_to_spend) " coins. "
+            } @else {
+                "it may not be possible to spend all of your coins. "
+            }
             "It is okay to leave a few coins unspent."
         }
     }
 }
 
-fn form_create_team(config: &Config) -> Markup {
+/// Generate a fresh key to identify a single form submission.
+///
+/// This only needs to be unique per user, not unpredictable, so the randomly
+/// seeded hasher from the standard library is good enough.
+fn new_idempotency_key() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    hasher.write_u128(now.as_nanos());
+    format!("{:016x}", hasher.finish())
+}
+
+/// Generate the salt that anonymizes voters in the dataset, see `handle_dataset_json`.
+///
+/// The standard library seeds its hashers from the operating system's random
+/// number generator, so with unique inputs, the outputs are unpredictable.
+pub fn new_dataset_salt() -> String {
+    let mut salt = String::with_capacity(32);
+    for i in 0..2_u8 {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u8(i);
+        salt.push_str(&format!("{:016x}", hasher.finish()));
+    }
+    salt
+}
+
+fn form_event_feedback(config: &Config, current: Option<&db::EventFeedback>) -> Markup {
+    let submit_url = format!("{}/feedback", config.server.prefix);
+    let current_rating = current.map(|f| f.rating).unwrap_or(0);
+    html! {
+        h2 { "Feedback" }
+        p {
+            "How was the hackathon? "
+            @if current.is_some() {
+                "Thanks for your feedback! You can still change it."
+            }
+        }
+        form .feedback action=(submit_url) method="post" {
+            fieldset .stars {
+                @for rating in 1..=5 {
+                    label title=(format!("{rating} out of 5")) {
+                        input
+                            type="radio"
+                            name="rating"
+                            value=(rating)
+                            checked[rating == current_rating];
+                        " " (rating) " ★"
+                    }
+                }
+            }
+            label {
+                "Comment (optional): ";
+                input name="comment" value=(current.map(|f| f.comment.as_str()).unwrap_or(""));
+            }
+            button type="submit" { "Submit Feedback" }
+        }
+    }
+}
+
+fn view_event_feedback_summary(feedback: &[db::EventFeedback]) -> Markup {
+    let mut counts = [0_u32; 5];
+    for f in feedback {
+        counts[(f.rating - 1) as usize] += 1;
+    }
+    let total: i64 = feedback.iter().map(|f| f.rating).sum();
+    html! {
+        h3 { "Feedback Summary" }
+        @if feedback.is_empty() {
+            p { "Nobody has given feedback yet." }
+        } @else {
+            p {
+                "The average rating is "
+                (format!("{:.1}", total as f64 / feedback.len() as f64))
+                " out of 5, from " (feedback.len()) " responses."
+            }
+            ul {
+                @for (i, count) in counts.iter().enumerate().rev() {
+                    li { ((i + 1)) " ★: " (count) }
+                }
+            }
+            ul {
+                @for f in feedback {
+                    @if !f.comment.is_empty() {
+                        li { "(" (f.rating) " ★) " (f.comment) }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn form_create_team(config: &Config, submitted: Option<&CreateTeamForm>) -> Markup {
     let submit_url = format!("{}/create-team", config.server.prefix);
+    let error_for = |field: Option<CreateTeamField>| match submitted.and_then(|f| f.error.as_ref())
+    {
+        Some((f, message)) if *f == field => Some(message.as_str()),
+        _ => None,
+    };
+    let value = |get: fn(&CreateTeamForm) -> &str| submitted.map_or("", get);
+    // A rejected submission did not create a team, so its key is still unused.
+    let idempotency_key = match submitted {
+        Some(f) if !f.idempotency_key.is_empty() => f.idempotency_key.clone(),
+        _ => new_idempotency_key(),
+    };
+    let team_name_error = error_for(Some(CreateTeamField::TeamName));
+    let description_error = error_for(Some(CreateTeamField::Description));
+    let project_url_error = error_for(Some(CreateTeamField::ProjectUrl));
     html! {
         form action=(submit_url) method="post" {
+            @if let Some(message) = error_for(None) {
+                p .field-error { (message) }
+            }
+            // If the browser submits this form twice, for example because the
+            // user got impatient on a slow connection, then the key enables us
+            // to recognize the second submission and not create a second team.
+            input type="hidden" name="idempotency-key" value=(idempotency_key);
             label {
                 "Team name: ";
-                input name="team-name";
+                input
+                    name="team-name"
+                    value=(value(|f| &f.team_name))
+                    autofocus[team_name_error.is_some()];
+            }
+            @if let Some(message) = team_name_error {
+                p .field-error { (message) }
             }
             label {
                 "One-line description: ";
-                input name="description";
+                input
+                    name="description"
+                    value=(value(|f| &f.description))
+                    autofocus[description_error.is_some()];
+            }
+            @if let Some(message) = description_error {
+                p .field-error { (message) }
+            }
+            label {
+                "Project url (optional): ";
+                input
+                    name="project-url"
+                    type="url"
+                    placeholder="https://"
+                    value=(value(|f| &f.project_url))
+                    autofocus[project_url_error.is_some()];
+            }
+            @if let Some(message) = project_url_error {
+                p .field-error { (message) }
             }
             button type="submit" { "Create Team" }
         }
     }
 }
 
+fn form_edit_team(config: &Config, entry: &TeamEntry) -> Markup {
+    let team = &entry.team;
+    let submit_url = format!("{}/edit-team", config.server.prefix);
+    html! {
+        details {
+            summary { "Edit team" }
+            form action=(submit_url) method="post" {
+                input type="hidden" name="team-id" value=(team.id);
+                label {
+                    "Team name: ";
+                    input name="team-name" value=(team.name);
+                }
+                label {
+                    "One-line description: ";
+                    input name="description" value=(team.description);
+                }
+                button type="submit" { "Save Changes" }
+            }
+            @if entry.member_emails.len() > 1 {
+                form action=(format!("{}/set-captain", config.server.prefix)) method="post" {
+                    input type="hidden" name="team-id" value=(team.id);
+                    label {
+                        "Captain: ";
+                        select name="member-email" {
+                            @for member in &entry.member_emails {
+                                option
+                                    value=(member)
+                                    selected[entry.captain_email.as_ref() == Some(member)]
+                                { (view_email(config, member)) }
+                            }
+                        }
+                    }
+                    button type="submit" { "Set Captain" }
+                }
+            }
+        }
+    }
+}
+
 fn form_team_actions(config: &Config, user: &User, team_id: i64, members: &[String]) -> Markup {
     // Linear search, I know I know. Teams are small anyway.
     let is_member = members.contains(&user.email);
     let is_singleton = members.len() == 1;
+    let is_full = is_team_full(config, members.len());
 
     let (slug, label) = if is_member && is_singleton {
         ("delete-team", "Delete Team")
@@ -497,87 +1646,386 @@ fn form_team_actions(config: &Config, user: &User, team_id: i64, members: &[Stri
     html! {
         form action=(submit_url) method="post" {
             input type="hidden" name="team-id" value=(team_id);
-            button type="submit" { (label) }
+            @if is_full && !is_member {
+                button type="submit" disabled { "Team Full" }
+            } @else {
+                button type="submit" { (label) }
+            }
+        }
+        // The admin can delete any team, not just their own empty ones.
+        @if user.is_admin && slug != "delete-team" {
+            form action=(format!("{}/delete-team", config.server.prefix)) method="post" {
+                input type="hidden" name="team-id" value=(team_id);
+                button type="submit" { "Delete Team" }
+            }
         }
     }
 }
 
-pub fn handle_index(
-    config: &Config,
-    tx: &mut db::Transaction,
-    user: &User,
-) -> db::Result<Response> {
-    let phase = crate::load_phase(tx)?;
+/// Return whether a team with this many members can't take any more.
+fn is_team_full(config: &Config, n_members: usize) -> bool {
+    match config.app.max_team_size {
+        Some(max_size) => n_members >= max_size as usize,
+        None => false,
+    }
+}
 
-    let teams = db::iter_teams(tx)?.collect::<Result<Vec<_>, _>>()?;
-    let mut team_entries = Vec::with_capacity(teams.len());
+/// The forms that the favorite buttons of the teams submit, see `view_favorite_toggle`.
+fn form_favorite(config: &Config) -> Markup {
+    html! {
+        form #favorite-form action=(format!("{}/favorite", config.server.prefix)) method="post" {}
+        form #unfavorite-form action=(format!("{}/unfavorite", config.server.prefix)) method="post" {}
+    }
+}
 
-    for team in teams {
-        let mut total_points = 0;
-        let members = db::iter_team_members(tx, team.id)?.collect::<Result<Vec<_>, _>>()?;
-        let data = match phase {
-            Phase::Registration | Phase::Presentation => TeamData::None,
-            Phase::Evaluation => {
-                let points = db::get_team_vote_for(tx, team.id, &user.email)?;
-                TeamData::UserVote {
-                    points: points.unwrap_or(0),
-                }
-            }
-            Phase::Revelation | Phase::Celebration => {
-                if user.can_see_outcome(phase) {
-                    let votes = db::iter_team_votes(tx, team.id)?.collect::<Result<Vec<_>, _>>()?;
-                    // The votes have been validated, so this should not
-                    // overflow unless we have a crazy number of voters.
-                    total_points = votes.iter().map(|v| v.points).sum();
-                    TeamData::AllVotes { votes: votes }
-                } else {
-                    TeamData::None
-                }
+/// The star that marks a team as a favorite of the user, or unmarks it.
+///
+/// During the vote the teams are inside the vote form, and forms can't nest,
+/// so the button submits one of the empty forms from `form_favorite`, with
+/// the team id as the value of the button.
+fn view_favorite_toggle(team_id: i64, is_favorite: bool) -> Markup {
+    let (form_id, label, title) = if is_favorite {
+        (
+            "unfavorite-form",
+            "★ Favorite",
+            "Remove this team from your favorites.",
+        )
+    } else {
+        (
+            "favorite-form",
+            "☆ Add to Favorites",
+            "Remember this team for the vote. Only you can see your favorites.",
+        )
+    };
+    html! {
+        p .favorite-toggle {
+            button type="submit" form=(form_id) name="team-id" value=(team_id) title=(title) {
+                (label)
             }
-        };
-
-        let entry = TeamEntry {
-            team,
-            data,
-            total_points,
-            member_emails: members,
-            rank: 0,
-        };
-        team_entries.push(entry);
+        }
     }
+}
 
-    // During the voting phase, shuffle all teams randomly for every user. This
-    // is to try and eliminate (or at least average out) bias for voting for the
-    // first or last entry. The sort key is pseudorandom but deterministic.
-    // Every user gets a different order, but for that user, the order is the
-    // same on every page load, even across restarts of the binary.
-    if matches!(phase, Phase::Evaluation) {
-        for entry in team_entries.iter_mut() {
-            let mut hasher = DefaultHasher::new();
-            hasher.write(user.email.as_bytes());
-            hasher.write_i64(entry.team.id);
-            // We abuse the total points field to store the sort key in,
-            // it's not used during the voting phase anyway.
-            entry.total_points = hasher.finish() as i64;
+fn form_team_presented(config: &Config, team_id: i64, presented: bool) -> Markup {
+    let (slug, label) = if presented {
+        ("unmark-presented", "Unmark as Presented")
+    } else {
+        ("mark-presented", "Mark as Presented")
+    };
+    let submit_url = format!("{}/{}", config.server.prefix, slug);
+    html! {
+        form action=(submit_url) method="post" {
+            input type="hidden" name="team-id" value=(team_id);
+            button type="submit" { (label) }
         }
-        team_entries.sort_by_key(|entry| entry.total_points);
     }
+}
 
-    // If we are displaying points, sort and compute the rank.
-    if user.can_see_outcome(phase) {
-        team_entries.sort_by_key(|entry| (-entry.total_points, entry.team.id));
-        let mut rank = 0;
-        let mut prev_points = -1;
-        for entry in team_entries.iter_mut() {
-            // Teams that have the same number of points have the same rank.
-            // I briefly considered breaking ties by the number of voters, but
-            // that would kind of defeat the purpose of quadratic voting, so
-            // let's keep it at points only.
-            if entry.total_points != prev_points {
-                rank += 1;
-                prev_points = entry.total_points;
-            }
-            entry.rank = rank;
+/// Compute the total points of a team, using the configured scoring strategy.
+fn compute_team_total(scoring: &Scoring, votes: &[db::Vote]) -> i64 {
+    // The votes have been validated, so this should not overflow unless we
+    // have a crazy number of voters.
+    match scoring {
+        Scoring::Sum => votes.iter().map(|v| v.points).sum(),
+        Scoring::Capped(cap) => votes.iter().map(|v| v.points.min(*cap)).sum(),
+        Scoring::SqrtDampened => {
+            let total: f64 = votes.iter().map(|v| (v.points as f64).sqrt()).sum();
+            total.round() as i64
+        }
+    }
+}
+
+/// Return the total of a team, including the weighted points from the judges.
+///
+/// The judges don't vote per category, their points count towards the first.
+fn compute_team_total_with_judges(
+    config: &Config,
+    tx: &mut db::Transaction,
+    team_id: i64,
+    category_id: i64,
+    votes: &[db::Vote],
+) -> db::Result<i64> {
+    let crowd_points = compute_team_total(&config.app.scoring, votes);
+    if category_id != 0 {
+        return Ok(crowd_points);
+    }
+    let judge_points = db::get_team_judge_points(tx, team_id)?;
+    let weight = config.app.judge_weight.unwrap_or(1.0);
+    Ok(crowd_points + (judge_points as f64 * weight).round() as i64)
+}
+
+/// Return the number of coins that a voter can spend in the given phase.
+///
+/// The admin can change the budget while the event runs, so it is stored in
+/// the settings, see `handle_set_coins_to_spend`. The config only seeds it.
+fn get_coins_to_spend(config: &Config, tx: &mut db::Transaction, phase: Phase) -> db::Result<u32> {
+    let coins_to_spend = db::get_setting(tx, "coins_to_spend")?
+        .and_then(|value| u32::from_str(&value).ok())
+        .unwrap_or(config.app.coins_to_spend);
+    let result = match phase {
+        Phase::Judging => config.app.judge_coins_to_spend.unwrap_or(coins_to_spend),
+        _ => coins_to_spend,
+    };
+    Ok(result)
+}
+
+/// Sort the teams from first to last and assign their rank.
+///
+/// This assumes the total points of every entry have been computed.
+fn rank_team_entries(config: &Config, team_entries: &mut [TeamEntry]) {
+    let is_eligible =
+        |entry: &TeamEntry| entry.presented || !config.app.require_presented_for_ranking;
+    let tiebreak_key = |entry: &TeamEntry| match config.app.tiebreak {
+        // The sort is stable, so a constant key preserves the order.
+        Tiebreak::None => 0,
+        Tiebreak::TeamId => entry.team.id,
+        Tiebreak::Earliest => entry.last_vote_id,
+        Tiebreak::FewestMembers => entry.member_emails.len() as i64,
+        Tiebreak::MostVoters => -(entry.n_voters as i64),
+    };
+    // Teams that are not eligible for a rank go after all ranked teams.
+    team_entries.sort_by_key(|entry| {
+        (
+            !is_eligible(entry),
+            -entry.total_points,
+            tiebreak_key(entry),
+        )
+    });
+    let mut rank = 0;
+    let mut prev_points = -1;
+    for entry in team_entries.iter_mut() {
+        if !is_eligible(entry) {
+            continue;
+        }
+        // Teams that have the same number of points have the same rank.
+        // I briefly considered breaking ties by the number of voters, but
+        // that would kind of defeat the purpose of quadratic voting, so
+        // let's keep it at points only.
+        if entry.total_points != prev_points {
+            rank += 1;
+            prev_points = entry.total_points;
+        }
+        entry.rank = rank;
+    }
+}
+
+/// Load the finalized standings of the category by team id, see `handle_finalize`.
+///
+/// The standings are only frozen during the celebration, in other phases and
+/// when the admin did not finalize them, this is empty.
+fn load_results_cache(
+    tx: &mut db::Transaction,
+    phase: Phase,
+    category_id: i64,
+) -> db::Result<HashMap<i64, db::CachedResult>> {
+    let mut result = HashMap::new();
+    if !matches!(phase, Phase::Celebration) {
+        return Ok(result);
+    }
+    for cached in db::iter_cached_results(tx, category_id)? {
+        let cached = cached?;
+        result.insert(cached.team_id, cached);
+    }
+    Ok(result)
+}
+
+/// Take the rank and order of the teams from the finalized standings.
+///
+/// Teams that did not exist when the admin finalized go last, without a rank.
+fn apply_results_cache(cache: &HashMap<i64, db::CachedResult>, team_entries: &mut [TeamEntry]) {
+    for entry in team_entries.iter_mut() {
+        match cache.get(&entry.team.id) {
+            Some(cached) => {
+                entry.rank = cached.rank as u32;
+                entry.total_points = cached.total_points;
+            }
+            None => entry.rank = 0,
+        }
+    }
+    team_entries.sort_by_key(|entry| cache.get(&entry.team.id).map_or(i64::MAX, |c| c.id));
+}
+
+/// Return the key to sort a team by in the shuffled order that a user sees.
+///
+/// This is SipHash-1-3 with zero keys over the email followed by the team id
+/// in little-endian. That is what `DefaultHasher` computed when we used it, so
+/// on little-endian machines, the order is the same as before.
+fn get_shuffle_key(email: &str, team_id: i64) -> i64 {
+    let mut message = Vec::with_capacity(email.len() + 8);
+    message.extend_from_slice(email.as_bytes());
+    message.extend_from_slice(&team_id.to_le_bytes());
+    hash::siphash13(0, 0, &message) as i64
+}
+
+pub fn handle_index(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    query: &str,
+) -> db::Result<Response> {
+    render_index(config, tx, user, query, None)
+}
+
+/// Render the index, optionally with a create team form that failed to submit.
+fn render_index(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    query: &str,
+    create_team: Option<&CreateTeamForm>,
+) -> db::Result<Response> {
+    // A link with `?team=ID` goes to that team. Unlike a `#team-ID` anchor,
+    // we get to see it, so we can tell people when the team no longer exists.
+    let linked_team_id = form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _value)| key == "team")
+        .and_then(|(_key, value)| i64::from_str(value.as_ref()).ok());
+    let phase = crate::load_phase(tx)?;
+    let can_vote = user.can_vote(phase) && !is_excluded_from_vote(config, tx, user, phase)?;
+
+    // The vote form must contain all teams, so we only paginate when the user
+    // can't vote. A page size of zero would mean no pages, so that's off too.
+    let page_size = match config.app.teams_per_page {
+        Some(n) if n > 0 && !can_vote => Some(n as usize),
+        _ => None,
+    };
+
+    let mut missing_team = false;
+    if let Some(team_id) = linked_team_id {
+        if db::get_team(tx, team_id)?.is_none() {
+            missing_team = true;
+        } else if page_size.is_none() {
+            let new_url = format!("{}#team-{}", config.server.prefix, team_id);
+            return Ok(redirect_see_other(new_url.as_bytes()));
+        }
+        // With pages, we redirect once we know which page the team is on.
+    }
+
+    let teams = db::iter_teams(tx)?.collect::<Result<Vec<_>, _>>()?;
+    let presented_teams = db::iter_presented_teams(tx)?.collect::<Result<Vec<_>, _>>()?;
+    let results_cache = load_results_cache(tx, phase, 0)?;
+    let favorites = match phase {
+        Phase::Presentation | Phase::Evaluation => {
+            db::iter_favorites(tx, &user.email)?.collect::<Result<Vec<_>, _>>()?
+        }
+        _ => Vec::new(),
+    };
+    let mut team_entries = Vec::with_capacity(teams.len());
+
+    for team in teams {
+        let mut total_points = 0;
+        let mut last_vote_id = 0;
+        let mut n_voters = 0;
+        let (members, captain) = load_team_members(tx, team.id)?;
+        let data = match phase {
+            Phase::Registration | Phase::Presentation => TeamData::None,
+            Phase::Evaluation if !can_vote => TeamData::None,
+            Phase::Evaluation if !config.app.categories.is_empty() => {
+                let mut points = Vec::with_capacity(config.app.categories.len());
+                for category_id in 0..config.app.categories.len() {
+                    let p = db::get_team_vote_for(tx, team.id, &user.email, category_id as i64)?;
+                    points.push(p.unwrap_or(0));
+                }
+                TeamData::CategoryVotes { points }
+            }
+            Phase::Evaluation => {
+                let points = db::get_team_vote_for(tx, team.id, &user.email, 0)?;
+                TeamData::UserVote {
+                    points: points.unwrap_or(0),
+                }
+            }
+            Phase::Judging if user.is_judge => {
+                let points = db::get_team_judge_vote_for(tx, team.id, &user.email)?;
+                TeamData::UserVote {
+                    points: points.unwrap_or(0),
+                }
+            }
+            Phase::Judging => TeamData::None,
+            // With categories, the outcome is shown per category, not per team.
+            Phase::Revelation | Phase::Celebration if !config.app.categories.is_empty() => {
+                TeamData::None
+            }
+            Phase::Revelation | Phase::Celebration => {
+                if user.can_see_outcome(phase) {
+                    let votes =
+                        db::iter_team_votes(tx, team.id, 0)?.collect::<Result<Vec<_>, _>>()?;
+                    total_points = match results_cache.get(&team.id) {
+                        // Once finalized, votes no longer change the outcome.
+                        Some(cached) => cached.total_points,
+                        None => compute_team_total_with_judges(config, tx, team.id, 0, &votes)?,
+                    };
+                    last_vote_id = votes.iter().map(|v| v.id).max().unwrap_or(0);
+                    n_voters = votes.len();
+                    TeamData::AllVotes { votes }
+                } else {
+                    TeamData::None
+                }
+            }
+        };
+
+        // Only the members read the feedback on their team, once it's over.
+        let feedback = if matches!(phase, Phase::Celebration) && members.contains(&user.email) {
+            db::iter_team_feedback(tx, team.id)?.collect::<Result<Vec<_>, _>>()?
+        } else {
+            Vec::new()
+        };
+
+        let entry = TeamEntry {
+            presented: presented_teams.contains(&team.id),
+            favorite: favorites.contains(&team.id),
+            feedback,
+            team,
+            data,
+            total_points,
+            last_vote_id,
+            n_voters,
+            member_emails: members,
+            captain_email: captain,
+            rank: 0,
+        };
+        team_entries.push(entry);
+    }
+
+    // During the voting phase, shuffle all teams randomly for every user. This
+    // is to try and eliminate (or at least average out) bias for voting for the
+    // first or last entry. The sort key is pseudorandom but deterministic.
+    // Every user gets a different order, but for that user, the order is the
+    // same on every page load, even across restarts of the binary, and across
+    // upgrades of the compiler, see `get_shuffle_key`.
+    if can_vote && config.app.shuffle_during_evaluation {
+        for entry in team_entries.iter_mut() {
+            // We abuse the total points field to store the sort key in,
+            // it's not used during the voting phase anyway.
+            entry.total_points = get_shuffle_key(&user.email, entry.team.id);
+        }
+        team_entries.sort_by_key(|entry| entry.total_points);
+    }
+
+    // Without the shuffle, everybody gets the same order, and alphabetical is
+    // the order that is easiest to find a team in.
+    if can_vote && !config.app.shuffle_during_evaluation {
+        team_entries.sort_by_key(|entry| entry.team.name.to_lowercase());
+    }
+
+    // During registration, the user picks the order. The query returns the
+    // newest team first already.
+    let team_sort = TeamSort::from_query(query);
+    if matches!(phase, Phase::Registration) {
+        match team_sort {
+            TeamSort::Newest => {}
+            TeamSort::Oldest => team_entries.sort_by(|a, b| {
+                (&a.team.created_at, a.team.id).cmp(&(&b.team.created_at, b.team.id))
+            }),
+            TeamSort::Name => team_entries.sort_by_key(|entry| entry.team.name.to_lowercase()),
+        }
+    }
+
+    // If we are displaying points, sort and compute the rank.
+    let mut category_rankings = Vec::new();
+    if user.can_see_outcome(phase) && config.app.categories.is_empty() {
+        if results_cache.is_empty() {
+            rank_team_entries(config, &mut team_entries);
+        } else {
+            apply_results_cache(&results_cache, &mut team_entries);
         }
 
         // Normally you want to see the teams from first to last. But during the
@@ -587,363 +2035,2934 @@ pub fn handle_index(
         if matches!(phase, Phase::Revelation) {
             team_entries.reverse();
         }
+    } else if user.can_see_outcome(phase) {
+        for (category_id, category) in config.app.categories.iter().enumerate() {
+            let mut teams = load_ranked_team_entries(config, tx, category_id as i64)?;
+            // Same as above, the ceremony goes from last to first.
+            if matches!(phase, Phase::Revelation) {
+                teams.reverse();
+            }
+            category_rankings.push(CategoryRanking {
+                name: &category.name,
+                teams,
+            });
+        }
+    }
+
+    // Pages are 1-based, and a page out of range shows the nearest one.
+    let n_teams = team_entries.len();
+    let mut pagination = None;
+    if let Some(page_size) = page_size {
+        let n_pages = n_teams.div_ceil(page_size).max(1);
+        let linked_index = match linked_team_id {
+            Some(team_id) if !missing_team => team_entries
+                .iter()
+                .position(|entry| entry.team.id == team_id),
+            _ => None,
+        };
+        if let (Some(index), Some(team_id)) = (linked_index, linked_team_id) {
+            let new_url = format!(
+                "{}?page={}#team-{}",
+                config.server.prefix,
+                index / page_size + 1,
+                team_id
+            );
+            return Ok(redirect_see_other(new_url.as_bytes()));
+        }
+        let page = form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _value)| key == "page")
+            .and_then(|(_key, value)| usize::from_str(value.as_ref()).ok())
+            .unwrap_or(1)
+            .clamp(1, n_pages);
+        let start = (page - 1) * page_size;
+        team_entries.truncate(start + page_size);
+        team_entries.drain(..start.min(team_entries.len()));
+        if n_pages > 1 {
+            pagination = Some(Pagination {
+                current: page,
+                n_pages,
+            });
+        }
     }
 
     let cheaters = db::iter_cheaters(tx)?.collect::<Result<Vec<_>, _>>()?;
-    let voter_count = db::count_voters(tx)?;
+    let cheat_attempts = db::count_cheat_attempts(tx)?;
+    let n_participants = if user.is_admin {
+        db::count_participants(tx)?
+    } else {
+        0
+    };
+    let non_voters = if user.is_admin && matches!(phase, Phase::Evaluation) {
+        db::iter_non_voters(tx)?.collect::<Result<Vec<_>, _>>()?
+    } else {
+        Vec::new()
+    };
+    let voter_count = if config.app.exclude_admin_from_turnout {
+        db::count_voters_except(tx, &config.app.admin_email)?
+    } else {
+        db::count_voters(tx)?
+    };
+    let banner = db::get_setting(tx, "banner")?;
+    let feedback = db::get_event_feedback(tx, &user.email)?;
+    let vote_eligibility = if can_vote {
+        let own_teams = db::iter_member_teams(tx, &user.email)?.collect::<Result<Vec<_>, _>>()?;
+        Some(VoteEligibility {
+            n_teams: team_entries.len(),
+            n_own_teams: own_teams.len(),
+        })
+    } else {
+        None
+    };
+    let all_feedback = if user.is_admin && matches!(phase, Phase::Celebration) {
+        db::iter_event_feedback(tx)?.collect::<Result<Vec<_>, _>>()?
+    } else {
+        Vec::new()
+    };
 
     let data = IndexData {
         phase,
+        // An empty banner is how the admin clears it.
+        banner: banner.as_deref().filter(|b| !b.is_empty()),
         teams: &team_entries,
+        n_teams,
         cheaters: &cheaters,
+        cheat_attempts,
         voter_count: voter_count as u32,
+        feedback,
+        all_feedback: &all_feedback,
+        missing_team,
+        category_rankings: &category_rankings,
+        coins_to_spend: get_coins_to_spend(config, tx, phase)?,
+        pagination,
+        team_sort,
+        results_finalized_at: db::get_results_finalized_at(tx)?,
+        results_revealed: are_results_revealed(tx)?,
+        registration_locked: is_registration_locked(tx)?,
+        can_vote,
+        vote_eligibility,
+        n_participants,
+        non_voters: &non_voters,
+        create_team,
     };
 
-    let body = view_index(config, &user, data);
+    let body = view_index(config, user, data);
     Ok(respond_html(body))
 }
 
-/// Validate user inputs against length limits and Unicode subset.
-///
-/// Users should be able to input text, but allowing any Unicode code point
-/// creates a can of worms where you can use distracting emoji, or reverse the
-/// text direction for all following content, use the mathematical symbols to do
-/// "markup", etc. So ban most of Unicode, but allow more than just ASCII
-/// because Tomás and Mikołaj are valid non-ASCII names. This is very crude but
-/// it'll do.
+/// Compute a fingerprint of the votes that a voter has on record.
 ///
-/// Returns a description of the violaton on error.
-fn validate_string(label: &'static str, max_len: usize, input: &str) -> Result<(), String> {
-    if input.is_empty() {
-        return Err(format!("{label} must not be empty."));
-    }
-
-    if input.len() > max_len {
-        return Err(format!("{label} may not be longer than {max_len} bytes."));
-    }
-
-    for ch in input.chars() {
-        // Control characters are not allowed (including newline).
-        // Space (U+0020) is the first one that is allowed.
-        if ch < '\u{20}' {
-            return Err(format!(
-                "{label} may not contain control characters (including newlines)."
-            ));
-        }
-
-        // Allow General Punctuation (U+2000 through U+206F).
-        if ch >= '\u{2000}' && ch < '\u{2070}' {
-            continue;
+/// Only the server can compute it, because it depends on a secret. When a voter
+/// disputes their vote, the admin can check that the fingerprint they show is
+/// genuine, and whether it matches the votes that are in the database now.
+fn get_vote_fingerprint(secret: &str, voter_email: &str, votes: &[db::VoterVote]) -> String {
+    let mut votes: Vec<(i64, i64, i64)> = votes
+        .iter()
+        .map(|v| (v.category_id, v.team_id, v.points))
+        .collect();
+    votes.sort();
+    let mut message = format!("votes:{voter_email}\n");
+    for (category_id, team_id, points) in votes {
+        // Votes without categories keep the format from before categories.
+        if category_id != 0 {
+            message.push_str(&format!("{category_id}/"));
         }
+        message.push_str(&format!("{team_id}:{points}\n"));
+    }
+    let (k0, k1) = derive_key(secret);
+    let fingerprint = hash::siphash24(k0, k1, message.as_bytes());
+    // Group the digits so it is easier to read out.
+    let hex = format!("{fingerprint:016x}");
+    format!(
+        "{}-{}-{}-{}",
+        &hex[..4],
+        &hex[4..8],
+        &hex[8..12],
+        &hex[12..]
+    )
+}
 
-        // Allow Basic Latin, the supplement, extended Latin, modifiers,
-        // diacritics, then a few other languages like Greek and Cyrillic, but
-        // stop after Arabic.
-        if ch >= '\u{0780}' {
-            return Err(format!(
-                "{label} contains an invalid character: ‘{ch}’ (U+{:04X}) is not allowed.",
-                ch as u32
-            ));
+fn view_my_vote(
+    config: &Config,
+    coins_to_spend: u32,
+    votes: &[db::VoterVote],
+    fingerprint: Option<&str>,
+) -> Markup {
+    // Without categories, all votes are in category 0, with the global budget.
+    let categories: Vec<(Option<&str>, u32)> = if config.app.categories.is_empty() {
+        vec![(None, coins_to_spend)]
+    } else {
+        config
+            .app
+            .categories
+            .iter()
+            .map(|c| (Some(c.name.as_str()), c.coins_to_spend))
+            .collect()
+    };
+    html! {
+        (view_html_head(config, "Your Vote — Hack-o-matic"))
+        body {
+            h1 { "Your Vote" }
+            @if votes.is_empty() {
+                p { "You have not voted yet." }
+            } @else {
+                p { "This is the vote that we have on record for you:" }
+            }
+            @for (category_id, (name, coins_to_spend)) in categories.iter().enumerate() {
+                @let category_votes = votes
+                    .iter()
+                    .filter(|v| v.category_id == category_id as i64)
+                    .collect::<Vec<_>>();
+                // The stored votes were validated when they were cast, they can't overflow.
+                @let coins_spent = category_votes
+                    .iter()
+                    .map(|v| get_vote_cost(config, v.points).unwrap_or(0))
+                    .sum::<i64>();
+                @if let Some(name) = name {
+                    h2 { (name) }
+                }
+                @if !category_votes.is_empty() {
+                    ul {
+                        @for vote in &category_votes {
+                            li {
+                                a href=(get_team_url(config, vote.team_id)) {
+                                    (vote.team_name)
+                                }
+                                ": " strong { (vote.points) }
+                                " (" (view_coins(get_vote_cost(config, vote.points).unwrap_or(0))) ")"
+                            }
+                        }
+                    }
+                }
+                p {
+                    "You spent " (coins_spent) " of your " (coins_to_spend) " coins, "
+                    "so you have " ((*coins_to_spend as i64 - coins_spent)) " coins left."
+                }
+            }
+            @if let Some(fingerprint) = fingerprint {
+                p {
+                    "Vote fingerprint: " code { (fingerprint) } ". "
+                    "If you ever disagree with the vote that we have on record, "
+                    "this lets the organizers confirm what your vote was at this time."
+                }
+            }
+            p {
+                a href=(format!("{}#your-vote", config.server.prefix)) { "« Back to voting" }
+            }
         }
     }
-
-    Ok(())
 }
 
-pub fn handle_create_team(
+pub fn handle_my_vote(
     config: &Config,
     tx: &mut db::Transaction,
     user: &User,
-    body: &str,
 ) -> db::Result<Response> {
     let phase = crate::load_phase(tx)?;
-    if !matches!(phase, Phase::Registration) {
-        return Ok(bad_request("Registration is closed."));
+    if !matches!(phase, Phase::Evaluation) {
+        return Ok(bad_request("Voting is closed, there is no vote to review."));
     }
-
-    let mut team_name = String::new();
-    let mut description = String::new();
-
-    for (key, value) in form_urlencoded::parse(body.as_bytes()) {
-        match key.as_ref() {
-            "team-name" => team_name = value.trim().to_string(),
-            "description" => description = value.trim().to_string(),
-            _ => return Ok(bad_request("Unexpected form field.")),
+    let votes = db::iter_voter_votes(tx, &user.email)?.collect::<Result<Vec<_>, _>>()?;
+    let fingerprint = match &config.app.vote_fingerprint_secret {
+        Some(secret) if !votes.is_empty() => {
+            Some(get_vote_fingerprint(secret, &user.email, &votes))
         }
-    }
+        _ => None,
+    };
+    let coins_to_spend = get_coins_to_spend(config, tx, phase)?;
+    Ok(respond_html(view_my_vote(
+        config,
+        coins_to_spend,
+        &votes,
+        fingerprint.as_deref(),
+    )))
+}
 
-    if let Err(msg) = validate_string("The team name", 65, &team_name) {
-        return Ok(bad_request(msg));
+/// Return the stored vote of the user as a JSON object from team id to points.
+///
+/// This is the vote as the server sees it, to debug votes that "didn't save".
+/// With categories, this is the vote for the first category.
+pub fn handle_my_vote_json(tx: &mut db::Transaction, user: &User) -> db::Result<Response> {
+    let phase = crate::load_phase(tx)?;
+    if !matches!(phase, Phase::Evaluation) {
+        return Ok(forbidden("Voting is closed, there is no vote to review."));
     }
-    if let Err(msg) = validate_string("The description", 120, &description) {
-        return Ok(bad_request(msg));
+    let mut entries = Vec::new();
+    for vote in db::iter_voter_votes(tx, &user.email)? {
+        let vote = vote?;
+        if vote.category_id == 0 {
+            entries.push(format!("\"{}\":{}", vote.team_id, vote.points));
+        }
     }
+    Ok(respond_json(format!("{{{}}}\n", entries.join(","))))
+}
 
-    let n_teams_by_user = db::count_teams_by_creator(tx, &user.email)?;
-    if n_teams_by_user >= config.app.max_teams_per_creator as i64 {
-        return Ok(bad_request(format!(
-            "You already created {n_teams_by_user} teams, chill out!"
-        )));
-    }
+/// Return the current phase as plain text, for screens that poll it.
+///
+/// Polling the index for this would work too, but that renders every team.
+pub fn handle_phase(tx: &mut db::Transaction) -> db::Result<Response> {
+    let phase = crate::load_phase(tx)?;
+    Ok(
+        Response::from_string(format!("{}\n", phase.to_str())).with_header(
+            Header::from_bytes(&b"Content-Type"[..], &b"text/plain; charset=utf-8"[..]).unwrap(),
+        ),
+    )
+}
 
-    let team_id = match db::add_team(tx, &team_name, &user.email, &description) {
-        Ok(id) => id,
-        Err(err)
-            if err
-                .message
-                .as_deref()
-                .unwrap_or("")
-                .contains("UNIQUE constraint") =>
-        {
-            return Ok(bad_request("A team with that name already exists."))
-        }
-        Err(err) => return Err(err),
-    };
+/// Return the current phase as a JSON object, see `handle_phase`.
+pub fn handle_phase_json(tx: &mut db::Transaction) -> db::Result<Response> {
+    let phase = crate::load_phase(tx)?;
+    Ok(respond_json(format!(
+        "{{\"phase\":{}}}\n",
+        json::string(phase.to_str())
+    )))
+}
 
-    // The user who creates the team is initially a member of it.
-    db::add_team_member(tx, team_id, &user.email)?;
+/// Return all teams with their members, e.g. for a kiosk to browse the teams.
+///
+/// This has no votes in it, so unlike the results, it is available in every phase.
+pub fn handle_teams_json(config: &Config, tx: &mut db::Transaction) -> db::Result<Response> {
+    let mut teams = Vec::new();
+    for team in db::iter_teams(tx)?.collect::<Result<Vec<_>, _>>()? {
+        let (members, _captain) = load_team_members(tx, team.id)?;
+        let members: Vec<String> = members
+            .iter()
+            .map(|email| json::string(view_email(config, email)))
+            .collect();
+        let project_url = match &team.project_url {
+            Some(url) => json::string(url),
+            None => "null".to_string(),
+        };
+        teams.push(format!(
+            "{{\"id\":{},\"name\":{},\"description\":{},\"project_url\":{},\"members\":[{}]}}",
+            team.id,
+            json::string(&team.name),
+            json::string(&team.description),
+            project_url,
+            members.join(","),
+        ));
+    }
+    Ok(respond_json(format!(
+        "{{\"teams\":[{}]}}\n",
+        teams.join(",")
+    )))
+}
 
-    let new_url = format!("{}#team-{}", config.server.prefix, team_id);
-    Ok(redirect_see_other(new_url.as_bytes()))
+fn view_verify_vote(config: &Config, email: &str, claimed: &str, actual: Option<&str>) -> Markup {
+    html! {
+        (view_html_head(config, "Verify Vote — Hack-o-matic"))
+        body {
+            h1 { "Verify Vote" }
+            form action=(format!("{}/verify-vote", config.server.prefix)) method="get" {
+                label { "Voter email: "; input name="email" value=(email); }
+                label { "Fingerprint: "; input name="fingerprint" value=(claimed); }
+                button type="submit" { "Verify" }
+            }
+            @if let Some(actual) = actual {
+                @if actual == claimed.trim() {
+                    p { strong { "Match." } " The fingerprint matches the votes on record." }
+                } @else {
+                    p {
+                        strong { "No match." } " The votes on record have fingerprint "
+                        code { (actual) } ". Either the fingerprint is not genuine, "
+                        "or the vote changed after it was taken."
+                    }
+                }
+            }
+            p {
+                a href=(config.server.prefix) { "« Back" }
+            }
+        }
+    }
 }
 
-fn get_body_team_id(body: &str) -> Result<i64, Response> {
-    let mut team_id = 0_i64;
+/// Let the admin check a vote fingerprint against the votes in the database.
+pub fn handle_verify_vote(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    query: &str,
+) -> db::Result<Response> {
+    if !user.is_admin {
+        return Ok(forbidden("Only the admin can verify votes."));
+    }
+    let secret = match &config.app.vote_fingerprint_secret {
+        Some(secret) => secret,
+        None => return Ok(not_found("Vote fingerprints are not enabled.")),
+    };
 
-    for (key, value) in form_urlencoded::parse(body.as_bytes()) {
+    let mut email = String::new();
+    let mut claimed = String::new();
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
         match key.as_ref() {
-            "team-id" => match i64::from_str(value.as_ref()) {
-                Ok(id) => team_id = id,
-                Err(..) => return Err(bad_request("Invalid team id.")),
-            },
-            _ => return Err(bad_request("Unexpected form field.")),
+            "email" => email = value.trim().to_string(),
+            "fingerprint" => claimed = value.trim().to_string(),
+            _ => return Ok(bad_request("Unexpected query parameter.")),
         }
     }
 
-    if team_id == 0 {
-        Err(bad_request("Need a team id."))
+    let actual = if email.is_empty() {
+        None
     } else {
-        Ok(team_id)
+        let votes = db::iter_voter_votes(tx, &email)?.collect::<Result<Vec<_>, _>>()?;
+        Some(get_vote_fingerprint(secret, &email, &votes))
+    };
+    Ok(respond_html(view_verify_vote(
+        config,
+        &email,
+        &claimed,
+        actual.as_deref(),
+    )))
+}
+
+fn view_my_rank(config: &Config, entries: &[&TeamEntry]) -> Markup {
+    html! {
+        (view_html_head(config, "Your Rank — Hack-o-matic"))
+        body {
+            h1 { "Your Rank" }
+            @if entries.is_empty() {
+                p { "You are not a member of any team." }
+            } @else {
+                p {
+                    "The ceremony is still in progress, "
+                    "please keep this to yourself until your team is revealed."
+                }
+                ul {
+                    @for entry in entries {
+                        li {
+                            a href=(get_team_url(config, entry.team.id)) {
+                                (entry.team.name)
+                            }
+                            ": "
+                            @if entry.rank > 0 {
+                                strong { "№ " (entry.rank) }
+                            } @else {
+                                "did not present, so not ranked"
+                            }
+                        }
+                    }
+                }
+            }
+            p {
+                a href=(config.server.prefix) { "« Back to the ceremony" }
+            }
+        }
+    }
+}
+
+/// Load all teams with their total points, sorted and ranked, without votes.
+fn load_ranked_team_entries(
+    config: &Config,
+    tx: &mut db::Transaction,
+    category_id: i64,
+) -> db::Result<Vec<TeamEntry>> {
+    let phase = crate::load_phase(tx)?;
+    let results_cache = load_results_cache(tx, phase, category_id)?;
+    let teams = db::iter_teams(tx)?.collect::<Result<Vec<_>, _>>()?;
+    let presented_teams = db::iter_presented_teams(tx)?.collect::<Result<Vec<_>, _>>()?;
+    let mut team_entries = Vec::with_capacity(teams.len());
+    for team in teams {
+        let (members, captain) = load_team_members(tx, team.id)?;
+        let votes =
+            db::iter_team_votes(tx, team.id, category_id)?.collect::<Result<Vec<_>, _>>()?;
+        let total_points = match results_cache.get(&team.id) {
+            Some(cached) => cached.total_points,
+            None => compute_team_total_with_judges(config, tx, team.id, category_id, &votes)?,
+        };
+        let entry = TeamEntry {
+            presented: presented_teams.contains(&team.id),
+            favorite: false,
+            feedback: Vec::new(),
+            team,
+            data: TeamData::None,
+            total_points,
+            last_vote_id: votes.iter().map(|v| v.id).max().unwrap_or(0),
+            n_voters: votes.len(),
+            member_emails: members,
+            captain_email: captain,
+            rank: 0,
+        };
+        team_entries.push(entry);
+    }
+    if results_cache.is_empty() {
+        rank_team_entries(config, &mut team_entries);
+    } else {
+        apply_results_cache(&results_cache, &mut team_entries);
+    }
+    Ok(team_entries)
+}
+
+/// Show the rank of the teams that the user is a member of.
+///
+/// This reveals the ranks before the ceremony gets to them, so it is only
+/// available when enabled with `allow_self_rank_peek`.
+pub fn handle_my_rank(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+) -> db::Result<Response> {
+    let phase = crate::load_phase(tx)?;
+    if !matches!(phase, Phase::Revelation | Phase::Celebration) {
+        return Ok(bad_request("The votes have not been counted yet."));
+    }
+    if !user.can_see_outcome(phase) && !config.app.allow_self_rank_peek {
+        return Ok(forbidden(
+            "The ranks are revealed during the ceremony, please wait for it.",
+        ));
+    }
+
+    // To know the rank of the user's teams we need the totals of all teams,
+    // but we only show the user's own teams, so we don't need their votes.
+    let team_entries = load_ranked_team_entries(config, tx, 0)?;
+
+    let own_entries: Vec<&TeamEntry> = team_entries
+        .iter()
+        .filter(|entry| entry.member_emails.contains(&user.email))
+        .collect();
+    Ok(respond_html(view_my_rank(config, &own_entries)))
+}
+
+fn get_unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("The clock should be after 1970.")
+        .as_secs()
+}
+
+/// Convert a TOML datetime to seconds since the epoch.
+///
+/// Returns `None` unless the datetime has a date, a time, and an offset, the
+/// local variants don't identify a moment. Fractional seconds are dropped.
+pub fn get_unix_time_from_datetime(datetime: &toml::value::Datetime) -> Option<u64> {
+    let (date, time, offset) = match (datetime.date, datetime.time, datetime.offset) {
+        (Some(date), Some(time), Some(offset)) => (date, time, offset),
+        _ => return None,
+    };
+
+    // Count the days since 1970-01-01 in the proleptic Gregorian calendar.
+    // This follows Howard Hinnant's `days_from_civil`, with years that start
+    // in March, so the leap day is at the end of the year.
+    let month = date.month as i64;
+    let year = date.year as i64 - if month <= 2 { 1 } else { 0 };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + date.day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let offset_minutes = match offset {
+        toml::value::Offset::Z => 0,
+        toml::value::Offset::Custom { minutes } => minutes as i64,
+    };
+    let seconds =
+        days * 86_400 + time.hour as i64 * 3600 + time.minute as i64 * 60 + time.second as i64
+            - offset_minutes * 60;
+    u64::try_from(seconds).ok()
+}
+
+fn is_past_evaluation_deadline(config: &Config) -> bool {
+    match config
+        .app
+        .evaluation_deadline
+        .as_ref()
+        .and_then(get_unix_time_from_datetime)
+    {
+        Some(deadline) => get_unix_time() >= deadline,
+        None => false,
+    }
+}
+
+/// Derive a SipHash key from a configured secret, which can have any length.
+fn derive_key(secret: &str) -> (u64, u64) {
+    let k0 = hash::siphash24(0, 0, secret.as_bytes());
+    let k1 = hash::siphash24(0, 1, secret.as_bytes());
+    (k0, k1)
+}
+
+/// Compute the signature of a shared results token that expires at the given time.
+fn get_results_token_tag(secret: &str, expires_at: u64) -> u64 {
+    let (k0, k1) = derive_key(secret);
+    hash::siphash24(k0, k1, format!("results:{expires_at}").as_bytes())
+}
+
+/// Create a token for the shared results page that is valid until `expires_at`.
+fn new_results_token(secret: &str, expires_at: u64) -> String {
+    let tag = get_results_token_tag(secret, expires_at);
+    format!("{expires_at}.{tag:016x}")
+}
+
+/// Check that the token was signed with our secret and has not expired yet.
+fn verify_results_token(secret: &str, token: &str, now: u64) -> Result<(), &'static str> {
+    let invalid = "This link is not valid, ask the organizers for a new one.";
+    let (expires_at, tag) = match token.split_once('.') {
+        Some((expires_at, tag)) => (expires_at, tag),
+        None => return Err(invalid),
+    };
+    let (expires_at, tag) = match (u64::from_str(expires_at), u64::from_str_radix(tag, 16)) {
+        (Ok(expires_at), Ok(tag)) => (expires_at, tag),
+        _ => return Err(invalid),
+    };
+    // Compare without early exit, so the time it takes does not reveal how
+    // much of the tag is right.
+    if (get_results_token_tag(secret, expires_at) ^ tag) != 0 {
+        return Err(invalid);
+    }
+    if now >= expires_at {
+        return Err("This link has expired, ask the organizers for a new one.");
+    }
+    Ok(())
+}
+
+fn form_finalize(config: &Config, finalized_at: Option<&str>) -> Markup {
+    html! {
+        h2 { "Final Results" }
+        @match finalized_at {
+            Some(finalized_at) => {
+                p {
+                    "The results are final since " (finalized_at) ". "
+                    "Changes to the votes or teams no longer affect the standings, "
+                    "until you recount."
+                }
+                form action={ (config.server.prefix) "/unfinalize" } method="post" {
+                    button type="submit" { "Recount" }
+                }
+            }
+            None => {
+                p {
+                    "The standings are recounted on every page load. "
+                    "Once you finalize them, they no longer change."
+                }
+                form action={ (config.server.prefix) "/finalize" } method="post" {
+                    button type="submit" { "Finalize Results" }
+                }
+            }
+        }
+    }
+}
+
+fn view_share_results(config: &Config, secret: &str) -> Markup {
+    let valid_days = config.app.results_token_valid_days.unwrap_or(7);
+    let expires_at = get_unix_time() + valid_days as u64 * 24 * 3600;
+    let url = format!(
+        "{}/results?token={}",
+        config.server.prefix,
+        new_results_token(secret, expires_at)
+    );
+    html! {
+        h2 { "Share Results" }
+        p {
+            "Anybody with "
+            a href=(url) { "this link" }
+            " can see the final standings for the next " (valid_days) " days, "
+            "without logging in. It does not show who voted for what."
+        }
+    }
+}
+
+fn view_shared_results(config: &Config, entries: &[TeamEntry]) -> Markup {
+    html! {
+        (view_html_head(config, "Results — Hack-o-matic"))
+        body {
+            h1 { "Results" }
+            ol {
+                @for entry in entries {
+                    li {
+                        @if entry.rank > 0 {
+                            strong { "№ " (entry.rank) }
+                        } @else {
+                            strong { "Unranked" }
+                        }
+                        " " (entry.team.name) " — " (entry.total_points)
+                        @if entry.total_points == 1 { " point" } @else { " points" }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Show the final standings to anybody with a valid token, without login.
+///
+/// The token is in the query string, so this is the only page that can be
+/// viewed without the `X-Email` header.
+pub fn handle_shared_results(
+    config: &Config,
+    tx: &mut db::Transaction,
+    query: &str,
+) -> db::Result<Response> {
+    let secret = match &config.app.results_token_secret {
+        Some(secret) => secret,
+        None => return Ok(not_found("Not found.")),
+    };
+    let token = form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _value)| key == "token")
+        .map(|(_key, value)| value.into_owned())
+        .unwrap_or_default();
+    if let Err(msg) = verify_results_token(secret, &token, get_unix_time()) {
+        return Ok(forbidden(msg));
+    }
+
+    let phase = crate::load_phase(tx)?;
+    if !matches!(phase, Phase::Celebration) {
+        return Ok(conflict("The results are not final yet, check back later."));
+    }
+
+    let team_entries = load_ranked_team_entries(config, tx, 0)?;
+    Ok(respond_html(view_shared_results(config, &team_entries)))
+}
+
+/// Show the standings to anybody, for a screen that is not logged in.
+///
+/// Like the shared results, this lists only names and totals, never who voted
+/// for whom. Unlike those, it also works during the revelation, so the screen
+/// can follow along with the ceremony.
+pub fn handle_public_results(config: &Config, tx: &mut db::Transaction) -> db::Result<Response> {
+    if !config.app.public_results {
+        return Ok(not_found("Not found."));
+    }
+
+    let phase = crate::load_phase(tx)?;
+    if !matches!(phase, Phase::Revelation | Phase::Celebration) {
+        return Ok(not_found("Not found."));
+    }
+
+    let team_entries = load_ranked_team_entries(config, tx, 0)?;
+    Ok(respond_html(view_shared_results(config, &team_entries)))
+}
+
+/// Return the outcome as JSON, for e.g. a dashboard on a projector.
+///
+/// The fields are always in the same order, and the teams are ordered from
+/// first to last. Teams that are not eligible for a rank have rank `null`.
+pub fn handle_results_json(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+) -> db::Result<Response> {
+    let phase = crate::load_phase(tx)?;
+    if !user.can_see_outcome(phase) {
+        return Ok(forbidden("The outcome is not available yet."));
+    }
+
+    let team_entries = load_ranked_team_entries(config, tx, 0)?;
+    let mut teams = Vec::with_capacity(team_entries.len());
+    for entry in &team_entries {
+        let rank = match entry.rank {
+            0 => "null".to_string(),
+            n => n.to_string(),
+        };
+        teams.push(format!(
+            "{{\"id\":{},\"name\":{},\"total_points\":{},\"rank\":{},\"member_count\":{}}}",
+            entry.team.id,
+            json::string(&entry.team.name),
+            entry.total_points,
+            rank,
+            entry.member_emails.len(),
+        ));
+    }
+    let body = format!(
+        "{{\"phase\":{},\"teams\":[{}]}}\n",
+        json::string(phase.to_str()),
+        teams.join(",")
+    );
+    Ok(respond_json(body))
+}
+
+/// Return all votes, with the voters replaced by anonymous ids, for research.
+///
+/// The id is a keyed hash of the email, with a salt that is generated once per
+/// database, so the same voter gets the same id within an event, but without
+/// the salt, the ids can't be linked back to voters.
+pub fn handle_dataset_json(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+) -> db::Result<Response> {
+    if !user.is_admin {
+        return Ok(forbidden("Only the admin can download the dataset."));
+    }
+    let phase = crate::load_phase(tx)?;
+    if !matches!(phase, Phase::Revelation | Phase::Celebration) {
+        return Ok(forbidden(
+            "The dataset is available once the results are revealed.",
+        ));
+    }
+    let salt = db::get_setting(tx, "dataset_salt")?
+        .expect("The salt is set at startup, see seed_settings.");
+    let (k0, k1) = derive_key(&salt);
+
+    let mut teams = Vec::new();
+    for team in db::iter_teams(tx)?.collect::<Result<Vec<_>, _>>()? {
+        let (members, _captain) = load_team_members(tx, team.id)?;
+        teams.push(format!(
+            "{{\"id\":{},\"name\":{},\"member_count\":{}}}",
+            team.id,
+            json::string(&team.name),
+            members.len(),
+        ));
+    }
+    let mut votes = Vec::new();
+    for vote in db::iter_all_votes(tx)? {
+        let vote = vote?;
+        let voter = hash::siphash24(k0, k1, vote.voter_email.as_bytes());
+        votes.push(format!(
+            "{{\"voter\":\"{:016x}\",\"team_id\":{},\"category_id\":{},\"points\":{}}}",
+            voter, vote.team_id, vote.category_id, vote.points,
+        ));
+    }
+    let categories: Vec<String> = config
+        .app
+        .categories
+        .iter()
+        .map(|c| json::string(&c.name))
+        .collect();
+    Ok(respond_json(format!(
+        "{{\"categories\":[{}],\"teams\":[{}],\"votes\":[{}]}}\n",
+        categories.join(","),
+        teams.join(","),
+        votes.join(","),
+    )))
+}
+
+/// Return the number of votes per minute, for the admin to chart the turnout.
+pub fn handle_vote_timeline_json(tx: &mut db::Transaction, user: &User) -> db::Result<Response> {
+    if !user.is_admin {
+        return Ok(forbidden("Only the admin can see the vote timeline."));
+    }
+
+    let mut buckets = Vec::new();
+    for bucket in db::iter_vote_timeline(tx)? {
+        let bucket = bucket?;
+        buckets.push(format!(
+            "{{\"minute\":{},\"votes\":{},\"voters\":{}}}",
+            json::string(&bucket.minute),
+            bucket.n_votes,
+            bucket.n_voters,
+        ));
+    }
+    Ok(respond_json(format!(
+        "{{\"buckets\":[{}]}}\n",
+        buckets.join(",")
+    )))
+}
+
+/// Quote a CSV field if needed, per RFC 4180.
+fn csv_field(value: &str) -> Cow<str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+/// Export the standings as CSV, for the admin to paste into a spreadsheet.
+///
+/// This works in any phase, before the vote all teams have zero points.
+pub fn handle_results_csv(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+) -> db::Result<Response> {
+    if !user.is_admin {
+        return Ok(forbidden("Only the admin can export the results."));
+    }
+
+    let team_entries = load_ranked_team_entries(config, tx, 0)?;
+    let mut body = "rank,team_name,total_points,num_voters,creator_email\r\n".to_string();
+    for entry in &team_entries {
+        let num_voters = db::iter_team_votes(tx, entry.team.id, 0)?.count();
+        // Teams that are not eligible for a rank get an empty rank.
+        let rank = match entry.rank {
+            0 => String::new(),
+            n => n.to_string(),
+        };
+        body.push_str(&format!(
+            "{},{},{},{},{}\r\n",
+            rank,
+            csv_field(&entry.team.name),
+            entry.total_points,
+            num_voters,
+            csv_field(&entry.team.creator_email),
+        ));
+    }
+
+    let response = Response::from_string(body)
+        .with_header(
+            Header::from_bytes(&b"Content-Type"[..], &b"text/csv; charset=utf-8"[..]).unwrap(),
+        )
+        .with_header(
+            Header::from_bytes(
+                &b"Content-Disposition"[..],
+                &b"attachment; filename=\"results.csv\""[..],
+            )
+            .unwrap(),
+        );
+    Ok(response)
+}
+
+pub fn handle_backup(
+    config: &Config,
+    raw_connection: &sqlite::Connection,
+    user: &User,
+) -> db::Result<Response> {
+    if !user.is_admin {
+        return Ok(forbidden("Only the admin can download a backup."));
+    }
+
+    // The scratch file goes next to the database, where we know we can write.
+    // The suffix keeps concurrent backups apart.
+    let scratch_path = format!("{}.backup-{}", config.database.path, new_idempotency_key());
+    let data = db_ext::backup_to_vec(raw_connection, scratch_path.as_ref())?;
+    println!("Created a backup of {} bytes.", data.len());
+
+    let response = Response::from_data(data)
+        .with_header(
+            Header::from_bytes(&b"Content-Type"[..], &b"application/vnd.sqlite3"[..]).unwrap(),
+        )
+        .with_header(
+            Header::from_bytes(
+                &b"Content-Disposition"[..],
+                &b"attachment; filename=\"backup.sqlite\""[..],
+            )
+            .unwrap(),
+        );
+    Ok(response)
+}
+
+/// Parse a form body, skipping the fields that the config says to ignore.
+///
+/// Handlers reject fields they don't know, to catch mistakes early. But some
+/// fields are harmless, e.g. a browser extension or a proxy may add its own,
+/// so those can be listed in `ignored_form_fields`.
+fn parse_form<'a>(
+    config: &'a Config,
+    body: &'a str,
+) -> impl Iterator<Item = (Cow<'a, str>, Cow<'a, str>)> {
+    form_urlencoded::parse(body.as_bytes())
+        .filter(|(key, _value)| !config.app.ignored_form_fields.iter().any(|f| f == key))
+}
+
+/// Whether the character is zero-width, or a bidirectional formatting character.
+fn is_invisible_or_bidi_control(ch: char) -> bool {
+    matches!(
+        ch,
+        // Soft hyphen, and the Arabic letter mark.
+        '\u{ad}' | '\u{61c}'
+        // Zero width space, non-joiner, joiner, and the LTR and RTL marks.
+        | '\u{200b}'..='\u{200f}'
+        // Directional embeddings and overrides.
+        | '\u{202a}'..='\u{202e}'
+        // Word joiner, invisible operators, directional isolates, and the
+        // deprecated formatting characters.
+        | '\u{2060}'..='\u{206f}'
+    )
+}
+
+/// Check that the email address from the authenticating proxy looks like one.
+///
+/// We don't try to implement RFC 5322, we only want to catch a misconfigured
+/// proxy that sends garbage such as "(null)", before it ends up in the
+/// database. Returns a description of the violation on error.
+pub fn validate_email(email: &str) -> Result<(), String> {
+    // The limits of RFC 5321 for the full address and the local part.
+    if email.len() > 254 {
+        return Err("The email address is too long.".to_string());
+    }
+    let (local, domain) = match email.split_once('@') {
+        Some((local, domain)) if !domain.contains('@') => (local, domain),
+        _ => return Err("The email address must contain exactly one '@'.".to_string()),
+    };
+    if local.is_empty() || domain.is_empty() {
+        return Err("The email address must have a part before and after the '@'.".to_string());
+    }
+    if local.len() > 64 {
+        return Err("The part of the email address before the '@' is too long.".to_string());
+    }
+    if email
+        .chars()
+        .any(|ch| ch.is_whitespace() || ch.is_control())
+    {
+        return Err("The email address must not contain whitespace.".to_string());
+    }
+    Ok(())
+}
+
+/// The code points that strings may contain when `allowed_unicode_blocks` is absent.
+///
+/// This is Basic Latin, the supplement, extended Latin, modifiers, diacritics,
+/// then a few other languages like Greek and Cyrillic, up to and including
+/// Arabic, and General Punctuation (U+2000 through U+206F).
+const DEFAULT_UNICODE_BLOCKS: &[(u32, u32)] = &[(0x0020, 0x077f), (0x2000, 0x206f)];
+
+/// Validate user inputs against length limits and Unicode subset.
+///
+/// Users should be able to input text, but allowing any Unicode code point
+/// creates a can of worms where you can use distracting emoji, or reverse the
+/// text direction for all following content, use the mathematical symbols to do
+/// "markup", etc. So ban most of Unicode, but allow more than just ASCII
+/// because Tomás and Mikołaj are valid non-ASCII names. This is very crude but
+/// it'll do.
+///
+/// Returns a description of the violaton on error.
+fn validate_string(
+    config: &Config,
+    label: &'static str,
+    max_len: usize,
+    input: &str,
+) -> Result<(), String> {
+    if input.is_empty() {
+        return Err(format!("{label} must not be empty."));
+    }
+
+    if input.len() > max_len {
+        return Err(format!("{label} may not be longer than {max_len} bytes."));
+    }
+
+    for ch in input.chars() {
+        // Control characters are not allowed (including newline).
+        // Space (U+0020) is the first one that is allowed. Delete and the C1
+        // controls that follow it are control characters too.
+        if ch < '\u{20}' || ('\u{7f}'..'\u{a0}').contains(&ch) {
+            return Err(format!(
+                "{label} may not contain control characters (including newlines)."
+            ));
+        }
+
+        // Invisible characters enable names that look identical to an existing
+        // one, and the bidi controls can reverse the direction of the text that
+        // follows. Some of these are in the ranges we allow below, so reject
+        // them explicitly. We can't print them, so only print the code point.
+        if is_invisible_or_bidi_control(ch) {
+            return Err(format!(
+                "{label} contains an invisible or text direction character \
+                (U+{:04X}), which is not allowed.",
+                ch as u32
+            ));
+        }
+
+        let allowed_blocks = config
+            .app
+            .allowed_unicode_blocks
+            .as_deref()
+            .unwrap_or(DEFAULT_UNICODE_BLOCKS);
+        let is_allowed = allowed_blocks
+            .iter()
+            .any(|(first, last)| (*first..=*last).contains(&(ch as u32)));
+        if !is_allowed {
+            return Err(format!(
+                "{label} contains an invalid character: ‘{ch}’ (U+{:04X}) is not allowed.",
+                ch as u32
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Lowercase the string and strip accents, to match it against `blocked_words`.
+///
+/// This is not full Unicode normalization, we don't have the tables for that.
+/// We drop combining marks, and map precomposed Latin letters to their base.
+fn fold_for_blocklist(input: &str) -> String {
+    input
+        .chars()
+        .flat_map(char::to_lowercase)
+        .filter(|ch| !('\u{300}'..='\u{36f}').contains(ch))
+        .map(|ch| match ch {
+            'à'..='å' | 'ā' | 'ă' | 'ą' => 'a',
+            'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => 'c',
+            'ď' | 'đ' => 'd',
+            'è'..='ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => 'e',
+            'ĝ' | 'ğ' | 'ġ' | 'ģ' => 'g',
+            'ĥ' | 'ħ' => 'h',
+            'ì'..='ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => 'i',
+            'ĵ' => 'j',
+            'ķ' => 'k',
+            'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => 'l',
+            'ñ' | 'ń' | 'ņ' | 'ň' => 'n',
+            'ò'..='ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => 'o',
+            'ŕ' | 'ŗ' | 'ř' => 'r',
+            'ś' | 'ŝ' | 'ş' | 'š' => 's',
+            'ţ' | 'ť' | 'ŧ' => 't',
+            'ù'..='ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => 'u',
+            'ŵ' => 'w',
+            'ý' | 'ÿ' | 'ŷ' => 'y',
+            'ź' | 'ż' | 'ž' => 'z',
+            _ => ch,
+        })
+        .collect()
+}
+
+/// Return the first of the `blocked_words` that occurs in any of the inputs.
+fn find_blocked_word<'a>(config: &'a Config, inputs: &[&str]) -> Option<&'a str> {
+    let inputs: Vec<String> = inputs.iter().map(|s| fold_for_blocklist(s)).collect();
+    config
+        .app
+        .blocked_words
+        .iter()
+        .map(|w| w.as_str())
+        .find(|word| {
+            let word = fold_for_blocklist(word);
+            !word.is_empty() && inputs.iter().any(|input| input.contains(&word))
+        })
+}
+
+/// Reject the team name and description if they contain a blocked word.
+///
+/// We don't say which word, the message should not help to find a way around
+/// the filter. The admin can find it in the log.
+fn check_blocked_words(
+    config: &Config,
+    user: &User,
+    team_name: &str,
+    description: &str,
+) -> Result<(), &'static str> {
+    if let Some(word) = find_blocked_word(config, &[team_name, description]) {
+        println!(
+            "Rejected team name {team_name:?} from {}, it contains blocked word {word:?}.",
+            user.email
+        );
+        return Err("The team name or description contains a word that is not allowed.");
+    }
+    Ok(())
+}
+
+/// Validate a project url, we only accept plain http and https links.
+///
+/// In particular, we don't want `javascript:` urls, which would run when
+/// somebody clicks the link.
+fn validate_project_url(config: &Config, url: &str) -> Result<(), String> {
+    validate_string(config, "The project url", 200, url)?;
+    let rest = match url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+    {
+        Some(rest) => rest,
+        None => return Err("The project url must start with https:// or http://.".to_string()),
+    };
+    if rest.is_empty() || url.contains(char::is_whitespace) {
+        return Err("The project url is not a valid url.".to_string());
+    }
+    Ok(())
+}
+
+pub fn handle_create_team(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    body: &str,
+) -> db::Result<Response> {
+    let phase = crate::load_phase(tx)?;
+    if !matches!(phase, Phase::Registration) {
+        return Ok(bad_request("Registration is closed."));
+    }
+
+    if is_registration_locked(tx)? {
+        return Ok(conflict(
+            "Registration is locked, teams cannot be changed right now.",
+        ));
+    }
+
+    let mut form = CreateTeamForm {
+        team_name: String::new(),
+        description: String::new(),
+        project_url: String::new(),
+        idempotency_key: String::new(),
+        error: None,
+    };
+
+    for (key, value) in parse_form(config, body) {
+        match key.as_ref() {
+            "team-name" => form.team_name = value.trim().to_string(),
+            "description" => form.description = value.trim().to_string(),
+            "project-url" => form.project_url = value.trim().to_string(),
+            "idempotency-key" => form.idempotency_key = value.to_string(),
+            _ => return Ok(bad_request("Unexpected form field.")),
+        }
+    }
+
+    // If we already handled this exact submission, then send the user to the
+    // team it created, instead of failing on the name that is now taken.
+    if form.idempotency_key.len() > 64 {
+        return Ok(bad_request("Invalid idempotency key."));
+    }
+    if !form.idempotency_key.is_empty() {
+        if let Some(team_id) = db::get_idempotent_team(tx, &user.email, &form.idempotency_key)? {
+            let new_url = get_team_url(config, team_id);
+            return Ok(redirect_see_other(new_url.as_bytes()));
+        }
+    }
+
+    use CreateTeamField::{Description, ProjectUrl, TeamName};
+    if let Err(msg) = validate_string(config, "The team name", 65, &form.team_name) {
+        return reject_create_team(config, tx, user, form, Some(TeamName), msg);
+    }
+    if let Err(msg) = validate_string(config, "The description", 120, &form.description) {
+        return reject_create_team(config, tx, user, form, Some(Description), msg);
+    }
+    if let Err(msg) = check_blocked_words(config, user, &form.team_name, &form.description) {
+        return reject_create_team(config, tx, user, form, None, msg.to_string());
+    }
+    if !form.project_url.is_empty() {
+        if let Err(msg) = validate_project_url(config, &form.project_url) {
+            return reject_create_team(config, tx, user, form, Some(ProjectUrl), msg);
+        }
+    }
+
+    let n_teams_by_user = db::count_teams_by_creator(tx, &user.email)?;
+    if n_teams_by_user >= config.app.max_teams_per_creator as i64 {
+        let msg = format!("You already created {n_teams_by_user} teams, chill out!");
+        return reject_create_team(config, tx, user, form, None, msg);
+    }
+
+    // Check for a duplicate name up front, so the common case does not depend
+    // on the constraint. The unique constraint below is only a backstop for
+    // when two people register the same name at the same time.
+    let name_taken = "A team with that name already exists.".to_string();
+    if db::count_teams_by_name(tx, &form.team_name)? > 0 {
+        return reject_create_team(config, tx, user, form, Some(TeamName), name_taken);
+    }
+
+    // A constraint violation is a client error, not a busy database, so it is
+    // not retried by `with_transaction`: the 400 rolls back and that's final.
+    let project_url = Some(&form.project_url).filter(|url| !url.is_empty());
+    let team_id = match db::add_team(
+        tx,
+        &form.team_name,
+        &user.email,
+        &form.description,
+        project_url.map(|url| url.as_str()),
+    )
+    .map_err(db_ext::Error::from)
+    {
+        Ok(id) => id,
+        Err(db_ext::Error::UniqueViolation { .. }) => {
+            return reject_create_team(config, tx, user, form, Some(TeamName), name_taken);
+        }
+        Err(err) => return Err(err.into_inner()),
+    };
+
+    // The user who creates the team is initially a member of it, and its captain.
+    db::add_team_member(tx, team_id, &user.email)?;
+    db::set_team_captain(tx, team_id, &user.email)?;
+
+    if !form.idempotency_key.is_empty() {
+        db::add_idempotency_key(tx, &user.email, &form.idempotency_key, team_id)?;
+    }
+
+    let new_url = get_team_url(config, team_id);
+    Ok(redirect_see_other(new_url.as_bytes()))
+}
+
+/// Show the index again with the create team form filled in, and the error.
+///
+/// An error page would lose everything the user typed, which is annoying to
+/// type again, especially on a phone. This responds with a 400 all the same,
+/// so the transaction is rolled back.
+fn reject_create_team(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    mut form: CreateTeamForm,
+    field: Option<CreateTeamField>,
+    message: String,
+) -> db::Result<Response> {
+    if user.wants_json {
+        return Ok(bad_request_for(user, message));
+    }
+    form.error = Some((field, message));
+    let response = render_index(config, tx, user, "", Some(&form))?;
+    Ok(response.with_status_code(400))
+}
+
+/// Return the members of the team in the order they joined, and the captain.
+fn load_team_members(
+    tx: &mut db::Transaction,
+    team_id: i64,
+) -> db::Result<(Vec<String>, Option<String>)> {
+    let mut emails = Vec::new();
+    let mut captain = None;
+    for member in db::iter_team_members(tx, team_id)? {
+        let member = member?;
+        if member.is_captain != 0 {
+            captain = Some(member.member_email.clone());
+        }
+        emails.push(member.member_email);
+    }
+    Ok((emails, captain))
+}
+
+fn get_body_team_id(config: &Config, user: &User, body: &str) -> Result<i64, Response> {
+    let mut team_id = None;
+
+    for (key, value) in parse_form(config, body) {
+        match key.as_ref() {
+            "team-id" => match i64::from_str(value.as_ref()) {
+                Ok(id) => team_id = Some(id),
+                Err(..) => return Err(bad_request_for(user, "Invalid team id.")),
+            },
+            _ => return Err(bad_request_for(user, "Unexpected form field.")),
+        }
+    }
+
+    check_team_id(user, team_id)
+}
+
+/// Check that the team id from a form is present and could exist.
+fn check_team_id(user: &User, team_id: Option<i64>) -> Result<i64, Response> {
+    match team_id {
+        None => Err(bad_request_for(user, "Need a team id.")),
+        // Team ids are assigned by SQLite, and those start at 1.
+        Some(id) if id <= 0 => Err(bad_request_for(
+            user,
+            format!("Invalid team id {id}, team ids are positive."),
+        )),
+        Some(id) => Ok(id),
+    }
+}
+
+pub fn handle_edit_team(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    body: &str,
+) -> db::Result<Response> {
+    let phase = crate::load_phase(tx)?;
+    if !matches!(phase, Phase::Registration) {
+        return Ok(conflict("Registration is closed, teams cannot be changed."));
+    }
+
+    if is_registration_locked(tx)? {
+        return Ok(conflict(
+            "Registration is locked, teams cannot be changed right now.",
+        ));
+    }
+
+    let mut team_id = None;
+    let mut team_name = String::new();
+    let mut description = String::new();
+
+    for (key, value) in parse_form(config, body) {
+        match key.as_ref() {
+            "team-id" => match i64::from_str(value.as_ref()) {
+                Ok(id) if id > 0 => team_id = Some(id),
+                _ => return Ok(bad_request_for(user, "Invalid team id.")),
+            },
+            "team-name" => team_name = value.trim().to_string(),
+            "description" => description = value.trim().to_string(),
+            _ => return Ok(bad_request_for(user, "Unexpected form field.")),
+        }
+    }
+
+    let team_id = match team_id {
+        Some(id) => id,
+        None => return Ok(bad_request_for(user, "Need a team id.")),
+    };
+    let team = match db::get_team(tx, team_id)? {
+        Some(team) => team,
+        None => return Ok(not_found("There is no team with this id.")),
+    };
+    let (members, _captain) = load_team_members(tx, team_id)?;
+    if !members.contains(&user.email) {
+        return Ok(forbidden(
+            "You can only edit teams that you are a member of.",
+        ));
+    }
+
+    if let Err(msg) = validate_string(config, "The team name", 65, &team_name) {
+        return Ok(bad_request_for(user, msg));
+    }
+    if let Err(msg) = validate_string(config, "The description", 120, &description) {
+        return Ok(bad_request_for(user, msg));
+    }
+    if let Err(msg) = check_blocked_words(config, user, &team_name, &description) {
+        return Ok(bad_request_for(user, msg));
+    }
+
+    // Keeping the current name is fine, also with a different case, it only
+    // needs to be unique among the other teams. SQLite only folds ASCII case.
+    // See also `handle_create_team` for the constraint backstop.
+    if !team_name.eq_ignore_ascii_case(&team.name) && db::count_teams_by_name(tx, &team_name)? > 0 {
+        return Ok(bad_request_for(
+            user,
+            "A team with that name already exists.",
+        ));
+    }
+
+    match db::update_team(tx, team_id, &team_name, &description).map_err(db_ext::Error::from) {
+        Ok(()) => {}
+        Err(db_ext::Error::UniqueViolation { .. }) => {
+            return Ok(bad_request_for(
+                user,
+                "A team with that name already exists.",
+            ))
+        }
+        Err(err) => return Err(err.into_inner()),
+    }
+
+    let new_url = get_team_url(config, team_id);
+    Ok(redirect_see_other(new_url.as_bytes()))
+}
+
+pub fn handle_delete_team(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    body: &str,
+) -> db::Result<Response> {
+    let phase = crate::load_phase(tx)?;
+    if !matches!(phase, Phase::Registration) {
+        return Ok(conflict("Registration is closed, teams cannot be changed."));
+    }
+
+    if is_registration_locked(tx)? {
+        return Ok(conflict(
+            "Registration is locked, teams cannot be changed right now.",
+        ));
+    }
+
+    // Deleting can't be undone, so the first submit only asks for confirmation,
+    // see `view_confirm_delete_team`, and that page submits `confirm=true`.
+    let mut confirm = false;
+    let mut team_id = None;
+    for (key, value) in parse_form(config, body) {
+        match key.as_ref() {
+            "team-id" => match i64::from_str(value.as_ref()) {
+                Ok(id) => team_id = Some(id),
+                Err(..) => return Ok(bad_request_for(user, "Invalid team id.")),
+            },
+            "confirm" => confirm = value == "true",
+            _ => return Ok(bad_request_for(user, "Unexpected form field.")),
+        }
+    }
+    let team_id = match check_team_id(user, team_id) {
+        Ok(id) => id,
+        Err(err_response) => return Ok(err_response),
+    };
+
+    // Only the people involved with the team get to delete it. In practice
+    // the emptiness check below already implies that, but we should not rely
+    // on that to authorize the deletion.
+    let team = match db::get_team(tx, team_id)? {
+        Some(team) => team,
+        None => return Ok(not_found("There is no team with this id.")),
+    };
+
+    // The admin can delete any team, including its members and votes, for
+    // example to clean up spam or test teams.
+    if user.is_admin {
+        if !confirm {
+            return Ok(respond_html(view_confirm_delete_team(config, &team, true)));
+        }
+        db::delete_team_cascade(tx, team_id)?;
+        let detail = format!("Deleted team {} (id {}).", team.name, team.id);
+        db::append_audit(tx, &user.email, "delete-team", &detail)?;
+        return Ok(redirect_see_other(config.server.prefix.as_bytes()));
+    }
+
+    let (members, _captain) = load_team_members(tx, team_id)?;
+    if !(team.creator_email == user.email || members.contains(&user.email)) {
+        return Ok(forbidden(
+            "You can only delete teams that you are a member of.",
+        ));
+    }
+
+    if !confirm {
+        return Ok(respond_html(view_confirm_delete_team(config, &team, false)));
+    }
+
+    // Remove ourselves from the team first.
+    db::remove_team_member(tx, team_id, &user.email)?;
+
+    // Confirm that the team is now empty.
+    if db::iter_team_members(tx, team_id)?.next().is_some() {
+        // Returning an error status code will also roll back the transaction.
+        return Ok(conflict("The team is not empty, we can't delete it yet."));
+    }
+
+    db::delete_team(tx, team_id)?;
+
+    Ok(redirect_see_other(config.server.prefix.as_bytes()))
+}
+
+/// The page that asks whether to really delete the team, see `handle_delete_team`.
+fn view_confirm_delete_team(config: &Config, team: &db::Team, is_cascade: bool) -> Markup {
+    html! {
+        (view_html_head(config, "Delete Team — Hack-o-matic"))
+        body {
+            h1 { "Delete " (team.name) "?" }
+            @if is_cascade {
+                p {
+                    "This deletes the team including its members and the votes "
+                    "for it. This can’t be undone."
+                }
+            } @else {
+                p { "This can’t be undone." }
+            }
+            form action=(format!("{}/delete-team", config.server.prefix)) method="post" {
+                input type="hidden" name="team-id" value=(team.id);
+                input type="hidden" name="confirm" value="true";
+                button type="submit" { "Delete Team" }
+            }
+            p {
+                a href=(get_team_url(config, team.id)) { "« Keep the team" }
+            }
+        }
+    }
+}
+
+pub fn handle_leave_team(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    body: &str,
+) -> db::Result<Response> {
+    let phase = crate::load_phase(tx)?;
+    if !matches!(phase, Phase::Registration) {
+        return Ok(conflict("Registration is closed, teams cannot be changed."));
+    }
+
+    if is_registration_locked(tx)? {
+        return Ok(conflict(
+            "Registration is locked, teams cannot be changed right now.",
+        ));
+    }
+
+    let team_id = match get_body_team_id(config, user, body) {
+        Ok(id) => id,
+        Err(err_response) => return Ok(err_response),
+    };
+
+    let (_members, captain) = load_team_members(tx, team_id)?;
+
+    // Remove ourselves from the team first.
+    db::remove_team_member(tx, team_id, &user.email)?;
+
+    // Confirm that the team is not empty. If it is, we should have deleted it.
+    // We could do it automatically but let's be safe and not delete anything
+    // unless a delete is explicitly what was requested.
+    let next_member = match db::iter_team_members(tx, team_id)?.next() {
+        Some(member) => member?.member_email,
+        None => {
+            return Ok(conflict(
+                "It looks like all your team members have abandoned you.\n\
+                You are the last member, leaving the team would leave it empty.\n\
+                If you really want to do that to the team, then go back, \n\
+                refresh the page, and choose 'Delete Team'.",
+            ))
+        }
+    };
+
+    // If the creator leaves, the team passes to the member who joined first
+    // after them, so the team does not count towards the limit of somebody who
+    // is no longer involved. The new creator may end up above their own limit
+    // this way, but we don't want to block people from leaving over that.
+    if let Some(team) = db::get_team(tx, team_id)? {
+        if team.creator_email == user.email {
+            db::set_team_creator(tx, team_id, &next_member)?;
+        }
+    }
+    // Same for the captain, a team should always have a point of contact.
+    if captain.as_deref() == Some(&user.email[..]) {
+        db::set_team_captain(tx, team_id, &next_member)?;
+    }
+
+    let new_url = get_team_url(config, team_id);
+    Ok(redirect_see_other(new_url.as_bytes()))
+}
+
+/// Make a member of the team its captain, the point of contact for the organizers.
+pub fn handle_set_captain(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    body: &str,
+) -> db::Result<Response> {
+    let phase = crate::load_phase(tx)?;
+    if !matches!(phase, Phase::Registration) {
+        return Ok(conflict("Registration is closed, teams cannot be changed."));
+    }
+
+    if is_registration_locked(tx)? {
+        return Ok(conflict(
+            "Registration is locked, teams cannot be changed right now.",
+        ));
+    }
+
+    let mut team_id = None;
+    let mut member_email = None;
+    for (key, value) in parse_form(config, body) {
+        match key.as_ref() {
+            "team-id" => match i64::from_str(value.as_ref()) {
+                Ok(id) => team_id = Some(id),
+                Err(..) => return Ok(bad_request_for(user, "Invalid team id.")),
+            },
+            "member-email" => member_email = Some(value.into_owned()),
+            _ => return Ok(bad_request_for(user, "Unexpected form field.")),
+        }
+    }
+    let team_id = match check_team_id(user, team_id) {
+        Ok(id) => id,
+        Err(err_response) => return Ok(err_response),
+    };
+    let member_email = match member_email {
+        Some(email) => email,
+        None => return Ok(bad_request_for(user, "Need the email of the new captain.")),
+    };
+
+    let (members, _captain) = load_team_members(tx, team_id)?;
+    if !members.contains(&user.email) {
+        return Ok(forbidden(
+            "You can only pick the captain of teams that you are a member of.",
+        ));
+    }
+    if !members.contains(&member_email) {
+        return Ok(bad_request_for(
+            user,
+            "The captain must be a member of the team.",
+        ));
+    }
+
+    db::set_team_captain(tx, team_id, &member_email)?;
+
+    let new_url = get_team_url(config, team_id);
+    Ok(redirect_see_other(new_url.as_bytes()))
+}
+
+pub fn handle_join_team(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    body: &str,
+) -> db::Result<Response> {
+    let phase = crate::load_phase(tx)?;
+    if !matches!(phase, Phase::Registration) {
+        return Ok(conflict("Registration is closed, teams cannot be changed."));
+    }
+
+    if is_registration_locked(tx)? {
+        return Ok(conflict(
+            "Registration is locked, teams cannot be changed right now.",
+        ));
+    }
+
+    let team_id = match get_body_team_id(config, user, body) {
+        Ok(id) => id,
+        Err(err_response) => return Ok(err_response),
+    };
+
+    // Confirm that the team exists before we join it. For it to exist, it must
+    // have members.
+    let (members, _captain) = load_team_members(tx, team_id)?;
+    if members.is_empty() {
+        return Ok(conflict(
+            "It looks like all team members have left this team before you joined.\n\
+            It no longer exists, but if you like you can go back and create a new team.",
+        ));
+    }
+    if is_team_full(config, members.len()) && !members.contains(&user.email) {
+        return Ok(conflict(
+            "This team is full, it already has as many members as a team can have.",
+        ));
+    }
+
+    db::add_team_member(tx, team_id, &user.email)?;
+
+    let new_url = get_team_url(config, team_id);
+    Ok(redirect_see_other(new_url.as_bytes()))
+}
+
+pub fn handle_set_banner(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    body: &str,
+) -> db::Result<Response> {
+    if !user.is_admin {
+        return Ok(forbidden("Only the admin is allowed to change the banner."));
+    }
+
+    let mut message = String::new();
+    for (key, value) in parse_form(config, body) {
+        match key.as_ref() {
+            // If the form has both the text field and the clear button, then
+            // the button comes last and takes precedence.
+            "message" => message = value.trim().to_string(),
+            _ => return Ok(bad_request("Unexpected form field.")),
+        }
+    }
+
+    if !message.is_empty() {
+        if let Err(msg) = validate_string(config, "The banner", 200, &message) {
+            return Ok(bad_request(msg));
+        }
+    }
+
+    db::set_setting(tx, "banner", &message)?;
+    Ok(redirect_see_other(config.server.prefix.as_bytes()))
+}
+
+/// Lock or unlock registration, so teams can be frozen before the next phase.
+///
+/// While locked, nobody can create, join, leave, or delete teams, but the phase
+/// stays the same, so e.g. voting does not open yet.
+pub fn handle_set_registration_lock(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    body: &str,
+) -> db::Result<Response> {
+    if !user.is_admin {
+        return Ok(forbidden("Only the admin can lock registration."));
+    }
+
+    let mut locked = None;
+    for (key, value) in parse_form(config, body) {
+        match key.as_ref() {
+            "locked" => locked = bool::from_str(&value).ok(),
+            _ => return Ok(bad_request("Unexpected form field.")),
+        }
+    }
+    let locked = match locked {
+        Some(locked) => locked,
+        None => return Ok(bad_request("Expected locked=true or locked=false.")),
+    };
+
+    db::set_setting(tx, "registration_locked", &locked.to_string())?;
+    let action = if locked {
+        "lock-registration"
+    } else {
+        "unlock-registration"
+    };
+    db::append_audit(tx, &user.email, action, "")?;
+    Ok(redirect_see_other(config.server.prefix.as_bytes()))
+}
+
+/// Add participants to the list that `view_non_voters` checks against.
+///
+/// The body has one email address per line. If any of them is invalid, we
+/// import none, so the admin can fix the list and submit it again as a whole.
+pub fn handle_import_participants(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    body: &str,
+) -> db::Result<Response> {
+    if !user.is_admin {
+        return Ok(forbidden("Only the admin can import participants."));
+    }
+
+    let mut emails_text = String::new();
+    for (key, value) in parse_form(config, body) {
+        match key.as_ref() {
+            "emails" => emails_text = value.into_owned(),
+            _ => return Ok(bad_request("Unexpected form field.")),
+        }
+    }
+
+    let mut emails = Vec::new();
+    for (i, line) in emails_text.lines().enumerate() {
+        let email = line.trim();
+        if email.is_empty() {
+            continue;
+        }
+        if let Err(msg) = validate_email(email) {
+            return Ok(bad_request(format!(
+                "Line {} is not a valid email address: {msg}",
+                i + 1
+            )));
+        }
+        emails.push(email);
+    }
+
+    for email in emails {
+        db::add_participant(tx, email)?;
+    }
+    Ok(redirect_see_other(config.server.prefix.as_bytes()))
+}
+
+pub fn handle_set_coins_to_spend(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    body: &str,
+) -> db::Result<Response> {
+    if !user.is_admin {
+        return Ok(forbidden("Only the admin is allowed to change the budget."));
+    }
+    let phase = crate::load_phase(tx)?;
+    if !matches!(
+        phase,
+        Phase::Registration | Phase::Presentation | Phase::Evaluation
+    ) {
+        return Ok(conflict(
+            "Voting is closed, the budget can’t change anymore.",
+        ));
+    }
+
+    let mut coins_to_spend = None;
+    for (key, value) in parse_form(config, body) {
+        match key.as_ref() {
+            "coins-to-spend" => coins_to_spend = u32::from_str(value.trim()).ok(),
+            _ => return Ok(bad_request("Unexpected form field.")),
+        }
+    }
+    let coins_to_spend = match coins_to_spend {
+        Some(n) if n > 0 => n,
+        _ => {
+            return Ok(bad_request(
+                "The budget must be a positive number of coins.",
+            ))
+        }
+    };
+
+    // If we lowered the budget below what somebody spent, their vote would
+    // become invalid, and they could not change it without starting over.
+    let max_spent = match config.app.vote_cost {
+        VoteCost::Quadratic => db::get_max_coins_spent(tx)?,
+        VoteCost::Linear => db::get_max_points_spent(tx)?,
+    };
+    if (coins_to_spend as i64) < max_spent {
+        return Ok(conflict(format!(
+            "Somebody already spent {max_spent} coins, \
+            the budget can’t be lower than that."
+        )));
+    }
+
+    db::set_setting(tx, "coins_to_spend", &coins_to_spend.to_string())?;
+    Ok(redirect_see_other(config.server.prefix.as_bytes()))
+}
+
+pub fn handle_event_feedback(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    body: &str,
+) -> db::Result<Response> {
+    let phase = crate::load_phase(tx)?;
+    if !matches!(phase, Phase::Celebration) {
+        return Ok(bad_request(
+            "Feedback opens when the hackathon is over, come back later.",
+        ));
+    }
+
+    let mut rating = 0_i64;
+    let mut comment = String::new();
+    for (key, value) in parse_form(config, body) {
+        match key.as_ref() {
+            "rating" => match i64::from_str(value.as_ref()) {
+                Ok(r) if (1..=5).contains(&r) => rating = r,
+                _ => return Ok(bad_request("The rating must be between 1 and 5.")),
+            },
+            "comment" => comment = value.trim().to_string(),
+            _ => return Ok(bad_request("Unexpected form field.")),
+        }
+    }
+
+    if rating == 0 {
+        return Ok(bad_request("Please pick a rating between 1 and 5."));
+    }
+    if !comment.is_empty() {
+        if let Err(msg) = validate_string(config, "The comment", 200, &comment) {
+            return Ok(bad_request(msg));
+        }
+    }
+
+    db::set_event_feedback(tx, &user.email, rating, &comment)?;
+    Ok(redirect_see_other(config.server.prefix.as_bytes()))
+}
+
+/// The form that the admin submits to change the phase.
+struct PhaseForm {
+    /// The phase that the page with the form showed, see `is_stale`.
+    expected: Option<Phase>,
+    /// Whether the admin confirmed reopening the vote, see `handle_phase_prev`.
+    confirm: bool,
+}
+
+impl PhaseForm {
+    fn parse(config: &Config, body: &str) -> Result<PhaseForm, Response> {
+        let mut form = PhaseForm {
+            expected: None,
+            confirm: false,
+        };
+        for (key, value) in parse_form(config, body) {
+            match key.as_ref() {
+                "phase" => match Phase::from_str(value.as_ref()) {
+                    Some(phase) => form.expected = Some(phase),
+                    None => return Err(bad_request("Invalid phase.")),
+                },
+                "confirm" => form.confirm = value == "true",
+                _ => return Err(bad_request("Unexpected form field.")),
+            }
+        }
+        Ok(form)
+    }
+
+    /// Return whether the form is from a page that showed an older phase.
+    ///
+    /// With two tabs open, clicking "Next Phase" in both would skip a phase.
+    /// The form includes the phase that the page showed, and when that is no
+    /// longer the current phase, we leave it. Without the field, e.g. when the
+    /// admin uses curl, we don't check.
+    fn is_stale(&self, current: Phase) -> bool {
+        self.expected.map_or(false, |phase| phase != current)
+    }
+}
+
+/// Change the phase, and remember once the results have been shown.
+fn set_phase(tx: &mut db::Transaction, phase: Phase) -> db::Result<()> {
+    db::set_current_phase(tx, phase.to_str())?;
+    if matches!(phase, Phase::Revelation | Phase::Celebration) {
+        db::set_setting(tx, "results_revealed", "true")?;
+    }
+    Ok(())
+}
+
+/// Return whether the admin locked registration, see `handle_set_registration_lock`.
+fn is_registration_locked(tx: &mut db::Transaction) -> db::Result<bool> {
+    Ok(db::get_setting(tx, "registration_locked")?.as_deref() == Some("true"))
+}
+
+/// Return whether the phase has been past the vote, so people saw the results.
+///
+/// During the revelation only the admin sees them, but they reveal them to
+/// everybody in the room.
+fn are_results_revealed(tx: &mut db::Transaction) -> db::Result<bool> {
+    Ok(db::get_setting(tx, "results_revealed")?.is_some())
+}
+
+fn view_confirm_reopen_vote(config: &Config, current: Phase) -> Markup {
+    html! {
+        (view_html_head(config, "Reopen the Vote — Hack-o-matic"))
+        body {
+            h1 { "Reopen the vote?" }
+            p {
+                "The results were already revealed. If you go back to the "
+                "evaluation, people who saw them can change their vote "
+                "with that knowledge, and the results become provisional."
+            }
+            form action=(format!("{}/prev", config.server.prefix)) method="post" {
+                input type="hidden" name="phase" value=(current.to_str());
+                input type="hidden" name="confirm" value="true";
+                button type="submit" { "Reopen the Vote" }
+            }
+            p {
+                a href=(config.server.prefix) { "« Stay in the " (current.to_str()) }
+            }
+        }
+    }
+}
+
+pub fn handle_phase_prev(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    body: &str,
+) -> db::Result<Response> {
+    if !user.is_admin {
+        return Ok(forbidden("Only the admin is allowed to change the phase."));
+    }
+    let current = crate::load_phase(tx)?;
+    let form = match PhaseForm::parse(config, body) {
+        Ok(form) => form,
+        Err(err_response) => return Ok(err_response),
+    };
+    if form.is_stale(current) {
+        return Ok(redirect_see_other(config.server.prefix.as_bytes()));
+    }
+    let mut prev = current.prev();
+    if matches!(prev, Phase::Judging) && config.app.judge_emails.is_empty() {
+        prev = prev.prev();
+    }
+    // Going back into the vote after the reveal gives everybody who saw the
+    // results an advantage, so that takes a second click.
+    let is_reopen = matches!(prev, Phase::Evaluation) && are_results_revealed(tx)?;
+    if is_reopen && !form.confirm {
+        return Ok(respond_html(view_confirm_reopen_vote(config, current)));
+    }
+    // The votes can change again before we get back to the celebration,
+    // so the finalized standings would go stale.
+    if matches!(current, Phase::Celebration) {
+        db::delete_results_cache(tx)?;
+    }
+    set_phase(tx, prev)?;
+    let detail = format!("From {} back to {}.", current.to_str(), prev.to_str());
+    let action = if is_reopen {
+        "reopen-vote"
+    } else {
+        "phase-prev"
+    };
+    db::append_audit(tx, &user.email, action, &detail)?;
+    Ok(redirect_see_other(config.server.prefix.as_bytes()))
+}
+
+/// Freeze the current standings, so the page no longer recounts the votes.
+///
+/// After the vote, the standings only change when the admin changes
+/// something by hand, but we would recount all votes on every page load of
+/// every participant, right when everybody is looking at the results.
+pub fn handle_finalize(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+) -> db::Result<Response> {
+    if !user.is_admin {
+        return Ok(forbidden("Only the admin can finalize the results."));
+    }
+    if !matches!(crate::load_phase(tx)?, Phase::Celebration) {
+        return Ok(conflict(
+            "The results can only be finalized during the celebration.",
+        ));
+    }
+    if db::get_results_finalized_at(tx)?.is_some() {
+        return Ok(conflict("The results are already final."));
+    }
+    // Without categories, the votes are in category 0.
+    let n_categories = config.app.categories.len().max(1);
+    for category_id in 0..n_categories as i64 {
+        // The cache is still empty, so this counts the votes.
+        for entry in load_ranked_team_entries(config, tx, category_id)? {
+            db::insert_cached_result(
+                tx,
+                entry.team.id,
+                category_id,
+                entry.rank as i64,
+                entry.total_points,
+            )?;
+        }
+    }
+    Ok(redirect_see_other(config.server.prefix.as_bytes()))
+}
+
+/// Drop the finalized standings, so the page counts the votes again.
+pub fn handle_unfinalize(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+) -> db::Result<Response> {
+    if !user.is_admin {
+        return Ok(forbidden("Only the admin can recount the results."));
+    }
+    db::delete_results_cache(tx)?;
+    Ok(redirect_see_other(config.server.prefix.as_bytes()))
+}
+
+pub fn handle_phase_next(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    body: &str,
+) -> db::Result<Response> {
+    if !user.is_admin {
+        return Ok(forbidden("Only the admin is allowed to change the phase."));
+    }
+    let current = crate::load_phase(tx)?;
+    match PhaseForm::parse(config, body) {
+        Ok(form) if form.is_stale(current) => {
+            return Ok(redirect_see_other(config.server.prefix.as_bytes()))
+        }
+        Ok(_form) => {}
+        Err(err_response) => return Ok(err_response),
+    }
+    if let (Phase::Presentation, Some(min_size)) = (current.next(), config.app.min_team_size) {
+        let small_teams =
+            db::iter_teams_below_size(tx, min_size as i64)?.collect::<Result<Vec<_>, _>>()?;
+        if !small_teams.is_empty() {
+            let teams = small_teams
+                .iter()
+                .map(|t| match t.n_members {
+                    1 => format!("{} (1 member)", t.team_name),
+                    n => format!("{} ({n} members)", t.team_name),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Ok(conflict(format!(
+                "Teams need at least {min_size} members before they can present, \
+                but these teams are smaller: {teams}."
+            )));
+        }
+    }
+    if let (Phase::Evaluation, Some(min_teams)) = (current.next(), config.app.min_teams_to_vote) {
+        let n_teams = db::count_teams(tx)?;
+        if n_teams < min_teams as i64 {
+            let teams = match n_teams {
+                0 => "There are no teams".to_string(),
+                1 => "There is only one team".to_string(),
+                n => format!("There are only {n} teams"),
+            };
+            return Ok(conflict(format!(
+                "{teams}, but voting needs at least {min_teams}. \
+                Wait for more teams to register before opening the vote."
+            )));
+        }
+    }
+    let next = get_next_phase(config, current);
+    set_phase(tx, next)?;
+    let detail = format!("From {} to {}.", current.to_str(), next.to_str());
+    db::append_audit(tx, &user.email, "phase-next", &detail)?;
+    Ok(redirect_see_other(config.server.prefix.as_bytes()))
+}
+
+fn get_next_phase(config: &Config, current: Phase) -> Phase {
+    // Without judges, there is nothing to do in the judging phase.
+    let next = current.next();
+    if matches!(next, Phase::Judging) && config.app.judge_emails.is_empty() {
+        next.next()
+    } else {
+        next
+    }
+}
+
+/// Close the vote if the evaluation deadline has passed, see `evaluation_deadline`.
+///
+/// Returns whether the phase changed.
+pub fn advance_past_evaluation_deadline(
+    config: &Config,
+    tx: &mut db::Transaction,
+) -> db::Result<bool> {
+    let current = crate::load_phase(tx)?;
+    if !matches!(current, Phase::Evaluation) || !is_past_evaluation_deadline(config) {
+        return Ok(false);
+    }
+    let next = get_next_phase(config, current);
+    set_phase(tx, next)?;
+    println!(
+        "The evaluation deadline has passed, moved on to {}.",
+        next.to_str()
+    );
+    Ok(true)
+}
+
+pub fn handle_mark_presented(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    body: &str,
+) -> db::Result<Response> {
+    if !user.is_admin {
+        return Ok(forbidden("Only the admin can mark teams as presented."));
+    }
+    if crate::load_phase(tx)? != Phase::Presentation {
+        return Ok(conflict(
+            "Teams can only be marked as presented during the presentations.",
+        ));
+    }
+    let team_id = match get_body_team_id(config, user, body) {
+        Ok(id) => id,
+        Err(err_response) => return Ok(err_response),
+    };
+    if db::get_team(tx, team_id)?.is_none() {
+        return Ok(not_found("This team does not exist (any more)."));
+    }
+    db::set_team_presented(tx, team_id)?;
+    let new_url = get_team_url(config, team_id);
+    Ok(redirect_see_other(new_url.as_bytes()))
+}
+
+pub fn handle_unmark_presented(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    body: &str,
+) -> db::Result<Response> {
+    if !user.is_admin {
+        return Ok(forbidden("Only the admin can mark teams as presented."));
+    }
+    if crate::load_phase(tx)? != Phase::Presentation {
+        return Ok(conflict(
+            "Teams can only be marked as presented during the presentations.",
+        ));
+    }
+    let team_id = match get_body_team_id(config, user, body) {
+        Ok(id) => id,
+        Err(err_response) => return Ok(err_response),
+    };
+    if db::get_team(tx, team_id)?.is_none() {
+        return Ok(not_found("This team does not exist (any more)."));
+    }
+    db::unset_team_presented(tx, team_id)?;
+    let new_url = get_team_url(config, team_id);
+    Ok(redirect_see_other(new_url.as_bytes()))
+}
+
+/// Mark a team as a favorite of the user, to remember it for the vote.
+pub fn handle_favorite(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    body: &str,
+) -> db::Result<Response> {
+    if !matches!(
+        crate::load_phase(tx)?,
+        Phase::Presentation | Phase::Evaluation
+    ) {
+        return Ok(conflict(
+            "You can only pick favorites during the presentations and the vote.",
+        ));
+    }
+    let team_id = match get_body_team_id(config, user, body) {
+        Ok(id) => id,
+        Err(err_response) => return Ok(err_response),
+    };
+    if db::get_team(tx, team_id)?.is_none() {
+        return Ok(not_found("This team does not exist (any more)."));
+    }
+    db::add_favorite(tx, &user.email, team_id)?;
+    let new_url = get_team_url(config, team_id);
+    Ok(redirect_see_other(new_url.as_bytes()))
+}
+
+pub fn handle_unfavorite(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    body: &str,
+) -> db::Result<Response> {
+    let team_id = match get_body_team_id(config, user, body) {
+        Ok(id) => id,
+        Err(err_response) => return Ok(err_response),
+    };
+    db::remove_favorite(tx, &user.email, team_id)?;
+    let new_url = get_team_url(config, team_id);
+    Ok(redirect_see_other(new_url.as_bytes()))
+}
+
+/// Whether the user can write feedback on teams, see `handle_team_feedback`.
+///
+/// When there are judges, only they can, otherwise everybody can. There is
+/// nothing to give feedback on before the presentations.
+fn can_give_team_feedback(config: &Config, user: &User, phase: Phase) -> bool {
+    !matches!(phase, Phase::Registration) && (config.app.judge_emails.is_empty() || user.is_judge)
+}
+
+/// Return the team that the user wants to give feedback, or the error response.
+fn check_team_feedback(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    team_id: i64,
+) -> db::Result<Result<db::Team, Response>> {
+    let phase = crate::load_phase(tx)?;
+    if matches!(phase, Phase::Registration) {
+        return Ok(Err(conflict(
+            "Feedback on teams opens with the presentations.",
+        )));
+    }
+    if !can_give_team_feedback(config, user, phase) {
+        return Ok(Err(forbidden(
+            "Only the judges can give feedback on teams.",
+        )));
+    }
+    let team = match db::get_team(tx, team_id)? {
+        Some(team) => team,
+        None => return Ok(Err(not_found("This team does not exist (any more)."))),
+    };
+    let own_teams = db::iter_member_teams(tx, &user.email)?.collect::<Result<Vec<_>, _>>()?;
+    if own_teams.contains(&team_id) {
+        return Ok(Err(forbidden("You can’t give feedback on your own team.")));
+    }
+    Ok(Ok(team))
+}
+
+fn view_team_feedback_form(config: &Config, team: &db::Team, current: &str) -> Markup {
+    html! {
+        (view_html_head(config, "Team Feedback — Hack-o-matic"))
+        body {
+            h1 { "Feedback for " (team.name) }
+            p {
+                "The members of the team can read your feedback, with your name, "
+                "once the hackathon is over. Leave it empty to remove your feedback."
+            }
+            form action=(format!("{}/team-feedback", config.server.prefix)) method="post" {
+                input type="hidden" name="team-id" value=(team.id);
+                label {
+                    "Feedback: "
+                    textarea name="body" rows="6" { (current) }
+                }
+                button type="submit" { "Save Feedback" }
+            }
+            p {
+                a href=(get_team_url(config, team.id)) { "« Back to the teams" }
+            }
+        }
     }
 }
 
-pub fn handle_delete_team(
+/// Show the form to write feedback on a team, with the feedback so far.
+pub fn handle_team_feedback_form(
     config: &Config,
     tx: &mut db::Transaction,
     user: &User,
-    body: &str,
+    query: &str,
 ) -> db::Result<Response> {
-    let phase = crate::load_phase(tx)?;
-    if !matches!(phase, Phase::Registration) {
-        return Ok(bad_request(
-            "Registration is closed, teams cannot be changed.",
-        ));
-    }
-
-    let team_id = match get_body_team_id(body) {
+    let team_id = form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _value)| key == "team-id")
+        .and_then(|(_key, value)| i64::from_str(value.as_ref()).ok());
+    let team_id = match check_team_id(user, team_id) {
         Ok(id) => id,
         Err(err_response) => return Ok(err_response),
     };
-
-    // Remove ourselves from the team first.
-    db::remove_team_member(tx, team_id, &user.email)?;
-
-    // Confirm that the team is now empty.
-    for _member in db::iter_team_members(tx, team_id)? {
-        // Returning an error status code will also roll back the transaction.
-        return Ok(conflict("The team is not empty, we can't delete it yet."));
-    }
-
-    db::delete_team(tx, team_id)?;
-
-    Ok(redirect_see_other(config.server.prefix.as_bytes()))
+    let team = match check_team_feedback(config, tx, user, team_id)? {
+        Ok(team) => team,
+        Err(response) => return Ok(response),
+    };
+    let current = db::get_team_feedback_by(tx, &user.email, team_id)?.unwrap_or_default();
+    Ok(respond_html(view_team_feedback_form(
+        config, &team, &current,
+    )))
 }
 
-pub fn handle_leave_team(
+/// Save written feedback on a team, for the members to read afterwards.
+///
+/// Empty feedback removes the feedback that the user gave before.
+pub fn handle_team_feedback(
     config: &Config,
     tx: &mut db::Transaction,
     user: &User,
     body: &str,
 ) -> db::Result<Response> {
-    let phase = crate::load_phase(tx)?;
-    if !matches!(phase, Phase::Registration) {
-        return Ok(bad_request(
-            "Registration is closed, teams cannot be changed.",
-        ));
+    let mut team_id = None;
+    let mut text = String::new();
+    for (key, value) in parse_form(config, body) {
+        match key.as_ref() {
+            "team-id" => match i64::from_str(value.as_ref()) {
+                Ok(id) => team_id = Some(id),
+                Err(..) => return Ok(bad_request("Invalid team id.")),
+            },
+            // The form has a text area, but `validate_string` does not allow
+            // line breaks, so we store a single paragraph.
+            "body" => {
+                text = value
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+            _ => return Ok(bad_request("Unexpected form field.")),
+        }
     }
-
-    let team_id = match get_body_team_id(body) {
+    let team_id = match check_team_id(user, team_id) {
         Ok(id) => id,
         Err(err_response) => return Ok(err_response),
     };
+    if let Err(response) = check_team_feedback(config, tx, user, team_id)? {
+        return Ok(response);
+    }
 
-    // Remove ourselves from the team first.
-    db::remove_team_member(tx, team_id, &user.email)?;
+    if text.is_empty() {
+        db::delete_team_feedback(tx, &user.email, team_id)?;
+    } else {
+        if let Err(msg) = validate_string(config, "The feedback", 1000, &text) {
+            return Ok(bad_request(msg));
+        }
+        db::set_team_feedback(tx, &user.email, team_id, &text)?;
+    }
+    let new_url = get_team_url(config, team_id);
+    Ok(redirect_see_other(new_url.as_bytes()))
+}
 
-    // Confirm that the team is not empty. If it is, we should have deleted it.
-    // We could do it automatically but let's be safe and not delete anything
-    // unless a delete is explicitly what was requested.
-    if db::iter_team_members(tx, team_id)?.next().is_none() {
-        return Ok(conflict(
-            "It looks like all your team members have abandoned you.\n\
-            You are the last member, leaving the team would leave it empty.\n\
-            If you really want to do that to the team, then go back, \n\
-            refresh the page, and choose 'Delete Team'.",
-        ));
+fn view_integrity(config: &Config, orphans: &[db::OrphanedRow]) -> Markup {
+    html! {
+        (view_html_head(config, "Integrity Check — Hack-o-matic"))
+        body {
+            h1 { "Integrity Check" }
+            @if orphans.is_empty() {
+                p { "All memberships, votes, and presentations refer to existing teams." }
+            } @else {
+                p { "The following rows refer to a team that does not exist:" }
+                ul {
+                    @for orphan in orphans {
+                        li {
+                            code { (orphan.table_name) } ", team " (orphan.team_id)
+                            @if !orphan.email.is_empty() {
+                                ", " (orphan.email)
+                            }
+                        }
+                    }
+                }
+                form action=(format!("{}/integrity", config.server.prefix)) method="post" {
+                    button type="submit" { "Delete These Rows" }
+                }
+            }
+            p {
+                a href=(config.server.prefix) { "« Back" }
+            }
+        }
     }
+}
 
-    let new_url = format!("{}#team-{}", config.server.prefix, team_id);
-    Ok(redirect_see_other(new_url.as_bytes()))
+fn view_audit_log(config: &Config, entries: &[db::AuditEntry]) -> Markup {
+    html! {
+        (view_html_head(config, "Audit Log — Hack-o-matic"))
+        body {
+            h1 { "Audit Log" }
+            @if entries.is_empty() {
+                p { "Nothing has been changed by the admin yet." }
+            } @else {
+                table {
+                    tr { th { "Time" } th { "Admin" } th { "Action" } th { "Detail" } }
+                    @for entry in entries {
+                        tr {
+                            td { (entry.created_at) }
+                            td { (entry.actor_email) }
+                            td { code { (entry.action) } }
+                            td { (entry.detail) }
+                        }
+                    }
+                }
+            }
+            p {
+                a href=(config.server.prefix) { "« Back" }
+            }
+        }
+    }
 }
 
-pub fn handle_join_team(
+/// Show the changes that the admin made, newest first, see `db::append_audit`.
+pub fn handle_audit_log(
     config: &Config,
     tx: &mut db::Transaction,
     user: &User,
-    body: &str,
 ) -> db::Result<Response> {
-    let phase = crate::load_phase(tx)?;
-    if !matches!(phase, Phase::Registration) {
-        return Ok(bad_request(
-            "Registration is closed, teams cannot be changed.",
-        ));
-    }
-
-    let team_id = match get_body_team_id(body) {
-        Ok(id) => id,
-        Err(err_response) => return Ok(err_response),
-    };
-
-    // Confirm that the team exists before we join it. For it to exist, it must
-    // have members.
-    if db::iter_team_members(tx, team_id)?.next().is_none() {
-        return Ok(conflict(
-            "It looks like all team members have left this team before you joined.\n\
-            It no longer exists, but if you like you can go back and create a new team.",
-        ));
+    if !user.is_admin {
+        return Ok(forbidden("Only the admin can view the audit log."));
     }
-
-    db::add_team_member(tx, team_id, &user.email)?;
-
-    let new_url = format!("{}#team-{}", config.server.prefix, team_id);
-    Ok(redirect_see_other(new_url.as_bytes()))
+    let entries = db::iter_audit_log(tx)?.collect::<Result<Vec<_>, _>>()?;
+    Ok(respond_html(view_audit_log(config, &entries)))
 }
 
-pub fn handle_phase_prev(
+/// Report rows that reference teams that no longer exist.
+///
+/// Foreign keys should prevent this, but they are only enforced when enabled
+/// on the connection, which may not have been the case for manual edits.
+pub fn handle_integrity(
     config: &Config,
     tx: &mut db::Transaction,
     user: &User,
 ) -> db::Result<Response> {
     if !user.is_admin {
-        return Ok(forbidden("Only the admin is allowed to change the phase."));
+        return Ok(forbidden(
+            "Only the admin can check the database integrity.",
+        ));
     }
-    let current = crate::load_phase(tx)?;
-    db::set_current_phase(tx, current.prev().to_str())?;
-    Ok(redirect_see_other(config.server.prefix.as_bytes()))
+    let orphans = db::iter_orphaned_rows(tx)?.collect::<Result<Vec<_>, _>>()?;
+    Ok(respond_html(view_integrity(config, &orphans)))
 }
 
-pub fn handle_phase_next(
+pub fn handle_integrity_cleanup(
     config: &Config,
     tx: &mut db::Transaction,
     user: &User,
 ) -> db::Result<Response> {
     if !user.is_admin {
-        return Ok(forbidden("Only the admin is allowed to change the phase."));
+        return Ok(forbidden("Only the admin can clean up the database."));
+    }
+    let n_orphans = db::iter_orphaned_rows(tx)?.count();
+    db::delete_orphaned_rows(tx)?;
+    println!("Deleted {n_orphans} rows that referenced nonexistent teams.");
+    let new_url = format!("{}/integrity", config.server.prefix);
+    Ok(redirect_see_other(new_url.as_bytes()))
+}
+
+/// Render an amount of coins, e.g. "1 coin" or "5 coins", like `vote.js` does.
+fn view_coins(n: i64) -> Markup {
+    html! {
+        @if n == 1 { "1 coin" } @else { (n) " coins" }
     }
-    let current = crate::load_phase(tx)?;
-    db::set_current_phase(tx, current.next().to_str())?;
-    Ok(redirect_see_other(config.server.prefix.as_bytes()))
 }
 
-/// Sum the squares of the values in the hashmap without overflow.
-fn get_coins_spent<T>(points: &HashMap<T, i64>) -> Option<i64> {
+/// Return what it costs to give a team this many points, `None` on overflow.
+fn get_vote_cost(config: &Config, points: i64) -> Option<i64> {
+    match config.app.vote_cost {
+        VoteCost::Quadratic => points.checked_mul(points),
+        VoteCost::Linear => points.checked_abs(),
+    }
+}
+
+/// Return the power that points are raised to for the cost, for `vote.js`.
+fn get_vote_cost_exponent(config: &Config) -> u32 {
+    match config.app.vote_cost {
+        VoteCost::Quadratic => 2,
+        VoteCost::Linear => 1,
+    }
+}
+
+/// Return the most points that a voter can give a single team with the coins.
+fn get_max_points(config: &Config, coins_to_spend: u32) -> i32 {
+    let max_points = match config.app.vote_cost {
+        // With quadratic cost, that is the square root of the coins.
+        VoteCost::Quadratic => (coins_to_spend as f32).sqrt().floor() as i32,
+        VoteCost::Linear => coins_to_spend.min(i32::MAX as u32) as i32,
+    };
+    match config.app.max_points_per_team {
+        Some(cap) => max_points.min(cap.min(i32::MAX as u32) as i32),
+        None => max_points,
+    }
+}
+
+/// Sum the cost of the points in the hashmap without overflow.
+fn get_coins_spent<T>(config: &Config, points: &HashMap<T, i64>) -> Option<i64> {
     let mut total: i64 = 0;
     for p in points.values() {
-        let p2 = p.checked_mul(*p)?;
-        total = total.checked_add(p2)?;
+        total = total.checked_add(get_vote_cost(config, *p)?)?;
     }
     Some(total)
 }
 
-pub fn handle_vote(
+/// Split a vote form field name into the category id and the team id.
+///
+/// Without categories, the fields are named `team-ID`, and the category is 0.
+/// With categories, they are named `category-C-team-ID`.
+fn parse_vote_field(key: &str) -> Option<(&str, &str)> {
+    match key.strip_prefix("category-") {
+        Some(rest) => {
+            let (category_id, team) = rest.split_once('-')?;
+            Some((category_id, team.strip_prefix("team-")?))
+        }
+        None => Some(("0", key.strip_prefix("team-")?)),
+    }
+}
+
+/// Return the error response if the user can't vote right now.
+fn check_can_vote(
     config: &Config,
     tx: &mut db::Transaction,
     user: &User,
-    body: &str,
-) -> db::Result<Response> {
-    let phase = crate::load_phase(tx)?;
-    if !matches!(phase, Phase::Evaluation) {
-        return Ok(bad_request("Voting is closed, you can’t vote right now."));
+    phase: Phase,
+) -> db::Result<Option<Response>> {
+    if !user.can_vote(phase) {
+        // This is not a malformed request, the form may simply be from a tab
+        // that was opened while voting was still open.
+        return Ok(Some(conflict(
+            "Voting is closed, you can’t vote right now.",
+        )));
+    }
+    // The phase may lag behind the deadline by a few seconds, until the
+    // background thread advances it.
+    if matches!(phase, Phase::Evaluation) && is_past_evaluation_deadline(config) {
+        return Ok(Some(forbidden(
+            "The voting deadline has passed, you can’t vote anymore.",
+        )));
+    }
+    if is_excluded_from_vote(config, tx, user, phase)? {
+        return Ok(Some(forbidden(
+            "Only the registered participants can vote. If you are participating, \
+            ask the organizers to add you.",
+        )));
+    }
+    Ok(None)
+}
+
+/// Whether the user is not on the participant list, when only participants vote.
+///
+/// See `restrict_vote_to_participants` in the config. Judges vote in their own
+/// phase, and the admin is exempt.
+fn is_excluded_from_vote(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    phase: Phase,
+) -> db::Result<bool> {
+    if !config.app.restrict_vote_to_participants
+        || user.is_admin
+        || !matches!(phase, Phase::Evaluation)
+    {
+        return Ok(false);
     }
+    Ok(db::is_participant(tx, &user.email)? == 0)
+}
+
+/// The points from the vote form, before we check them against the budget.
+struct VoteForm<'a> {
+    /// The name and budget of every category. Without categories, and during
+    /// judging, there is a single unnamed one.
+    categories: Vec<(&'a str, u32)>,
+    /// Map team id to points, per category. Would be nice to do a newtype
+    /// wrapper for teams but I can't be bothered right now.
+    teams_points: Vec<HashMap<i64, i64>>,
+}
+
+/// Parse the vote form, for `handle_vote` and `handle_vote_preview`.
+///
+/// When the form is malformed, the inner result is the response to send.
+fn parse_vote_form<'a>(
+    config: &'a Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    phase: Phase,
+    body: &str,
+) -> db::Result<Result<VoteForm<'a>, Response>> {
+    let categories: Vec<(&str, u32)> =
+        if matches!(phase, Phase::Evaluation) && !config.app.categories.is_empty() {
+            config
+                .app
+                .categories
+                .iter()
+                .map(|c| (c.name.as_str(), c.coins_to_spend))
+                .collect()
+        } else {
+            vec![("", get_coins_to_spend(config, tx, phase)?)]
+        };
 
-    // Map team id to points. Would be nice to do a newtype wrapper for teams
-    // but I can't be bothered right now.
-    let mut teams_points: HashMap<i64, i64> = HashMap::new();
+    let mut teams_points: Vec<HashMap<i64, i64>> = vec![HashMap::new(); categories.len()];
 
-    for (key, value) in form_urlencoded::parse(body.as_bytes()) {
-        match key.as_ref().strip_prefix("team-") {
-            Some(team_id_str) => {
+    for (key, value) in parse_form(config, body) {
+        match parse_vote_field(key.as_ref()) {
+            Some((category_id_str, team_id_str)) => {
+                let category_points = match usize::from_str(category_id_str) {
+                    Ok(i) if i < categories.len() => &mut teams_points[i],
+                    _ => return Ok(Err(bad_request_for(user, "Invalid category."))),
+                };
                 match (i64::from_str(team_id_str), i64::from_str(value.as_ref())) {
                     (Ok(team_id), Ok(points)) => {
-                        teams_points.insert(team_id, points);
+                        category_points.insert(team_id, points);
                     }
                     (_, Err(..)) => {
-                        return Ok(bad_request("You need to enter a number for every team."))
+                        return Ok(Err(bad_request_for(
+                            user,
+                            "You need to enter a number for every team.",
+                        )))
                     }
-                    (Err(..), _) => return Ok(bad_request("Invalid team id.")),
+                    (Err(..), _) => return Ok(Err(bad_request_for(user, "Invalid team id."))),
                 }
             }
-            None => return Ok(bad_request("Unexpected form field.")),
+            None => return Ok(Err(bad_request_for(user, "Unexpected form field."))),
         }
     }
 
-    // Verify that the user is not spending more coins than allowed.
-    let coins_spent = match get_coins_spent(&teams_points) {
-        Some(t) => t,
-        None => return Ok(bad_request("Overflowing an i64? Nice try, but no.")),
+    Ok(Ok(VoteForm {
+        categories,
+        teams_points,
+    }))
+}
+
+pub fn handle_vote(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    body: &str,
+) -> db::Result<Response> {
+    let phase = crate::load_phase(tx)?;
+    if let Some(response) = check_can_vote(config, tx, user, phase)? {
+        return Ok(response);
+    }
+    let VoteForm {
+        categories,
+        mut teams_points,
+    } = match parse_vote_form(config, tx, user, phase, body)? {
+        Ok(form) => form,
+        Err(response) => return Ok(response),
     };
-    if coins_spent > config.app.coins_to_spend as i64 {
-        return Ok(bad_request(format!(
-            "You tried to spend {} coins, but you can spend at most {}.",
-            coins_spent, config.app.coins_to_spend,
-        )));
+
+    // Verify that the user is not spending more coins than allowed. Coins
+    // can't be moved between categories, so every category has its own limit.
+    for ((name, coins_to_spend), category_points) in categories.iter().zip(&teams_points) {
+        let coins_spent = match get_coins_spent(config, category_points) {
+            Some(t) => t,
+            None => return Ok(bad_request("Overflowing an i64? Nice try, but no.")),
+        };
+        if coins_spent > *coins_to_spend as i64 {
+            let in_category = match *name {
+                "" => String::new(),
+                name => format!(" on {name}"),
+            };
+            return Ok(bad_request(format!(
+                "You tried to spend {coins_spent} coins{in_category}, \
+                but you can spend at most {coins_to_spend}.",
+            )));
+        }
+        // Reject votes that give any team more points than the cap.
+        if let Some(cap) = config.app.max_points_per_team {
+            if category_points.values().any(|points| *points > cap as i64) {
+                return Ok(bad_request(format!(
+                    "You can give at most {cap} points to a single team."
+                )));
+            }
+        }
     }
 
     // If the user tries to vote for a team that they're a member of, reset back
     // to zero and add them to the hall of shame.
     let mut did_cheat = false;
     for team_id_opt in db::iter_member_teams(tx, &user.email)? {
-        if let Some(p) = teams_points.get_mut(&team_id_opt?) {
-            if *p != 0 {
-                *p = 0;
-                did_cheat = true;
+        let team_id = team_id_opt?;
+        for category_points in teams_points.iter_mut() {
+            if let Some(p) = category_points.get_mut(&team_id) {
+                if *p != 0 {
+                    *p = 0;
+                    did_cheat = true;
+                }
             }
         }
     }
     if did_cheat {
         db::set_cheater(tx, &user.email)?;
+        db::add_cheat_attempt(tx)?;
     }
 
     // Clear out any old votes, in case the user already voted previously.
-    db::delete_votes_for_voter(tx, &user.email)?;
+    // During judging, the judges vote separately from their crowd vote.
+    // Replace the previous vote as a whole, so teams that the user no longer
+    // gives points lose them. Write transactions are immediate, so when the
+    // user submits twice, the second submission waits for the first and then
+    // replaces it, and the inserts also replace rather than conflict.
+    let is_judging = matches!(phase, Phase::Judging);
+    if is_judging {
+        db::delete_judge_votes_for_judge(tx, &user.email)?;
+    } else {
+        db::delete_votes_for_voter(tx, &user.email)?;
+    }
 
-    for (team_id, points) in teams_points.iter() {
-        if *points == 0 {
-            // No need to pollute the database with zero votes that don't do
-            // anything.
-            continue;
+    for (category_id, category_points) in teams_points.iter().enumerate() {
+        for (team_id, points) in category_points.iter() {
+            if *points == 0 {
+                // No need to pollute the database with zero votes that don't do
+                // anything.
+                continue;
+            }
+            if *points < 0 {
+                return Ok(bad_request(
+                    "While the math works fine if we allow awarding negative points, \
+                    are you really that kind of person who chooses to spend their coins \
+                    on destroying somebody else’s reputation, \
+                    rather than just voting for a different team?",
+                ));
+            }
+            if is_judging {
+                db::insert_judge_vote(tx, &user.email, *team_id, *points)?;
+            } else {
+                db::insert_vote(tx, &user.email, *team_id, category_id as i64, *points)?;
+            }
         }
-        if *points < 0 {
-            return Ok(bad_request(
-                "While the math works fine if we allow awarding negative points, \
-                are you really that kind of person who chooses to spend their coins \
-                on destroying somebody else’s reputation, \
-                rather than just voting for a different team?",
+    }
+
+    let new_url = format!("{}#your-vote", config.server.prefix);
+    Ok(redirect_see_other(new_url.as_bytes()))
+}
+
+/// A category of a previewed vote, see `handle_vote_preview`.
+struct VotePreview<'a> {
+    /// The name of the category, empty if there are no categories.
+    name: &'a str,
+    coins_to_spend: u32,
+    coins_spent: i64,
+    /// The teams that the user gives points, with their name, the points, and the cost.
+    teams: Vec<(i64, String, i64, i64)>,
+    /// The reasons why `handle_vote` would reject or change the vote.
+    warnings: Vec<String>,
+}
+
+/// Show what a vote would cost, without recording it.
+///
+/// This takes the same form as `handle_vote`, the vote form has a button that
+/// submits here, for people without Javascript. Where `handle_vote` would
+/// reject the vote, or reset points, this explains why instead.
+pub fn handle_vote_preview(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+    body: &str,
+) -> db::Result<Response> {
+    let phase = crate::load_phase(tx)?;
+    if let Some(response) = check_can_vote(config, tx, user, phase)? {
+        return Ok(response);
+    }
+    let form = match parse_vote_form(config, tx, user, phase, body)? {
+        Ok(form) => form,
+        Err(response) => return Ok(response),
+    };
+    let own_teams = db::iter_member_teams(tx, &user.email)?.collect::<Result<Vec<_>, _>>()?;
+
+    let mut previews = Vec::with_capacity(form.categories.len());
+    for ((name, coins_to_spend), category_points) in form.categories.iter().zip(&form.teams_points)
+    {
+        // If the total does not overflow, then neither does the cost per team.
+        let coins_spent = match get_coins_spent(config, category_points) {
+            Some(t) => t,
+            None => return Ok(bad_request("Overflowing an i64? Nice try, but no.")),
+        };
+        let mut team_points: Vec<(i64, i64)> = category_points
+            .iter()
+            .filter(|(_team_id, points)| **points != 0)
+            .map(|(team_id, points)| (*team_id, *points))
+            .collect();
+        team_points.sort();
+
+        let mut teams = Vec::with_capacity(team_points.len());
+        let mut warnings = Vec::new();
+        for (team_id, points) in team_points {
+            let team_name = match db::get_team(tx, team_id)? {
+                Some(team) => team.name,
+                None => {
+                    warnings.push(format!("There is no team with id {team_id}."));
+                    continue;
+                }
+            };
+            if points < 0 {
+                warnings.push(format!(
+                    "You gave {team_name} {points} points, but you can’t give negative points."
+                ));
+            }
+            if let Some(cap) = config.app.max_points_per_team {
+                if points > cap as i64 {
+                    warnings.push(format!(
+                        "You gave {team_name} {points} points, \
+                        but you can give at most {cap} points to a single team."
+                    ));
+                }
+            }
+            if own_teams.contains(&team_id) {
+                let consequence = if config.app.public_cheaters {
+                    "and you end up in the hall of shame"
+                } else {
+                    "and the organizers are told"
+                };
+                warnings.push(format!(
+                    "You are a member of {team_name}, so you can’t vote for it. \
+                    If you submit this, its points are reset to zero, {consequence}."
+                ));
+            }
+            let cost = get_vote_cost(config, points).unwrap_or(0);
+            teams.push((team_id, team_name, points, cost));
+        }
+        if coins_spent > *coins_to_spend as i64 {
+            warnings.push(format!(
+                "You are spending {coins_spent} coins, \
+                but you can spend at most {coins_to_spend}."
             ));
         }
-        db::insert_vote(tx, &user.email, *team_id, *points)?;
+        previews.push(VotePreview {
+            name,
+            coins_to_spend: *coins_to_spend,
+            coins_spent,
+            teams,
+            warnings,
+        });
+    }
+
+    if user.wants_json {
+        return Ok(respond_json(format_vote_preview_json(&previews)));
+    }
+    Ok(respond_html(view_vote_preview(config, &previews)))
+}
+
+fn format_vote_preview_json(previews: &[VotePreview]) -> String {
+    let mut out = String::from("{\"categories\":[");
+    for (i, preview) in previews.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str(&format!(
+            "{{\"name\":{},\"coins_to_spend\":{},\"coins_spent\":{},\"coins_left\":{},\"teams\":[",
+            json::string(preview.name),
+            preview.coins_to_spend,
+            preview.coins_spent,
+            preview.coins_to_spend as i64 - preview.coins_spent,
+        ));
+        for (j, (team_id, team_name, points, cost)) in preview.teams.iter().enumerate() {
+            if j > 0 {
+                out.push(',');
+            }
+            out.push_str(&format!(
+                "{{\"team_id\":{team_id},\"team_name\":{},\"points\":{points},\"coins\":{}}}",
+                json::string(team_name),
+                cost,
+            ));
+        }
+        out.push_str("],\"warnings\":[");
+        for (j, warning) in preview.warnings.iter().enumerate() {
+            if j > 0 {
+                out.push(',');
+            }
+            out.push_str(&json::string(warning));
+        }
+        out.push_str("]}");
+    }
+    out.push_str("]}\n");
+    out
+}
+
+fn view_vote_preview(config: &Config, previews: &[VotePreview]) -> Markup {
+    let has_warnings = previews.iter().any(|p| !p.warnings.is_empty());
+    html! {
+        (view_html_head(config, "Vote Preview — Hack-o-matic"))
+        body {
+            h1 { "Vote Preview" }
+            p { "This is what your vote would look like. It is not submitted yet." }
+            @for (category_id, preview) in previews.iter().enumerate() {
+                @if !preview.name.is_empty() {
+                    h2 { (preview.name) }
+                }
+                @if preview.teams.is_empty() {
+                    p { "You don’t give any team points." }
+                } @else {
+                    ul {
+                        @for (team_id, team_name, points, cost) in &preview.teams {
+                            li {
+                                a href=(get_team_url(config, *team_id)) { (team_name) }
+                                ": " strong { (points) }
+                                " (" (view_coins(*cost)) ")"
+                            }
+                        }
+                    }
+                }
+                p {
+                    "You would spend " (view_coins(preview.coins_spent))
+                    " of your " (preview.coins_to_spend) ", "
+                    "so you would have "
+                    (view_coins(preview.coins_to_spend as i64 - preview.coins_spent))
+                    " left."
+                }
+                @if !preview.warnings.is_empty() {
+                    ul .notice {
+                        @for warning in &preview.warnings {
+                            li { (warning) }
+                        }
+                    }
+                }
+                // Keep the points, so the user can submit the vote from here.
+                @if !has_warnings {
+                    @for (team_id, _team_name, points, _cost) in &preview.teams {
+                        @let field = if preview.name.is_empty() {
+                            format!("team-{team_id}")
+                        } else {
+                            format!("category-{category_id}-team-{team_id}")
+                        };
+                        input type="hidden" form="vote-preview-form" name=(field) value=(points);
+                    }
+                }
+            }
+            @if has_warnings {
+                p {
+                    "Go back to change your vote, "
+                    "if you submit it like this, it is rejected or changed."
+                }
+            } @else {
+                form #vote-preview-form action=(format!("{}/vote", config.server.prefix)) method="post" {
+                    button type="submit" { "Submit Vote" }
+                }
+            }
+            p {
+                a href=(format!("{}#your-vote", config.server.prefix)) { "« Back to the vote" }
+            }
+        }
     }
+}
 
+pub fn handle_clear_vote(
+    config: &Config,
+    tx: &mut db::Transaction,
+    user: &User,
+) -> db::Result<Response> {
+    let phase = crate::load_phase(tx)?;
+    if !matches!(phase, Phase::Evaluation) || is_past_evaluation_deadline(config) {
+        return Ok(forbidden(
+            "Voting is closed, you can’t change your vote anymore.",
+        ));
+    }
+    db::delete_votes_for_voter(tx, &user.email)?;
     let new_url = format!("{}#your-vote", config.server.prefix);
     Ok(redirect_see_other(new_url.as_bytes()))
 }
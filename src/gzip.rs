@@ -0,0 +1,220 @@
+// Hack-o-matic -- A webapp for facilitating remote and on-site hackathons
+// Copyright 2024 Chorus One
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! A small gzip encoder, to compress responses without another dependency.
+//!
+//! This is DEFLATE with greedy LZ77 matching and only the fixed Huffman codes
+//! (RFC 1951, section 3.2.6), in a gzip container (RFC 1952). It compresses
+//! less than zlib, but our pages are repetitive HTML, and most of the gain is
+//! in the back-references, which we do find.
+
+/// The base lengths for length symbols 257 through 285.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// The base distances for distance symbols 0 through 29.
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const WINDOW_SIZE: usize = 32 * 1024;
+
+/// How many earlier positions with the same hash to try, more is slower.
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+const CRC32_TABLE: [u32; 256] = make_crc32_table();
+
+const fn make_crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut c = 0xffff_ffff;
+    for &byte in data {
+        c = CRC32_TABLE[((c ^ byte as u32) & 0xff) as usize] ^ (c >> 8);
+    }
+    c ^ 0xffff_ffff
+}
+
+/// Writes bits least significant bit first, as DEFLATE wants.
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u64,
+    n_bits: u32,
+}
+
+impl BitWriter {
+    fn write_bits(&mut self, value: u32, n_bits: u32) {
+        self.buffer |= (value as u64) << self.n_bits;
+        self.n_bits += n_bits;
+        while self.n_bits >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.n_bits -= 8;
+        }
+    }
+
+    /// Huffman codes are defined most significant bit first, so reverse them.
+    fn write_code(&mut self, code: u32, n_bits: u32) {
+        self.write_bits(code.reverse_bits() >> (32 - n_bits), n_bits);
+    }
+
+    fn flush(&mut self) {
+        if self.n_bits > 0 {
+            self.out.push(self.buffer as u8);
+            self.buffer = 0;
+            self.n_bits = 0;
+        }
+    }
+
+    /// Write a literal/length symbol with the fixed Huffman code.
+    fn write_symbol(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xc0 + symbol - 280, 8),
+        }
+    }
+
+    fn write_match(&mut self, length: usize, distance: usize) {
+        // The tables are sorted, so the symbol is the last base that fits.
+        let i = LENGTH_BASE
+            .iter()
+            .rposition(|&b| b as usize <= length)
+            .unwrap();
+        self.write_symbol(257 + i as u32);
+        let extra = LENGTH_EXTRA_BITS[i] as u32;
+        self.write_bits((length - LENGTH_BASE[i] as usize) as u32, extra);
+
+        let j = DISTANCE_BASE
+            .iter()
+            .rposition(|&b| b as usize <= distance)
+            .unwrap();
+        self.write_code(j as u32, 5);
+        let extra = DISTANCE_EXTRA_BITS[j] as u32;
+        self.write_bits((distance - DISTANCE_BASE[j] as usize) as u32, extra);
+    }
+}
+
+fn hash3(data: &[u8]) -> usize {
+    let v = (data[0] as u32) << 16 | (data[1] as u32) << 8 | data[2] as u32;
+    (v.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Compress the data as a single DEFLATE block with the fixed Huffman codes.
+fn deflate(data: &[u8], out: &mut BitWriter) {
+    // Final block, fixed Huffman codes.
+    out.write_bits(1, 1);
+    out.write_bits(1, 2);
+
+    // For every hash, the most recent position plus one, zero if there is
+    // none. For every position, the previous position with the same hash.
+    let mut head = vec![0_u32; 1 << HASH_BITS];
+    let mut prev = vec![0_u32; data.len()];
+    let insert = |head: &mut [u32], prev: &mut [u32], pos: usize| {
+        if pos + MIN_MATCH <= data.len() {
+            let h = hash3(&data[pos..]);
+            prev[pos] = head[h];
+            head[h] = pos as u32 + 1;
+        }
+    };
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut best_len = 0;
+        let mut best_distance = 0;
+        if pos + MIN_MATCH <= data.len() {
+            let max_len = MAX_MATCH.min(data.len() - pos);
+            let mut candidate = head[hash3(&data[pos..])];
+            let mut n_tried = 0;
+            while candidate > 0 && n_tried < MAX_CHAIN {
+                let start = candidate as usize - 1;
+                if pos - start > WINDOW_SIZE {
+                    break;
+                }
+                let len = data[start..]
+                    .iter()
+                    .zip(&data[pos..pos + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best_len {
+                    best_len = len;
+                    best_distance = pos - start;
+                    if len == max_len {
+                        break;
+                    }
+                }
+                candidate = prev[start];
+                n_tried += 1;
+            }
+        }
+
+        if best_len >= MIN_MATCH {
+            out.write_match(best_len, best_distance);
+            for p in pos..pos + best_len {
+                insert(&mut head, &mut prev, p);
+            }
+            pos += best_len;
+        } else {
+            out.write_symbol(data[pos] as u32);
+            insert(&mut head, &mut prev, pos);
+            pos += 1;
+        }
+    }
+
+    out.write_symbol(256);
+    out.flush();
+}
+
+/// Compress the data into the gzip format.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = BitWriter {
+        out: Vec::with_capacity(data.len() / 4 + 32),
+        buffer: 0,
+        n_bits: 0,
+    };
+    // Magic, deflate, no flags, no modification time, no extra flags, and
+    // operating system "unknown".
+    out.out
+        .extend_from_slice(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff]);
+    deflate(data, &mut out);
+    out.out.extend_from_slice(&crc32(data).to_le_bytes());
+    // The size is modulo 2^32 by definition.
+    out.out
+        .extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.out
+}
@@ -0,0 +1,88 @@
+// Hack-o-matic -- A webapp for facilitating remote and on-site hackathons
+// Copyright 2024 Chorus One
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! SipHash, for when we need control over the key or a stable output.
+//!
+//! The standard library uses SipHash for its hash maps, but it does not expose
+//! a non-deprecated way to set the key, and it does not promise that its hash
+//! function stays the same across Rust versions, so we implement it here.
+
+struct State {
+    v0: u64,
+    v1: u64,
+    v2: u64,
+    v3: u64,
+}
+
+impl State {
+    fn round(&mut self) {
+        self.v0 = self.v0.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(13);
+        self.v1 ^= self.v0;
+        self.v0 = self.v0.rotate_left(32);
+        self.v2 = self.v2.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(16);
+        self.v3 ^= self.v2;
+        self.v0 = self.v0.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(21);
+        self.v3 ^= self.v0;
+        self.v2 = self.v2.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(17);
+        self.v1 ^= self.v2;
+        self.v2 = self.v2.rotate_left(32);
+    }
+
+    fn compress(&mut self, c_rounds: usize, m: u64) {
+        self.v3 ^= m;
+        for _ in 0..c_rounds {
+            self.round();
+        }
+        self.v0 ^= m;
+    }
+}
+
+/// SipHash with `c_rounds` rounds per message block and `d_rounds` finalization rounds.
+fn siphash(c_rounds: usize, d_rounds: usize, k0: u64, k1: u64, data: &[u8]) -> u64 {
+    let mut state = State {
+        v0: k0 ^ 0x736f_6d65_7073_6575,
+        v1: k1 ^ 0x646f_7261_6e64_6f6d,
+        v2: k0 ^ 0x6c79_6765_6e65_7261,
+        v3: k1 ^ 0x7465_6462_7974_6573,
+    };
+
+    let mut blocks = data.chunks_exact(8);
+    for block in &mut blocks {
+        let m = u64::from_le_bytes(block.try_into().expect("Chunks have 8 bytes."));
+        state.compress(c_rounds, m);
+    }
+
+    // The final block holds the remaining bytes, and the length in the top byte.
+    let mut last = (data.len() as u64) << 56;
+    for (i, byte) in blocks.remainder().iter().enumerate() {
+        last |= (*byte as u64) << (8 * i);
+    }
+    state.compress(c_rounds, last);
+
+    state.v2 ^= 0xff;
+    for _ in 0..d_rounds {
+        state.round();
+    }
+    state.v0 ^ state.v1 ^ state.v2 ^ state.v3
+}
+
+/// SipHash-2-4, the variant recommended for use as a MAC.
+pub fn siphash24(k0: u64, k1: u64, data: &[u8]) -> u64 {
+    siphash(2, 4, k0, k1, data)
+}
+
+/// SipHash-1-3, the variant that `DefaultHasher` uses at the time of writing.
+///
+/// With both keys zero, this matches `DefaultHasher::new()` that gets written
+/// the same bytes, but unlike that, it is guaranteed not to change.
+pub fn siphash13(k0: u64, k1: u64, data: &[u8]) -> u64 {
+    siphash(1, 3, k0, k1, data)
+}
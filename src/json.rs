@@ -0,0 +1,29 @@
+// Hack-o-matic -- A webapp for facilitating remote and on-site hackathons
+// Copyright 2024 Chorus One
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Just enough JSON to produce simple responses, without pulling in a crate.
+
+use std::fmt::Write;
+
+/// Format the string as a JSON string literal, including the quotes.
+pub fn string(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            ch if ch < '\u{20}' => write!(result, "\\u{:04x}", ch as u32).unwrap(),
+            ch => result.push(ch),
+        }
+    }
+    result.push('"');
+    result
+}
@@ -5,29 +5,81 @@
 // you may not use this file except in compliance with the License.
 // A copy of the License has been included in the root of the repository.
 
-use std::io::Cursor;
+use std::collections::hash_map::RandomState;
+use std::error::Error;
+use std::fs::Permissions;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{Cursor, ErrorKind, Read};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use tiny_http::{HeaderField, Method, Request, Server};
+use tiny_http::{Header, HeaderField, Method, Request, Server};
 
-use config::Config;
+use config::{Config, LogFormat};
 use database as db;
-use endpoints::{internal_error, not_found, service_unavailable};
+use endpoints::{
+    bad_request, forbidden, internal_error, not_found, payload_too_large, service_unavailable,
+    too_many_requests,
+};
+use metrics::Metrics;
+use rate_limit::RateLimiter;
 
 mod config;
 mod database;
+mod db_ext;
 mod endpoints;
+mod gzip;
+mod hash;
+mod json;
+mod metrics;
+mod rate_limit;
 
 type Response = tiny_http::Response<Cursor<Vec<u8>>>;
 
+/// The number of requests received, used to generate request ids.
+static REQUEST_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// The number of requests that took longer than `slow_request_ms` to handle.
+static SLOW_REQUEST_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Responses smaller than this are not worth compressing, see `compress_response`.
+const MIN_COMPRESS_LEN: usize = 1024;
+
+/// How long a connection waits for a lock before it fails with "database is locked".
+///
+/// Transactions are short, so when the timeout expires, something is wrong, but
+/// on a busy day there can be a queue of writers that each take a few ms. This
+/// is the default, see `busy_timeout_ms` in the config.
+const BUSY_TIMEOUT_MS: u32 = 5_000;
+
+/// How many times to retry a locked transaction by default, see `max_retries`.
+const MAX_RETRIES: u32 = 5;
+
+/// The largest request body that we accept by default, see `max_body_bytes`.
+const MAX_BODY_BYTES: u64 = 64 * 1024;
+
+/// Exit code when the listen address is already in use by another process.
+const EXIT_ADDR_IN_USE: i32 = 98;
+
+/// Exit code when we fail to listen on the address for any other reason.
+const EXIT_BIND_FAILED: i32 = 99;
+
+/// Exit code when one of the servers stops receiving requests.
+const EXIT_SERVER_STOPPED: i32 = 1;
+
 #[derive(Copy, Clone, PartialEq, Eq)]
 enum Phase {
     Registration,
     Presentation,
     Evaluation,
+    Judging,
     Revelation,
     Celebration,
 }
@@ -38,6 +90,7 @@ impl Phase {
             "registration" => Phase::Registration,
             "presentation" => Phase::Presentation,
             "evaluation" => Phase::Evaluation,
+            "judging" => Phase::Judging,
             "revelation" => Phase::Revelation,
             "celebration" => Phase::Celebration,
             _ => return None,
@@ -45,11 +98,12 @@ impl Phase {
         Some(result)
     }
 
-    fn to_str(&self) -> &'static str {
+    fn to_str(self) -> &'static str {
         match self {
             Phase::Registration => "registration",
             Phase::Presentation => "presentation",
             Phase::Evaluation => "evaluation",
+            Phase::Judging => "judging",
             Phase::Revelation => "revelation",
             Phase::Celebration => "celebration",
         }
@@ -60,7 +114,8 @@ impl Phase {
             Phase::Registration => Phase::Registration,
             Phase::Presentation => Phase::Registration,
             Phase::Evaluation => Phase::Presentation,
-            Phase::Revelation => Phase::Evaluation,
+            Phase::Judging => Phase::Evaluation,
+            Phase::Revelation => Phase::Judging,
             Phase::Celebration => Phase::Revelation,
         }
     }
@@ -69,7 +124,8 @@ impl Phase {
         match self {
             Phase::Registration => Phase::Presentation,
             Phase::Presentation => Phase::Evaluation,
-            Phase::Evaluation => Phase::Revelation,
+            Phase::Evaluation => Phase::Judging,
+            Phase::Judging => Phase::Revelation,
             Phase::Revelation => Phase::Celebration,
             Phase::Celebration => Phase::Celebration,
         }
@@ -99,30 +155,265 @@ fn load_config() -> Config {
         Err(err) => panic!("Failed to read {config_fname:?}: {err:?}"),
     };
 
-    match toml::from_str(&config_toml) {
+    let config: Config = match toml::from_str(&config_toml) {
         Ok(config) => config,
         Err(err) => panic!("Failed to parse {config_fname:?}: {err:?}"),
+    };
+
+    // SQLite would happily create a file named after the url.
+    if config.database.path.contains("://") {
+        panic!(
+            "Failed to parse {config_fname:?}: database.path must be the path of \
+            an SQLite file, other databases are not supported, got {:?}.",
+            config.database.path
+        );
+    }
+
+    if let Some(deadline) = &config.app.evaluation_deadline {
+        if endpoints::get_unix_time_from_datetime(deadline).is_none() {
+            panic!(
+                "Failed to parse {config_fname:?}: evaluation_deadline must have \
+                a date, time, and offset, e.g. 2024-05-17T18:00:00+02:00, got {deadline}."
+            );
+        }
+    }
+
+    if config.server.listen.addresses().is_empty() {
+        panic!("Failed to parse {config_fname:?}: server.listen must have at least one address.");
+    }
+
+    if config.database.checkpoint_interval_secs == Some(0) {
+        panic!(
+            "Failed to parse {config_fname:?}: database.checkpoint_interval_secs \
+            must be at least 1, leave it out to not checkpoint periodically."
+        );
+    }
+
+    if config.app.max_points_per_team == Some(0) {
+        panic!(
+            "Failed to parse {config_fname:?}: app.max_points_per_team \
+            must be at least 1, leave it out to not cap the points per team."
+        );
+    }
+
+    for (first, last) in config.app.allowed_unicode_blocks.iter().flatten() {
+        if first > last || *last > 0x10ffff {
+            panic!(
+                "Failed to parse {config_fname:?}: allowed_unicode_blocks must contain \
+                ranges [first, last] of code points, got [{first:#x}, {last:#x}]."
+            );
+        }
+    }
+
+    config
+}
+
+/// Check the evaluation deadline periodically, and close the vote once it passes.
+///
+/// The check runs on its own connection, because connections can't be shared
+/// between threads. If we hit a database error, we open a fresh connection.
+fn advance_phase_at_deadline(config: &Config, init_mutex: &Mutex<()>) {
+    loop {
+        // See the request handler threads for why we need the mutex.
+        let db_lock = init_mutex.lock().unwrap();
+        let raw_connection = sqlite::open(&config.database.path).expect("Failed to open database");
+        let mut connection =
+            init_database(config, &raw_connection).expect("Failed to initialize database.");
+        std::mem::drop(db_lock);
+
+        // Sleep first, so a persistent error does not make us spin.
+        loop {
+            thread::sleep(Duration::from_secs(30));
+            let result =
+                db_ext::begin_immediate(&raw_connection, &mut connection).and_then(|mut tx| {
+                    endpoints::advance_past_evaluation_deadline(config, &mut tx)?;
+                    tx.commit()
+                });
+            if let Err(err) = result {
+                println!("Failed to check the evaluation deadline: {err:?}");
+                break;
+            }
+        }
+    }
+}
+
+/// Checkpoint the write-ahead log periodically, and truncate it to zero bytes.
+///
+/// Like `advance_phase_at_deadline`, this runs on its own connection, and if we
+/// hit a database error, we open a fresh connection.
+fn checkpoint_periodically(config: &Config, init_mutex: &Mutex<()>, interval_secs: u32) {
+    loop {
+        // See the request handler threads for why we need the mutex.
+        let db_lock = init_mutex.lock().unwrap();
+        let raw_connection = sqlite::open(&config.database.path).expect("Failed to open database");
+        init_database(config, &raw_connection).expect("Failed to initialize database.");
+        std::mem::drop(db_lock);
+
+        loop {
+            thread::sleep(Duration::from_secs(interval_secs as u64));
+            match checkpoint(&raw_connection) {
+                Ok((false, n_pages, n_moved)) => {
+                    println!("Checkpointed the WAL, moved {n_moved} of {n_pages} pages.")
+                }
+                // Busy means that a reader or writer held on for longer than
+                // the busy timeout, we'll try again next time.
+                Ok((true, n_pages, n_moved)) => println!(
+                    "Could not complete the WAL checkpoint, the database is busy. \
+                    Moved {n_moved} of {n_pages} pages."
+                ),
+                Err(err) => {
+                    println!("Failed to checkpoint the WAL: {err:?}");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Run a checkpoint, return whether it was blocked, the number of pages in the
+/// log, and the number of pages moved into the database.
+fn checkpoint(raw_connection: &sqlite::Connection) -> db::Result<(bool, i64, i64)> {
+    let run = |mode: &str| -> db::Result<(bool, i64, i64)> {
+        let mut statement = raw_connection.prepare(format!("PRAGMA wal_checkpoint({mode});"))?;
+        statement.next()?;
+        let is_busy = statement.read::<i64>(0)? != 0;
+        Ok((is_busy, statement.read(1)?, statement.read(2)?))
+    };
+    // A truncating checkpoint that completes reports an empty log, so we move
+    // the pages first to learn the counts, and then truncate.
+    let (_, n_pages, n_moved) = run("PASSIVE")?;
+    let (is_busy, _, _) = run("TRUNCATE")?;
+    Ok((is_busy, n_pages, n_moved))
+}
+
+/// Replace all email addresses with pseudonyms if the retention period is over.
+fn redact_expired_emails(config: &Config, tx: &mut db::Transaction) -> db::Result<()> {
+    let days = match config.database.retention_days {
+        Some(days) => days,
+        None => return Ok(()),
+    };
+    if db::is_celebrating_for_days(tx, days as i64)? == 0 {
+        return Ok(());
+    }
+
+    // The pseudonym is a hash of the email address with a random key that we
+    // forget afterwards, so it cannot be reversed. Within this run the same
+    // address maps to the same pseudonym, so the distinct counts are preserved.
+    let hash_state = RandomState::new();
+    let emails = db::iter_unredacted_emails(tx)?.collect::<Result<Vec<_>, _>>()?;
+    for email in &emails {
+        let mut hasher = hash_state.build_hasher();
+        hasher.write(email.as_bytes());
+        let pseudonym = format!("redacted-{:016x}", hasher.finish());
+        db::redact_email(tx, email, &pseudonym)?;
+    }
+
+    if !emails.is_empty() {
+        println!(
+            "Retention period of {days} days is over, redacted {} email addresses.",
+            emails.len()
+        );
+    }
+    Ok(())
+}
+
+/// Add the columns that were added after the table was first created.
+///
+/// `ensure_schema_exists` creates tables with all of their columns, but it does
+/// not touch tables that exist already, so databases created by an older
+/// version need the new columns added.
+fn migrate_schema(tx: &mut db::Transaction) -> db::Result<()> {
+    if db::has_column(tx, "teams", "project_url")? == 0 {
+        println!("Adding column teams.project_url.");
+        db::add_teams_project_url(tx)?;
+    }
+    if db::has_column(tx, "votes", "category_id")? == 0 {
+        println!("Adding column votes.category_id.");
+        db::migrate_votes_add_category_id(tx)?;
+    }
+    if db::has_column(tx, "votes", "created_at")? == 0 {
+        println!("Adding column votes.created_at.");
+        db::add_votes_created_at(tx)?;
+    }
+    if db::has_column(tx, "team_memberships", "is_captain")? == 0 {
+        println!("Adding column team_memberships.is_captain.");
+        db::add_team_memberships_is_captain(tx)?;
+    }
+    if db::has_index(tx, "teams_name_lower")? == 0 {
+        let collisions = db::iter_case_colliding_team_names(tx)?.collect::<Result<Vec<_>, _>>()?;
+        if !collisions.is_empty() {
+            // We can't pick which team to rename, that's up to the organizers.
+            panic!(
+                "Team names must be unique regardless of case, but these teams \
+                differ only in case: {}. Rename them in the database and restart.",
+                collisions.join("; ")
+            );
+        }
+        println!("Adding index teams_name_lower.");
+        db::add_teams_name_lower_index(tx)?;
     }
+    Ok(())
 }
 
-fn init_database(raw_connection: &sqlite::Connection) -> db::Result<db::Connection> {
+/// Store the initial value of settings that the admin can change at runtime.
+///
+/// This only happens on the first run, afterwards the database has the value
+/// that the admin set, and the config is no longer used.
+fn seed_settings(config: &Config, tx: &mut db::Transaction) -> db::Result<()> {
+    if db::get_setting(tx, "coins_to_spend")?.is_none() {
+        let coins_to_spend = config.app.coins_to_spend.to_string();
+        db::set_setting(tx, "coins_to_spend", &coins_to_spend)?;
+    }
+    // Unlike the above, this is not from the config, but it is per event.
+    if db::get_setting(tx, "dataset_salt")?.is_none() {
+        db::set_setting(tx, "dataset_salt", &endpoints::new_dataset_salt())?;
+    }
+    Ok(())
+}
+
+fn init_database<'a>(
+    config: &Config,
+    raw_connection: &'a sqlite::Connection,
+) -> db::Result<db::Connection<'a>> {
     // Change the database to WAL mode if it wasn't already. Set the busy
-    // timeout to 30 milliseconds, so readers and writers can wait for each
-    // other a little bit. We also have a retry loop around the request handler.
+    // timeout, so writers can wait for each other to finish. We also have a
+    // retry loop around the request handler.
     raw_connection.execute("PRAGMA locking_mode = NORMAL;")?;
-    raw_connection.execute("PRAGMA busy_timeout = 30;")?;
+    let busy_timeout_ms = config.database.busy_timeout_ms.unwrap_or(BUSY_TIMEOUT_MS);
+    raw_connection.execute(format!("PRAGMA busy_timeout = {busy_timeout_ms};"))?;
     raw_connection.execute("PRAGMA journal_mode = WAL;")?;
     raw_connection.execute("PRAGMA foreign_keys = TRUE;")?;
     let mut connection = db::Connection::new(raw_connection);
     let mut tx = connection.begin()?;
     db::ensure_schema_exists(&mut tx)?;
+    migrate_schema(&mut tx)?;
+    seed_settings(config, &mut tx)?;
+    redact_expired_emails(config, &mut tx)?;
     tx.commit()?;
     Ok(connection)
 }
 
+/// Prepare a read-only connection for serving GET requests.
+///
+/// This assumes that the writer already initialized the database.
+fn init_reader_connection(
+    config: &Config,
+    raw_connection: &mut sqlite::Connection,
+) -> db::Result<()> {
+    // Readers don't block each other in WAL mode, they only need to wait when
+    // the writer is checkpointing.
+    let busy_timeout_ms = config.database.busy_timeout_ms.unwrap_or(BUSY_TIMEOUT_MS);
+    raw_connection.set_busy_timeout(busy_timeout_ms as usize)?;
+    raw_connection.execute("PRAGMA query_only = TRUE;")
+}
+
 pub struct User {
     email: String,
     is_admin: bool,
+    /// Whether the user is one of the judges, see `judge_emails`.
+    is_judge: bool,
+    /// Whether the client prefers JSON over html, per its `Accept` header.
+    wants_json: bool,
 }
 
 impl User {
@@ -139,14 +430,174 @@ impl User {
             _ => false,
         }
     }
+
+    /// Whether the user can vote in this phase.
+    ///
+    /// Everybody votes during the evaluation, only judges vote during judging.
+    fn can_vote(&self, phase: Phase) -> bool {
+        match phase {
+            Phase::Evaluation => true,
+            Phase::Judging => self.is_judge,
+            _ => false,
+        }
+    }
+}
+
+/// The fields of the access log entry for a request.
+struct RequestLog {
+    method: Method,
+
+    /// The url, without the query string, unless the query is safe to log.
+    url: String,
+
+    /// Whether the request was authorized by a token rather than an email.
+    has_token: bool,
+
+    /// The email of the user, if we got as far as authenticating them.
+    email: Option<String>,
+
+    /// The participant that the admin is looking as, see `?as=` below.
+    impersonating: Option<String>,
+}
+
+impl RequestLog {
+    fn new(request: &Request) -> Self {
+        let url = request.url();
+        Self {
+            method: request.method().clone(),
+            url: url
+                .split_once('?')
+                .map_or(url, |(path, _query)| path)
+                .to_string(),
+            has_token: false,
+            email: None,
+            impersonating: None,
+        }
+    }
+
+    /// Format the log entry as a line of text or JSON, depending on the config.
+    fn format(
+        &self,
+        config: &Config,
+        request_id: &str,
+        status: u16,
+        duration: Duration,
+        error: Option<&str>,
+    ) -> String {
+        let duration_ms = (duration.as_micros() as f32) * 1e-3;
+        match config.server.log_format {
+            LogFormat::Text => {
+                let mut line = format!("{request_id} {:4?} {}", self.method, self.url);
+                if self.has_token {
+                    line.push_str(" (token)");
+                }
+                if let Some(email) = &self.email {
+                    line.push(' ');
+                    line.push_str(email);
+                }
+                if let Some(other) = &self.impersonating {
+                    line.push_str(" as ");
+                    line.push_str(other);
+                }
+                match error {
+                    Some(err) => line.push_str(&format!(" -> Error: {err}")),
+                    None => line.push_str(&format!(" -> {status} [{duration_ms:.3} ms]")),
+                }
+                line
+            }
+            LogFormat::Json => {
+                let email = match &self.email {
+                    Some(email) => json::string(email),
+                    None => "null".to_string(),
+                };
+                let mut line = format!(
+                    r#"{{"request_id":{},"method":{},"url":{},"email":{},"status":{},"duration_ms":{:.3}"#,
+                    json::string(request_id),
+                    json::string(self.method.as_str()),
+                    json::string(&self.url),
+                    email,
+                    status,
+                    duration_ms,
+                );
+                if let Some(other) = &self.impersonating {
+                    line.push_str(&format!(r#","impersonating":{}"#, json::string(other)));
+                }
+                if let Some(err) = error {
+                    line.push_str(&format!(r#","error":{}"#, json::string(err)));
+                }
+                line.push('}');
+                line
+            }
+        }
+    }
 }
 
 fn handle_request(
     config: &Config,
+    raw_connection: &sqlite::Connection,
     connection: &mut db::Connection,
+    rate_limiter: Option<&RateLimiter>,
+    metrics: Option<&Metrics>,
     request: &mut Request,
-    log_line: &mut String,
+    log: &mut RequestLog,
 ) -> db::Result<Response> {
+    let (path, query) = match request.url().split_once('?') {
+        Some((path, query)) => (path.to_string(), query.to_string()),
+        None => (request.url().to_string(), String::new()),
+    };
+
+    // Crawlers don't log in either. They look for robots.txt at the root, but
+    // if the proxy puts us under a prefix, we also serve it there.
+    if path == "/robots.txt" || path == format!("{}/robots.txt", config.server.prefix) {
+        return Ok(endpoints::handle_robots_txt(config));
+    }
+
+    // The stylesheet contains nothing secret, so like robots.txt, we serve it
+    // without looking at who is asking.
+    if request.method() == &Method::Get && path == format!("{}/style.css", config.server.prefix) {
+        let if_none_match = request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("If-None-Match"))
+            .map(|h| h.value.as_str());
+        return Ok(endpoints::handle_stylesheet(if_none_match));
+    }
+
+    // The shared results page is for people who can't log in, the token in the
+    // query string authorizes them instead. Don't log the query, it is secret.
+    if request.method() == &Method::Get && path == format!("{}/results", config.server.prefix) {
+        log.has_token = true;
+        return with_transaction(config, raw_connection, connection, false, |tx| {
+            endpoints::handle_shared_results(config, tx, &query)
+        });
+    }
+
+    // Prometheus does not log in either. It usually scrapes the server
+    // directly, but through the proxy, the path has the prefix.
+    let is_metrics_path = path == "/metrics" || path == format!("{}/metrics", config.server.prefix);
+    if let (Method::Get, Some(metrics), true) = (request.method(), metrics, is_metrics_path) {
+        let is_allowed = match (&config.server.metrics_allowed_ips, request.remote_addr()) {
+            (None, _) => true,
+            (Some(ips), Some(addr)) => ips.contains(&addr.ip()),
+            (Some(_), None) => false,
+        };
+        if !is_allowed {
+            return Ok(forbidden(
+                "Your address is not allowed to read the metrics.",
+            ));
+        }
+        return with_transaction(config, raw_connection, connection, false, |tx| {
+            endpoints::handle_metrics(tx, metrics)
+        });
+    }
+
+    // The public standings are for a screen that can't log in, if enabled.
+    if request.method() == &Method::Get && path == format!("{}/public", config.server.prefix) {
+        return with_transaction(config, raw_connection, connection, false, |tx| {
+            endpoints::handle_public_results(config, tx)
+        });
+    }
+
     // Figure out who the user is. In debug mode we fall back to a default.
     let header_x_email = HeaderField::from_str("X-Email").unwrap();
     let mut email = None;
@@ -170,14 +621,59 @@ fn handle_request(
         },
     };
 
-    *log_line = format!("{:4?} {} {}", request.method(), request.url(), email);
+    if let Err(msg) = endpoints::validate_email(&email) {
+        return Ok(
+            Response::from_string(format!("Invalid authentication header. {msg}"))
+                .with_status_code(401),
+        );
+    }
+
+    log.url = request.url().to_string();
+    log.email = Some(email.clone());
+
+    // When somebody reports a problem, the admin can look at the page as
+    // them with `?as=email`. This is only for looking, the admin can't act
+    // on behalf of somebody else, so we only allow it for GET requests.
+    let impersonate = form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _value)| key == "as")
+        .map(|(_key, value)| value.into_owned());
+    let email = match impersonate {
+        None => email,
+        Some(_) if email != config.app.admin_email => {
+            return Ok(forbidden(
+                "Only the admin can view the page as somebody else.",
+            ));
+        }
+        Some(_) if request.method() != &Method::Get => {
+            return Ok(forbidden(
+                "Viewing the page as somebody else is read-only, you can't submit forms.",
+            ));
+        }
+        Some(other) => {
+            if let Err(msg) = endpoints::validate_email(&other) {
+                return Ok(bad_request(format!("Invalid email to view as. {msg}")));
+            }
+            log.impersonating = Some(other.clone());
+            other
+        }
+    };
+
+    // We don't do full content negotiation, a client that lists JSON at all
+    // is an API client, browsers don't.
+    let header_accept = HeaderField::from_str("Accept").unwrap();
+    let wants_json = request
+        .headers()
+        .iter()
+        .any(|h| h.field == header_accept && h.value.as_str().contains("application/json"));
 
     let user = User {
         is_admin: email == config.app.admin_email,
+        is_judge: config.app.judge_emails.contains(&email),
         email,
+        wants_json,
     };
 
-    let url_inner = match request.url().strip_prefix(&config.server.prefix) {
+    let url_inner = match path.strip_prefix(&config.server.prefix) {
         Some(url) => url.to_string(),
         None => {
             return Ok(not_found(format!(
@@ -187,34 +683,96 @@ fn handle_request(
         }
     };
 
+    // Only changes are limited, reloading the page is harmless.
+    if let (Method::Post, Some(limiter)) = (request.method(), rate_limiter) {
+        if !limiter.try_acquire(&user.email) {
+            return Ok(too_many_requests(
+                "You are submitting forms too quickly, wait a minute and try again.",
+            ));
+        }
+    }
+
     // For post requests, read the body. We need to do this once. The handler
     // may be retried, but the body we can only consume once.
     let mut body = String::new();
     if request.method() == &Method::Post {
+        // Don't let a client make us buffer an arbitrary amount of data. When
+        // the client announces the length, we can reject it without reading,
+        // otherwise we read one byte past the limit to find out.
+        let max_body_bytes = config.server.max_body_bytes.unwrap_or(MAX_BODY_BYTES);
+        let too_large =
+            || payload_too_large(format!("The request body exceeds {max_body_bytes} bytes."));
+        if request
+            .body_length()
+            .is_some_and(|n| n as u64 > max_body_bytes)
+        {
+            return Ok(too_large());
+        }
         // Read the body, ignore any IO errors there. In most cases this is
         // probably fine and we'll fail elsewhere, but it might happen that
         // we read a truncated body and fail half-way.
-        if let Err(_) = request.as_reader().read_to_string(&mut body) {
+        let mut reader = request.as_reader().take(max_body_bytes + 1);
+        if reader.read_to_string(&mut body).is_err() {
             return Ok(internal_error("Failed to read full request body."));
         }
+        if body.len() as u64 > max_body_bytes {
+            return Ok(too_large());
+        }
     }
 
-    with_transaction(connection, |tx| {
-        if request.method() == &Method::Post {
+    let is_write = request.method() == &Method::Post;
+    with_transaction(config, raw_connection, connection, is_write, |tx| {
+        if is_write {
             match url_inner.as_ref() {
                 "/create-team" => endpoints::handle_create_team(config, tx, &user, &body),
+                "/edit-team" => endpoints::handle_edit_team(config, tx, &user, &body),
                 "/delete-team" => endpoints::handle_delete_team(config, tx, &user, &body),
                 "/leave-team" => endpoints::handle_leave_team(config, tx, &user, &body),
+                "/set-captain" => endpoints::handle_set_captain(config, tx, &user, &body),
                 "/join-team" => endpoints::handle_join_team(config, tx, &user, &body),
                 "/vote" => endpoints::handle_vote(config, tx, &user, &body),
-                "/prev" => endpoints::handle_phase_prev(config, tx, &user),
-                "/next" => endpoints::handle_phase_next(config, tx, &user),
+                "/vote-preview" => endpoints::handle_vote_preview(config, tx, &user, &body),
+                "/clear-vote" => endpoints::handle_clear_vote(config, tx, &user),
+                "/mark-presented" => endpoints::handle_mark_presented(config, tx, &user, &body),
+                "/favorite" => endpoints::handle_favorite(config, tx, &user, &body),
+                "/unfavorite" => endpoints::handle_unfavorite(config, tx, &user, &body),
+                "/unmark-presented" => endpoints::handle_unmark_presented(config, tx, &user, &body),
+                "/banner" => endpoints::handle_set_banner(config, tx, &user, &body),
+                "/registration-lock" => {
+                    endpoints::handle_set_registration_lock(config, tx, &user, &body)
+                }
+                "/import-participants" => {
+                    endpoints::handle_import_participants(config, tx, &user, &body)
+                }
+                "/coins" => endpoints::handle_set_coins_to_spend(config, tx, &user, &body),
+                "/finalize" => endpoints::handle_finalize(config, tx, &user),
+                "/unfinalize" => endpoints::handle_unfinalize(config, tx, &user),
+                "/feedback" => endpoints::handle_event_feedback(config, tx, &user, &body),
+                "/team-feedback" => endpoints::handle_team_feedback(config, tx, &user, &body),
+                "/integrity" => endpoints::handle_integrity_cleanup(config, tx, &user),
+                "/prev" => endpoints::handle_phase_prev(config, tx, &user, &body),
+                "/next" => endpoints::handle_phase_next(config, tx, &user, &body),
                 _ => Ok(not_found("Not found.")),
             }
         } else {
             // Assume everything else is a GET request.
             match url_inner.as_ref() {
-                "" | "/" => endpoints::handle_index(config, tx, &user),
+                "" | "/" => endpoints::handle_index(config, tx, &user, &query),
+                "/my-vote" => endpoints::handle_my_vote(config, tx, &user),
+                "/my-vote.json" => endpoints::handle_my_vote_json(tx, &user),
+                "/phase" => endpoints::handle_phase(tx),
+                "/phase.json" => endpoints::handle_phase_json(tx),
+                "/teams.json" => endpoints::handle_teams_json(config, tx),
+                "/my-rank" => endpoints::handle_my_rank(config, tx, &user),
+                "/team-feedback" => endpoints::handle_team_feedback_form(config, tx, &user, &query),
+                "/results.json" => endpoints::handle_results_json(config, tx, &user),
+                "/results.csv" => endpoints::handle_results_csv(config, tx, &user),
+                "/dataset.json" => endpoints::handle_dataset_json(config, tx, &user),
+                "/vote-timeline.json" => endpoints::handle_vote_timeline_json(tx, &user),
+                "/backup.sqlite" => endpoints::handle_backup(config, raw_connection, &user),
+                "/integrity" => endpoints::handle_integrity(config, tx, &user),
+                "/audit" => endpoints::handle_audit_log(config, tx, &user),
+                "/verify-vote" => endpoints::handle_verify_vote(config, tx, &user, &query),
                 _ => Ok(not_found("Not found.")),
             }
         }
@@ -226,13 +784,60 @@ fn handle_request(
 /// SQLite does not support concurrent writes, but we do spawn multiple server
 /// threads. It might happen that one of them encounters a concurrency error and
 /// needs to restart the transaction, try that a few times before finally gving up.
-fn with_transaction<F>(connection: &mut db::Connection, mut f: F) -> db::Result<Response>
+///
+/// Transactions that may write should set `is_write`. They take the write lock
+/// up front with `BEGIN IMMEDIATE`. A plain `BEGIN` only takes it at the first
+/// write, and if another thread committed after our first read, SQLite fails
+/// right away with "database is locked", without waiting for the busy timeout,
+/// because our snapshot is stale. That was the reason that more than one
+/// handler thread used to fail frequently.
+fn with_transaction<F>(
+    config: &Config,
+    raw_connection: &sqlite::Connection,
+    connection: &mut db::Connection,
+    is_write: bool,
+    mut f: F,
+) -> db::Result<Response>
 where
     F: FnMut(&mut db::Transaction) -> db::Result<Response>,
 {
+    let max_attempts = config.database.max_retries.unwrap_or(MAX_RETRIES) as usize + 1;
+    let should_retry = |err: &sqlite::Error, attempt: usize| {
+        println!(
+            "Database is locked (attempt {} of {max_attempts}): {err:?}",
+            attempt + 1
+        );
+        // The database is locked by a writer. Retry if we haven't retried too
+        // many times already.
+        attempt + 1 < max_attempts
+    };
+    let busy_response = |attempt: usize| {
+        service_unavailable(format!(
+            "The database is busy (attempt {} of {max_attempts}), \
+            wait a few seconds and try again.",
+            attempt + 1
+        ))
+    };
+
     for attempt in 0.. {
-        let mut tx = connection.begin()?;
-        match f(&mut tx) {
+        let begin_result = if is_write {
+            db_ext::begin_immediate(raw_connection, connection)
+        } else {
+            connection.begin()
+        };
+        let mut tx = match begin_result.map_err(db_ext::Error::from) {
+            Ok(tx) => tx,
+            Err(db_ext::Error::Busy(err)) => {
+                if should_retry(&err, attempt) {
+                    continue;
+                }
+                return Ok(busy_response(attempt));
+            }
+            Err(err) => return Err(err.into_inner()),
+        };
+        let result = f(&mut tx);
+        db_ext::reset_statements(raw_connection);
+        match result.map_err(db_ext::Error::from) {
             Ok(response) => {
                 // Commit on success responses (we assume redirects to be success
                 // as well, for example for use after submitting a form). If we
@@ -246,54 +851,104 @@ where
                 }
                 return Ok(response);
             }
-            Err(err) if err.code == Some(5) => {
+            Err(db_ext::Error::Busy(err)) => {
                 tx.rollback()?;
-                println!("Database is locked (attempt {}): {err:?}", attempt + 1);
-                // The database is locked by a writer. Retry if we haven't
-                // retried too many times already.
-                if attempt + 1 < 6 {
+                if should_retry(&err, attempt) {
                     continue;
-                } else {
-                    return Ok(service_unavailable(
-                        "The database is busy, wait a few seconds and try again.",
-                    ));
                 }
+                return Ok(busy_response(attempt));
             }
             Err(err) => {
                 // Try to roll back, but if it doesn't work, we are going to
                 // open a new connection anyway.
                 let _ = tx.rollback();
-                return Err(err);
+                return Err(err.into_inner());
             }
         }
     }
     unreachable!("The number of continuations is bounded.");
 }
 
-fn serve_until_error(config: &Config, connection: &mut db::Connection, server: &Server) {
+/// Generate an id for a request, to find the request in the logs.
+///
+/// The ids are short so people can read them out, they only need to be unique
+/// enough to tell apart the requests around the same time.
+fn new_request_id() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(REQUEST_COUNT.fetch_add(1, Ordering::Relaxed));
+    format!("{:08x}", hasher.finish() as u32)
+}
+
+fn serve_until_error(
+    config: &Config,
+    raw_connection: &sqlite::Connection,
+    connection: &mut db::Connection,
+    rate_limiter: Option<&RateLimiter>,
+    metrics: Option<&Metrics>,
+    requests: &Mutex<Receiver<Request>>,
+) {
     loop {
         let mut fatal_error = None;
-        let mut request = server.recv().unwrap();
+        let mut request = requests
+            .lock()
+            .unwrap()
+            .recv()
+            .expect("The request dispatcher should outlive the handler threads.");
         let start_time = Instant::now();
+        let request_id = new_request_id();
 
-        let mut log_line = "Unparsed request".to_string();
-        let response = match handle_request(config, connection, &mut request, &mut log_line) {
+        let mut log = RequestLog::new(&request);
+        let response = match handle_request(
+            config,
+            raw_connection,
+            connection,
+            rate_limiter,
+            metrics,
+            &mut request,
+            &mut log,
+        ) {
             Ok(resp) => {
-                println!(
-                    "{log_line} -> {} [{:.3} ms]",
-                    resp.status_code().0,
-                    (start_time.elapsed().as_micros() as f32) * 1e-3
-                );
+                let elapsed = start_time.elapsed();
+                let status = resp.status_code().0;
+                if let Some(metrics) = metrics {
+                    metrics.record_request(status, elapsed);
+                }
+                println!("{}", log.format(config, &request_id, status, elapsed, None));
+                match config.server.slow_request_ms {
+                    Some(threshold) if elapsed.as_millis() > threshold as u128 => {
+                        let n = SLOW_REQUEST_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+                        println!(
+                            "{request_id} WARN Slow request: {:4?} {} took {:.3} ms, \
+                            threshold is {threshold} ms ({n} slow requests so far).",
+                            log.method,
+                            log.url,
+                            (elapsed.as_micros() as f32) * 1e-3
+                        );
+                    }
+                    _ => {}
+                }
                 resp
             }
             Err(err) => {
                 // Some unrecoverable error happened.
-                println!("{log_line} -> Error: {err:?}");
+                let elapsed = start_time.elapsed();
+                if let Some(metrics) = metrics {
+                    metrics.record_request(500, elapsed);
+                }
+                let err_str = format!("{err:?}");
+                println!(
+                    "{}",
+                    log.format(config, &request_id, 500, elapsed, Some(&err_str))
+                );
                 fatal_error = Some(err);
-                internal_error("Internal server error.")
+                internal_error(format!(
+                    "Internal server error. If this keeps happening, \
+                    please tell the organizers about error reference {request_id}."
+                ))
             }
         };
 
+        let response = compress_response(&request, response);
         if let Err(err) = request.respond(response) {
             println!("Error writing response: {err:?}");
         }
@@ -304,29 +959,209 @@ fn serve_until_error(config: &Config, connection: &mut db::Connection, server: &
     }
 }
 
+/// Return whether the `Accept-Encoding` header value includes gzip.
+fn accepts_gzip(accept_encoding: &str) -> bool {
+    accept_encoding.split(',').any(|item| {
+        let mut parts = item.split(';').map(|part| part.trim());
+        let coding = parts.next().unwrap_or("");
+        // A quality of zero means "not acceptable", e.g. "gzip;q=0".
+        let is_refused = parts.any(|param| {
+            param
+                .strip_prefix("q=")
+                .and_then(|q| q.parse::<f32>().ok())
+                .map_or(false, |q| q == 0.0)
+        });
+        (coding.eq_ignore_ascii_case("gzip") || coding == "*") && !is_refused
+    })
+}
+
+/// Gzip the body of text responses, if the client accepts that.
+///
+/// We do this here rather than in `respond_html` and friends, so the handlers
+/// don't need to know about the request headers. The index page is mostly
+/// repetitive markup, it shrinks by a lot, which helps on a crowded wifi.
+fn compress_response(request: &Request, response: Response) -> Response {
+    let content_type = response
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Content-Type"))
+        .map(|h| h.value.as_str().to_string())
+        .unwrap_or_default();
+    let is_text = content_type.starts_with("text/") || content_type.starts_with("application/json");
+    if !is_text {
+        return response;
+    }
+
+    let vary = Header::from_bytes(&b"Vary"[..], &b"Accept-Encoding"[..]).unwrap();
+    let is_accepted = request
+        .headers()
+        .iter()
+        .any(|h| h.field.equiv("Accept-Encoding") && accepts_gzip(h.value.as_str()));
+    let is_large = response.data_length().unwrap_or(0) >= MIN_COMPRESS_LEN;
+    let is_encoded = response
+        .headers()
+        .iter()
+        .any(|h| h.field.equiv("Content-Encoding"));
+    if !is_accepted || !is_large || is_encoded {
+        return response.with_header(vary);
+    }
+
+    let status = response.status_code();
+    let mut headers = response.headers().to_vec();
+    let body = gzip::compress(response.into_reader().get_ref());
+    headers.push(Header::from_bytes(&b"Content-Encoding"[..], &b"gzip"[..]).unwrap());
+    headers.push(vary);
+    let n_bytes = body.len();
+    Response::new(status, headers, Cursor::new(body), Some(n_bytes), None)
+}
+
+/// Listen on a `host:port`, or on a Unix domain socket for `unix:` and a path.
+fn bind_server(listen: &str) -> Result<Server, Box<dyn Error + Send + Sync + 'static>> {
+    match listen.strip_prefix("unix:") {
+        Some(path) => bind_unix_socket(Path::new(path)),
+        None => Server::http(listen),
+    }
+}
+
+/// Listen on a Unix domain socket, creating the socket file.
+///
+/// An instance that did not shut down cleanly leaves its socket file behind,
+/// and then binding would fail, so we remove stale sockets first. If another
+/// instance still accepts connections on it, that is an address in use.
+fn bind_unix_socket(path: &Path) -> Result<Server, Box<dyn Error + Send + Sync + 'static>> {
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => {
+            if UnixStream::connect(path).is_ok() {
+                return Err(std::io::Error::from(ErrorKind::AddrInUse).into());
+            }
+            std::fs::remove_file(path)?;
+        }
+        // Not a socket, so probably a mistake in the config, don't delete it.
+        Ok(_) => {
+            return Err(std::io::Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{path:?} exists and is not a socket"),
+            )
+            .into());
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => return Err(err.into()),
+    }
+    let listener = UnixListener::bind(path)?;
+    // The proxy usually runs as a different user, so the group can connect
+    // too, but others can't, they would bypass the authentication.
+    std::fs::set_permissions(path, Permissions::from_mode(0o660))?;
+    Server::from_listener(listener, None)
+}
+
+/// Hand the requests of one server to the handler threads, until that fails.
+///
+/// If all threads of a kind are gone, they panicked, and there is nothing left
+/// to serve the request.
+fn dispatch_requests(
+    server: &Server,
+    n_readers: usize,
+    write_sender: &Sender<Request>,
+    read_sender: &Sender<Request>,
+) {
+    loop {
+        let request = match server.recv() {
+            Ok(request) => request,
+            Err(err) => {
+                println!("Failed to receive request: {err}");
+                return;
+            }
+        };
+        let sender = if n_readers > 0 && request.method() == &Method::Get {
+            read_sender
+        } else {
+            write_sender
+        };
+        if sender.send(request).is_err() {
+            return;
+        }
+    }
+}
+
+/// Remove the socket files of the `unix:` addresses.
+///
+/// If we get killed, the socket stays behind, see `bind_unix_socket`.
+fn remove_unix_sockets(addresses: &[String]) {
+    for path in addresses.iter().filter_map(|a| a.strip_prefix("unix:")) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Turn a failure to start listening into an exit code and message.
+///
+/// The most common cause is another instance that is still running, so we
+/// give that one a distinct exit code that systemd and operators can check for.
+fn describe_bind_error(listen: &str, err: &(dyn Error + 'static)) -> (i32, String) {
+    match err.downcast_ref::<std::io::Error>() {
+        Some(io_err) if io_err.kind() == ErrorKind::AddrInUse => (
+            EXIT_ADDR_IN_USE,
+            format!("Failed to listen: address already in use: {listen}"),
+        ),
+        _ => (
+            EXIT_BIND_FAILED,
+            format!("Failed to listen on {listen}: {err}"),
+        ),
+    }
+}
+
 fn main() {
     let config = Arc::new(load_config());
 
     let n_threads = config.server.num_threads as usize;
-    let server = Arc::new(Server::http(&config.server.listen).unwrap());
-    let mut guards = Vec::with_capacity(n_threads);
+    let n_readers = config.server.num_reader_threads as usize;
+    let mut servers = Vec::with_capacity(config.server.listen.addresses().len());
+    for listen in config.server.listen.addresses() {
+        match bind_server(listen) {
+            Ok(server) => servers.push(server),
+            Err(err) => {
+                let (exit_code, message) = describe_bind_error(listen, err.as_ref());
+                eprintln!("{message}");
+                // Don't leave behind the sockets that we did bind.
+                remove_unix_sockets(&config.server.listen.addresses()[..servers.len()]);
+                std::process::exit(exit_code);
+            }
+        }
+    }
     let init_mutex = Arc::new(Mutex::new(()));
+    let rate_limiter = config
+        .rate_limit
+        .as_ref()
+        .map(|c| Arc::new(RateLimiter::new(c)));
+    let metrics = config
+        .server
+        .enable_metrics
+        .then(|| Arc::new(Metrics::new()));
+
+    // We open a fresh connection on the main thread once, so the schema exists
+    // by the time the readers start, they can't create it themselves.
+    {
+        let raw_connection = sqlite::open(&config.database.path).expect("Failed to open database");
+        init_database(&config, &raw_connection).expect("Failed to initialize database.");
+    }
 
-    // In theory everything should work with more server threads. And it does,
-    // with 2 or 3, but with 4 or more threads, requests frequently get error 5
-    // "database is locked" from SQLite. Printf debugging shows that all
-    // transactions that get started also commit. But still, something is
-    // holding on to the write lock? What's also really strange, it happens
-    // frequently for 4 threads (~1 in 3 requests), while I haven't been able to
-    // reproduce at all with 3 threads. But just to be sure, just do one.
-    assert_eq!(n_threads, 1, "Currently only 1 thread works well.");
+    // The writer threads handle all requests that may modify the database, and
+    // when there are no readers, all other requests as well. Readers handle
+    // GET requests on read-only connections, in WAL mode they do not contend
+    // with the writer, so e.g. a big screen that reloads the page frequently
+    // does not slow down voters.
+    let (write_sender, write_receiver) = mpsc::channel::<Request>();
+    let (read_sender, read_receiver) = mpsc::channel::<Request>();
+    let write_receiver = Arc::new(Mutex::new(write_receiver));
+    let read_receiver = Arc::new(Mutex::new(read_receiver));
 
     for _ in 0..n_threads {
-        let server = server.clone();
         let config = config.clone();
         let init_mutex = init_mutex.clone();
+        let requests = write_receiver.clone();
+        let rate_limiter = rate_limiter.clone();
+        let metrics = metrics.clone();
 
-        let guard = thread::spawn(move || {
+        thread::spawn(move || {
             loop {
                 // The database connections need to be opened sequentially, because
                 // SQLite supports only a single writer at a time. If we let all
@@ -340,24 +1175,85 @@ fn main() {
                 let db_lock = init_mutex.lock().unwrap();
                 let raw_connection =
                     sqlite::open(&config.database.path).expect("Failed to open database");
-                let mut connection =
-                    init_database(&raw_connection).expect("Failed to initialize database.");
+                let mut connection = init_database(&config, &raw_connection)
+                    .expect("Failed to initialize database.");
                 std::mem::drop(db_lock);
 
                 // Handle requests until we encounter a database error.
                 // At that point we loop and open a fresh connection.
-                serve_until_error(&config, &mut connection, &server);
+                serve_until_error(
+                    &config,
+                    &raw_connection,
+                    &mut connection,
+                    rate_limiter.as_deref(),
+                    metrics.as_deref(),
+                    &requests,
+                );
             }
         });
-        guards.push(guard);
     }
 
-    println!(
-        "Serving on http://{}{} ...",
-        config.server.listen, config.server.prefix
-    );
+    if config.app.evaluation_deadline.is_some() {
+        let config = config.clone();
+        let init_mutex = init_mutex.clone();
+        thread::spawn(move || advance_phase_at_deadline(&config, &init_mutex));
+    }
+
+    if let Some(interval_secs) = config.database.checkpoint_interval_secs {
+        let config = config.clone();
+        let init_mutex = init_mutex.clone();
+        thread::spawn(move || checkpoint_periodically(&config, &init_mutex, interval_secs));
+    }
+
+    for _ in 0..n_readers {
+        let config = config.clone();
+        let requests = read_receiver.clone();
+        let metrics = metrics.clone();
+
+        thread::spawn(move || loop {
+            let flags = sqlite::OpenFlags::new().set_read_only();
+            let mut raw_connection =
+                sqlite::Connection::open_with_flags(&config.database.path, flags)
+                    .expect("Failed to open database");
+            init_reader_connection(&config, &mut raw_connection)
+                .expect("Failed to initialize read-only connection.");
+            let mut connection = db::Connection::new(&raw_connection);
+            // Readers only serve GET requests, which are not rate limited.
+            serve_until_error(
+                &config,
+                &raw_connection,
+                &mut connection,
+                None,
+                metrics.as_deref(),
+                &requests,
+            );
+        });
+    }
+
+    for listen in config.server.listen.addresses() {
+        match listen.strip_prefix("unix:") {
+            Some(path) => println!("Serving {} on Unix socket {path} ...", config.server.prefix),
+            None => println!("Serving on http://{listen}{} ...", config.server.prefix),
+        }
+    }
 
-    for guard in guards.drain(..) {
-        guard.join().unwrap();
+    // Every address gets a thread that dispatches its requests to the shared
+    // handler threads. When one of them stops, we exit, rather than serve only
+    // some of the addresses. We don't wait for the handler threads, the other
+    // dispatchers still hold senders, so the handlers would wait forever.
+    let (stopped_sender, stopped_receiver) = mpsc::channel::<()>();
+    for server in servers {
+        let write_sender = write_sender.clone();
+        let read_sender = read_sender.clone();
+        let stopped_sender = stopped_sender.clone();
+        thread::spawn(move || {
+            dispatch_requests(&server, n_readers, &write_sender, &read_sender);
+            let _ = stopped_sender.send(());
+        });
     }
+    let _ = stopped_receiver.recv();
+
+    remove_unix_sockets(config.server.listen.addresses());
+    eprintln!("Stopped serving requests, exiting.");
+    std::process::exit(EXIT_SERVER_STOPPED);
 }
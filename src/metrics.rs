@@ -0,0 +1,93 @@
+// Hack-o-matic -- A webapp for facilitating remote and on-site hackathons
+// Copyright 2024 Chorus One
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Request counters and latencies, for Prometheus to scrape at `/metrics`.
+//!
+//! All handler threads record into the same `Metrics`, so everything in here
+//! is an atomic. We don't need consistent snapshots, so relaxed is fine.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// The upper bounds of the latency histogram buckets, in seconds.
+const DURATION_BUCKETS: [f64; 12] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0,
+];
+
+/// Status codes are three digits, the first one is between 1 and 5.
+const MIN_STATUS: u16 = 100;
+const MAX_STATUS: u16 = 599;
+
+pub struct Metrics {
+    /// The number of requests per status code, indexed by `status - MIN_STATUS`.
+    requests_by_status: Vec<AtomicU64>,
+
+    /// The number of requests per bucket, the last one is for slower requests.
+    ///
+    /// Unlike in the output, these are not cumulative.
+    duration_buckets: [AtomicU64; DURATION_BUCKETS.len() + 1],
+    duration_sum_us: AtomicU64,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics {
+            requests_by_status: (MIN_STATUS..=MAX_STATUS)
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+            duration_buckets: Default::default(),
+            duration_sum_us: AtomicU64::new(0),
+        }
+    }
+
+    /// Count a handled request, with the time it took to handle it.
+    pub fn record_request(&self, status: u16, duration: Duration) {
+        let status = status.clamp(MIN_STATUS, MAX_STATUS);
+        self.requests_by_status[(status - MIN_STATUS) as usize].fetch_add(1, Ordering::Relaxed);
+
+        let seconds = duration.as_secs_f64();
+        let bucket = DURATION_BUCKETS
+            .iter()
+            .position(|le| seconds <= *le)
+            .unwrap_or(DURATION_BUCKETS.len());
+        self.duration_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.duration_sum_us
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Append the counters in the Prometheus text format.
+    pub fn format(&self, out: &mut String) {
+        out.push_str("# HELP hackomatic_requests_total Requests handled, by status code.\n");
+        out.push_str("# TYPE hackomatic_requests_total counter\n");
+        for (status, n) in (MIN_STATUS..).zip(&self.requests_by_status) {
+            let n = n.load(Ordering::Relaxed);
+            if n > 0 {
+                writeln!(out, "hackomatic_requests_total{{status=\"{status}\"}} {n}").unwrap();
+            }
+        }
+
+        out.push_str("# HELP hackomatic_request_duration_seconds Time to handle a request.\n");
+        out.push_str("# TYPE hackomatic_request_duration_seconds histogram\n");
+        let mut n_total = 0;
+        for (i, n) in self.duration_buckets.iter().enumerate() {
+            n_total += n.load(Ordering::Relaxed);
+            let le = match DURATION_BUCKETS.get(i) {
+                Some(le) => le.to_string(),
+                None => "+Inf".to_string(),
+            };
+            writeln!(
+                out,
+                "hackomatic_request_duration_seconds_bucket{{le=\"{le}\"}} {n_total}"
+            )
+            .unwrap();
+        }
+        let sum_seconds = self.duration_sum_us.load(Ordering::Relaxed) as f64 * 1e-6;
+        writeln!(out, "hackomatic_request_duration_seconds_sum {sum_seconds}").unwrap();
+        writeln!(out, "hackomatic_request_duration_seconds_count {n_total}").unwrap();
+    }
+}
@@ -0,0 +1,87 @@
+// Hack-o-matic -- A webapp for facilitating remote and on-site hackathons
+// Copyright 2024 Chorus One
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! A token bucket per user, to stop scripts from flooding us with requests.
+//!
+//! The checks in the handlers, such as `max_teams_per_creator`, are not safe
+//! against a script that fires many requests at once. Rather than making every
+//! check race-free, we limit how fast a single user can make changes.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::RateLimitConfig;
+
+/// How often to drop the buckets of users who have been quiet for a while.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Bucket {
+    /// The number of requests the user can make right now, up to `burst`.
+    tokens: f64,
+    updated_at: Instant,
+}
+
+struct Buckets {
+    by_email: HashMap<String, Bucket>,
+    pruned_at: Instant,
+}
+
+pub struct RateLimiter {
+    burst: f64,
+    tokens_per_second: f64,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> RateLimiter {
+        RateLimiter {
+            burst: config.burst as f64,
+            tokens_per_second: config.per_minute as f64 / 60.0,
+            buckets: Mutex::new(Buckets {
+                by_email: HashMap::new(),
+                pruned_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take a token from the bucket of the user, return false if it is empty.
+    pub fn try_acquire(&self, email: &str) -> bool {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        // A bucket that refilled completely is the same as no bucket, so we
+        // can drop it. Without this, the map would keep every user who ever
+        // made a request, for the entire event.
+        if now.duration_since(buckets.pruned_at) > PRUNE_INTERVAL {
+            buckets
+                .by_email
+                .retain(|_, bucket| self.refill(bucket, now) < self.burst);
+            buckets.pruned_at = now;
+        }
+
+        let bucket = buckets.by_email.entry(email.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            updated_at: now,
+        });
+        bucket.tokens = self.refill(bucket, now);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Return the number of tokens that the bucket has at time `now`.
+    fn refill(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        (bucket.tokens + elapsed * self.tokens_per_second).min(self.burst)
+    }
+}
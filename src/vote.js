@@ -1,49 +1,64 @@
 function validate() {
-    const maxPoints = Math.floor(Math.sqrt(coinsToSpend));
     var allOk = true;
-    var spent = 0.0;
 
-    for (const inputBox of inputBoxes) {
-        const span = document.getElementById(inputBox.id.replace("input", "cost"));
-        const n = Number(inputBox.value);
-        if (Number.isSafeInteger(n) && Number.isSafeInteger(n * n)) {
-            spent += n * n;
-            if (n == 0) {
-                inputBox.classList.remove("nonzero");
-                span.innerText = "";
-            } else if (n > maxPoints) {
-                inputBox.classList.add("nonzero");
-                span.innerText = `Too many points! You can give at most ${maxPoints} points per team.`;
+    // Every category has its own budget, coins can't be moved between them.
+    for (const category of voteCategories) {
+        // The cost of n points is n to the power costExponent, see `vote_cost`.
+        // The server computes the max, it may be capped, see `get_max_points`.
+        const maxPoints = category.maxPoints;
+        var spent = 0.0;
+
+        for (const inputBox of category.inputBoxes) {
+            const span = document.getElementById(inputBox.id.replace("input", "cost"));
+            const n = Number(inputBox.value);
+            const cost = Math.abs(n) ** category.costExponent;
+            if (Number.isSafeInteger(n) && Number.isSafeInteger(cost)) {
+                spent += cost;
+                if (n == 0) {
+                    inputBox.classList.remove("nonzero");
+                    span.innerText = "";
+                } else if (n > maxPoints) {
+                    inputBox.classList.add("nonzero");
+                    span.innerText = `Too many points! You can give at most ${maxPoints} points per team.`;
+                } else {
+                    inputBox.classList.add("nonzero");
+                    span.innerText = cost == 1 ? "(1 coin)" : `(${cost} coins)`;
+                }
             } else {
-                inputBox.classList.add("nonzero");
-                span.innerText = `(${n * n} coins)`;
+                allOk = false;
+                span.innerText = "Must be an integer!";
             }
-        } else {
-            allOk = false;
-            span.innerText = "Must be an integer!";
         }
-    }
 
-    const coinsLeft = coinsToSpend - spent;
+        const coinsLeft = category.coinsToSpend - spent;
+        const coinsLeftSpan = document.getElementById(
+            category.id === null ? "coins-left" : `coins-left-${category.id}`
+        );
+        coinsLeftSpan.innerText = coinsLeft == 1 ? "1 coin" : `${coinsLeft} coins`;
+        allOk = allOk && (coinsLeft >= 0);
+    }
 
-    const coinsLeftSpan = document.getElementById("coins-left");
     const submitButton = document.getElementById("submit-vote");
-    coinsLeftSpan.innerText = coinsLeft == 1 ? "1 coin" : `${coinsLeft} coins`;
-
-    submitButton.disabled = (coinsLeft < 0) || !allOk;
+    submitButton.disabled = !allOk;
 }
 
 function initialize() {
-    for (const inputBox of inputBoxes) {
-        inputBox.addEventListener("input", (event) => {
-            validate();
-            voteMessage.innerText = (
-                "You have unsaved changes. " +
-                "Click the button above to submit. " +
-                "You can still change your vote after you submit, " +
-                "as long as voting is open."
-            );
-        });
+    for (const category of voteCategories) {
+        // Without categories, the ids don't include the category.
+        category.inputBoxes = category.teamIds.map((teamId) => document.getElementById(
+            category.id === null ? `input${teamId}` : `input${category.id}-${teamId}`
+        ));
+        for (const inputBox of category.inputBoxes) {
+            inputBox.addEventListener("input", (event) => {
+                validate();
+                voteMessage.innerText = (
+                    "You have unsaved changes. " +
+                    "Click the button above to submit. " +
+                    "You can still change your vote after you submit, " +
+                    "as long as voting is open."
+                );
+            });
+        }
     }
     validate();
 }